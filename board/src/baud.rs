@@ -0,0 +1,93 @@
+//! Bit rates that follow clock changes
+//!
+//! UARTHS divides the CPU clock and SPI0 its own clock down to a bit rate, so changing a PLL or
+//! divider silently changes the rate of both. `keep_uarths_baud` and `keep_spi0_rate` remember the
+//! rate the driver set up and re-derive the divider from a clock listener.
+//!
+//! SPI0 only takes a new divider while it is disabled, and disabling it would cut off a transfer
+//! in flight. A divider for a busy SPI0 is held back until `sync_spi0_rate`, which the SPI0 driver
+//! calls before starting a transfer.
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use k210_hal::pac;
+
+use crate::sysctl::{self, clock, ClockChange, SysctlError};
+
+/** Rates to keep, zero if none */
+static UARTHS_BAUD: AtomicU32 = AtomicU32::new(0);
+static SPI0_RATE: AtomicU32 = AtomicU32::new(0);
+
+/** SPI0 divider waiting for the bus to go idle, zero if none */
+static SPI0_PENDING: AtomicU32 = AtomicU32::new(0);
+
+static SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+
+/** UARTHS divider for `baud` from a CPU clock of `cpu_freq` (from Kendryte uarths.c) */
+pub fn uarths_divider(cpu_freq: u32, baud: u32) -> u32 {
+    (cpu_freq / baud).max(1) - 1
+}
+
+/** SPI baud divider for `rate` from an SPI clock of `spi_freq` (from Kendryte spi.c) */
+/* `Ord::clamp` needs Rust 1.50 */
+#[allow(clippy::manual_clamp)]
+pub fn spi_divider(spi_freq: u32, rate: u32) -> u32 {
+    (spi_freq / rate).max(2).min(65534)
+}
+
+/** Keep UARTHS at `baud` across clock changes, it should already be configured for it */
+pub fn keep_uarths_baud(baud: u32) -> Result<(), SysctlError> {
+    UARTHS_BAUD.store(baud, Ordering::Relaxed);
+    subscribe()
+}
+
+/** Keep SPI0 at `rate` across clock changes, it should already be configured for it */
+pub fn keep_spi0_rate(rate: u32) -> Result<(), SysctlError> {
+    SPI0_RATE.store(rate, Ordering::Relaxed);
+    subscribe()
+}
+
+fn subscribe() -> Result<(), SysctlError> {
+    if !SUBSCRIBED.swap(true, Ordering::AcqRel) {
+        if let Err(err) = sysctl::clock_subscribe(on_clock_change) {
+            SUBSCRIBED.store(false, Ordering::Release);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+fn on_clock_change(change: &ClockChange) {
+    let baud = UARTHS_BAUD.load(Ordering::Relaxed);
+    if baud != 0 && change.affects(clock::CPU) {
+        if let Ok(freq) = change.after.freq(clock::CPU) {
+            let uarths = unsafe { &*pac::UARTHS::ptr() };
+            uarths
+                .div
+                .write(|w| unsafe { w.bits(uarths_divider(freq, baud)) });
+        }
+    }
+
+    let rate = SPI0_RATE.load(Ordering::Relaxed);
+    if rate != 0 && change.affects(clock::SPI0) {
+        if let Ok(freq) = change.after.freq(clock::SPI0) {
+            let divider = spi_divider(freq, rate);
+            SPI0_PENDING.store(divider, Ordering::Release);
+            sync_spi0_rate();
+        }
+    }
+}
+
+/** Write a SPI0 divider held back by a clock change, if SPI0 is idle now */
+pub fn sync_spi0_rate() {
+    let divider = SPI0_PENDING.swap(0, Ordering::AcqRel);
+    if divider == 0 {
+        return;
+    }
+    let spi = unsafe { &*pac::SPI0::ptr() };
+    if spi.ssienr.read().bits() == 0 {
+        spi.baudr.write(|w| unsafe { w.bits(divider) });
+    } else {
+        /* Unless a newer change got in first */
+        let _ = SPI0_PENDING.compare_exchange(0, divider, Ordering::AcqRel, Ordering::Relaxed);
+    }
+}
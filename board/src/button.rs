@@ -0,0 +1,245 @@
+//! Keys
+//!
+//! `Buttons` turns samples of the three CorgiDude keys into events: press and release after
+//! debouncing, long press, auto-repeat while held and double click. It only sees samples and
+//! timestamps, so it runs the same on the board (`Buttons::update`) and in host tests
+//! (`Buttons::sample`). Samples have to keep coming while a key is held, or long presses and
+//! repeats are late.
+use crate::def::io;
+use crate::gpio;
+use crate::gpiohs::{self, edge, gpiohs_handler};
+use crate::pins;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum key {
+    /** Direction 1 (IO15) */
+    DIR1,
+    /** Center (BOOT) */
+    CENTER,
+    /** Direction 2 (IO17) */
+    DIR2,
+}
+
+impl key {
+    pub const ALL: &'static [key] = &[key::DIR1, key::CENTER, key::DIR2];
+
+    pub fn pad(self) -> io {
+        pins::KEYS[self as usize].0
+    }
+
+    /** GPIOHS pin the key is routed to by `pins::subsystem::KEYS` */
+    pub fn gpiohs_pin(self) -> u8 {
+        pins::KEYS[self as usize].1
+    }
+
+    /** Whether the key is held down right now, without debouncing */
+    pub fn is_down(self) -> bool {
+        !gpiohs::get_pin(self.gpiohs_pin())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum action {
+    PRESS,
+    RELEASE,
+    /** Held for `Timing::long_press_ms` */
+    LONG_PRESS,
+    /** Still held, every `Timing::repeat_ms` after `Timing::repeat_delay_ms` */
+    REPEAT,
+    /** Released twice within `Timing::double_click_ms`, after the second release */
+    DOUBLE_CLICK,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub key: key,
+    pub action: action,
+    /** Time of the sample that caused the event, in ms */
+    pub time: u32,
+}
+
+/** Timing of the events, all in ms */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /** How long a new level has to hold before it counts */
+    pub debounce_ms: u32,
+    pub long_press_ms: u32,
+    /** From press to the first repeat */
+    pub repeat_delay_ms: u32,
+    /** Between repeats */
+    pub repeat_ms: u32,
+    /** Longest time from the first release to the second press of a double click */
+    pub double_click_ms: u32,
+}
+
+impl Timing {
+    pub const DEFAULT: Timing = Timing {
+        debounce_ms: 20,
+        long_press_ms: 1000,
+        repeat_delay_ms: 500,
+        repeat_ms: 100,
+        double_click_ms: 300,
+    };
+}
+
+/** Number of events `Buttons` keeps until they are read */
+pub const EVENT_QUEUE_LEN: usize = 16;
+
+/** Debouncing and timing state of one key */
+#[derive(Copy, Clone)]
+struct State {
+    /** Debounced level */
+    down: bool,
+    /** Last sampled level, and since when */
+    raw: bool,
+    raw_since: u32,
+    pressed_at: u32,
+    long_sent: bool,
+    next_repeat: u32,
+    /** Release time of a short click that may become a double click */
+    last_click: Option<u32>,
+}
+
+impl State {
+    const fn new() -> State {
+        State {
+            down: false,
+            raw: false,
+            raw_since: 0,
+            pressed_at: 0,
+            long_sent: false,
+            next_repeat: 0,
+            last_click: None,
+        }
+    }
+}
+
+/** Event source for all keys */
+pub struct Buttons {
+    timing: Timing,
+    states: [State; 3],
+    queue: [Option<Event>; EVENT_QUEUE_LEN],
+    head: usize,
+    len: usize,
+    dropped: usize,
+}
+
+impl Buttons {
+    pub const fn new(timing: Timing) -> Buttons {
+        Buttons {
+            timing,
+            states: [State::new(); 3],
+            queue: [None; EVENT_QUEUE_LEN],
+            head: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn timing(&self) -> &Timing {
+        &self.timing
+    }
+
+    /** Feed the level of `key` at time `now` (ms, may wrap) */
+    pub fn sample(&mut self, key: key, down: bool, now: u32) {
+        let timing = self.timing;
+        let mut state = self.states[key as usize];
+        if down != state.raw {
+            state.raw = down;
+            state.raw_since = now;
+        }
+        let settled = now.wrapping_sub(state.raw_since) >= timing.debounce_ms;
+
+        if settled && state.raw != state.down {
+            state.down = state.raw;
+            if state.down {
+                state.pressed_at = now;
+                state.long_sent = false;
+                state.next_repeat = now.wrapping_add(timing.repeat_delay_ms);
+                self.push(key, action::PRESS, now);
+            } else {
+                self.push(key, action::RELEASE, now);
+                if state.long_sent {
+                    state.last_click = None;
+                } else {
+                    match state.last_click {
+                        Some(release)
+                            if state.pressed_at.wrapping_sub(release) <= timing.double_click_ms =>
+                        {
+                            self.push(key, action::DOUBLE_CLICK, now);
+                            state.last_click = None;
+                        }
+                        _ => state.last_click = Some(now),
+                    }
+                }
+            }
+        } else if state.down {
+            let held = now.wrapping_sub(state.pressed_at);
+            if !state.long_sent && held >= timing.long_press_ms {
+                state.long_sent = true;
+                self.push(key, action::LONG_PRESS, now);
+            }
+            /* The wrapping difference is "negative" until the repeat is due */
+            if (now.wrapping_sub(state.next_repeat) as i32) >= 0 {
+                state.next_repeat = now.wrapping_add(timing.repeat_ms);
+                self.push(key, action::REPEAT, now);
+            }
+        }
+        self.states[key as usize] = state;
+    }
+
+    /** Feed a sample of every key, `down` tells whether a key is held */
+    pub fn sample_all<F: FnMut(key) -> bool>(&mut self, now: u32, mut down: F) {
+        for &key in key::ALL {
+            self.sample(key, down(key), now);
+        }
+    }
+
+    /** Sample the keys on the board */
+    pub fn update(&mut self, now: u32) {
+        self.sample_all(now, key::is_down)
+    }
+
+    /** Debounced level of a key */
+    pub fn is_down(&self, key: key) -> bool {
+        self.states[key as usize].down
+    }
+
+    /** Oldest event not read yet */
+    pub fn next_event(&mut self) -> Option<Event> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.queue[self.head].take();
+        self.head = (self.head + 1) % EVENT_QUEUE_LEN;
+        self.len -= 1;
+        event
+    }
+
+    /** Events lost because the queue was full */
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /** Queue an event, the newest ones are lost when nobody reads them */
+    fn push(&mut self, key: key, action: action, time: u32) {
+        if self.len == EVENT_QUEUE_LEN {
+            self.dropped += 1;
+            return;
+        }
+        self.queue[(self.head + self.len) % EVENT_QUEUE_LEN] = Some(Event { key, action, time });
+        self.len += 1;
+    }
+}
+
+/**
+ * Call `handler` with the GPIOHS pin of a key whenever the key changes, so the firmware can wait
+ * for interrupts instead of polling an idle keypad. The handler should just note the wakeup, the
+ * events still come from sampling.
+ */
+pub fn wake_on_keys(priority: u32, handler: gpiohs_handler) {
+    for &key in key::ALL {
+        gpiohs::set_direction(key.gpiohs_pin(), gpio::direction::INPUT);
+        gpiohs::set_irq(key.gpiohs_pin(), edge::BOTH, priority, handler);
+    }
+}
@@ -0,0 +1,115 @@
+//! Reference-counted clock gating
+//!
+//! A `ClockGuard` keeps a peripheral clock, and the APB bus it sits on, enabled for as long as it
+//! lives. Every clock counts its guards; the clock is gated again when the last guard is dropped,
+//! and its bus once no peripheral on it is clocked any more.
+//!
+//! Clocks enabled with `sysctl::clock_enable` directly are not counted. Their bus is left alone
+//! while they run, also when the last guard on the bus itself is dropped, but a guard on such a
+//! peripheral clock still gates it when dropped.
+//!
+//! The drivers in this crate are generic over their registers and enable their clocks through
+//! `Sysctl` directly. Guards are for code running on the SoC that shares clocks between parts
+//! started and stopped independently, or that switches profiles: `profile::set_profile` leaves
+//! guarded clocks and PLLs on.
+//!
+//! ```ignore
+//! let kpu = ClockGuard::new(clock::AI);
+//! profile::set_profile(profile::LOW_POWER)?; // the KPU keeps running
+//! drop(kpu); // now it is gated
+//! ```
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::sysctl::{self, clock};
+use crate::utils::cs;
+
+const CLOCK_COUNT: usize = clock::ALL.len();
+
+/* Only used as the initializer of `USERS`, every element gets its own copy */
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_USERS: AtomicUsize = AtomicUsize::new(0);
+
+/** Number of live guards per clock, indexed by `clock` */
+static USERS: [AtomicUsize; CLOCK_COUNT] = [NO_USERS; CLOCK_COUNT];
+
+/** Keeps a clock enabled while alive */
+pub struct ClockGuard {
+    clock: clock,
+}
+
+impl ClockGuard {
+    /** Enable `clock` and its bus, if this is their first user */
+    pub fn new(clock: clock) -> ClockGuard {
+        cs(|| {
+            if let Some(bus) = sysctl::clock_bus(clock) {
+                acquire(bus);
+            }
+            acquire(clock);
+        });
+        ClockGuard { clock }
+    }
+
+    pub fn clock(&self) -> clock {
+        self.clock
+    }
+}
+
+impl Clone for ClockGuard {
+    fn clone(&self) -> Self {
+        ClockGuard::new(self.clock)
+    }
+}
+
+impl Drop for ClockGuard {
+    fn drop(&mut self) {
+        let clock = self.clock;
+        cs(|| {
+            if is_bus(clock) {
+                release_bus(clock);
+            } else {
+                release(clock);
+            }
+            if let Some(bus) = sysctl::clock_bus(clock) {
+                release_bus(bus);
+            }
+        });
+    }
+}
+
+/** Number of live guards for a clock */
+pub fn users(clock: clock) -> usize {
+    USERS[clock as usize].load(Ordering::Relaxed)
+}
+
+fn acquire(clock: clock) {
+    if USERS[clock as usize].fetch_add(1, Ordering::Relaxed) == 0 {
+        sysctl::clock_enable(clock);
+    }
+}
+
+fn release(clock: clock) {
+    if USERS[clock as usize].fetch_sub(1, Ordering::Relaxed) == 1 {
+        sysctl::sysctl_clock_disable(clock);
+    }
+}
+
+/** Like `release`, but keeps the bus on while a peripheral behind it is still clocked */
+fn release_bus(bus: clock) {
+    if USERS[bus as usize].fetch_sub(1, Ordering::Relaxed) == 1 && !bus_in_use(bus) {
+        sysctl::sysctl_clock_disable(bus);
+    }
+}
+
+/** Whether peripheral clocks are attached to `clock` */
+fn is_bus(clock: clock) -> bool {
+    clock::ALL
+        .iter()
+        .any(|&peripheral| sysctl::clock_bus(peripheral) == Some(clock))
+}
+
+/** Whether any peripheral on `bus` is enabled, with or without a guard */
+fn bus_in_use(bus: clock) -> bool {
+    clock::ALL
+        .iter()
+        .any(|&clock| sysctl::clock_bus(clock) == Some(bus) && sysctl::clock_is_enabled(clock))
+}
@@ -0,0 +1,415 @@
+//! Clock configuration planner
+//!
+//! Given the frequencies some peripherals should run at, the planner picks PLL parameters (with
+//! `pll_compute::compute_params`), `threshold` dividers and `clock_select` muxes that come closest,
+//! and reports the achieved frequency and error of every target. Solving happens on a
+//! `ClockTree`, so plans can be computed off-target; `ClockPlan::apply` writes a finished plan to
+//! the hardware in one go.
+use crate::clock_tree::{divider, parent, route, ClockTree};
+use crate::pll_compute;
+use crate::regs::{Mmio, Registers};
+use crate::sysctl::{self, clock, clock_select, clock_source, pll, threshold, Sysctl, SysctlError};
+use crate::utils::cs;
+
+/** Highest PLL frequency the planner picks on its own, a PLL target may still ask for more */
+pub const PLL_MAX: u32 = 800_000_000;
+
+/** Desired frequency of a clock */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub clock: clock,
+    pub freq: u32,
+}
+
+impl Target {
+    pub const fn new(clock: clock, freq: u32) -> Target {
+        Target { clock, freq }
+    }
+}
+
+/** Outcome of the plan for a single target */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub clock: clock,
+    /** Requested frequency in Hz */
+    pub target: u32,
+    /** Frequency the plan ends up with in Hz */
+    pub achieved: u32,
+    /** Relative error in parts per million */
+    pub error_ppm: u32,
+}
+
+/** Complete clock configuration solved for a list of targets */
+pub struct ClockPlan<'a> {
+    targets: &'a [Target],
+    tree: ClockTree,
+}
+
+impl<'a> ClockPlan<'a> {
+    /** Solve `targets` starting from `base`
+     *
+     * PLLs, dividers and muxes that no target depends on keep their value from `base`. A target
+     * on a PLL itself pins that PLL, otherwise the PLL is chosen to minimize the total error of
+     * the clocks it feeds.
+     */
+    pub fn solve(base: &ClockTree, targets: &'a [Target]) -> Result<ClockPlan<'a>, SysctlError> {
+        let mut tree = *base;
+        for target in targets {
+            if target.freq == 0 {
+                return Err(SysctlError::InvalidTarget(target.clock, target.freq));
+            }
+        }
+
+        /* PLL2 may run from PLL0 or PLL1, so it comes last */
+        for &pll in &[pll::PLL0, pll::PLL1, pll::PLL2] {
+            solve_pll(&mut tree, targets, pll)?;
+        }
+        fit(&mut tree, targets)?;
+
+        /* Make sure every target resolves, `entries` relies on it */
+        for target in targets {
+            tree.freq(target.clock)?;
+        }
+        Ok(ClockPlan { targets, tree })
+    }
+
+    /** Clock tree the plan results in */
+    pub fn tree(&self) -> &ClockTree {
+        &self.tree
+    }
+
+    /** Achieved frequency and error of every target, in the order they were given */
+    pub fn entries(&self) -> impl Iterator<Item = Entry> + '_ {
+        self.targets.iter().map(move |target| {
+            /* Resolved while solving */
+            let achieved = self.tree.freq(target.clock).unwrap_or(0);
+            Entry {
+                clock: target.clock,
+                target: target.freq,
+                achieved,
+                error_ppm: error_ppm(target.freq, achieved),
+            }
+        })
+    }
+
+    /** Largest relative error of all targets in parts per million */
+    pub fn max_error_ppm(&self) -> u32 {
+        self.entries()
+            .map(|entry| entry.error_ppm)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /** Whether a target runs from `pll` */
+    fn needs_pll(&self, pll: pll) -> bool {
+        self.targets
+            .iter()
+            .any(|target| self.tree.uses_pll(target.clock, pll))
+    }
+
+    /** Write the plan to SYSCTL
+     *
+     * The CPU runs from IN0 and interrupts are disabled while the PLLs and dividers are
+     * reprogrammed; ACLK is switched to its planned source last. Clock listeners are notified
+     * once, after the whole plan is in place.
+     */
+    pub fn apply(&self) -> Result<(), SysctlError> {
+        self.apply_to(&Sysctl::new(Mmio))
+    }
+
+    /** `apply`, writing the plan through `sysctl` */
+    pub fn apply_to<R: Registers>(&self, sysctl: &Sysctl<R>) -> Result<(), SysctlError> {
+        let current = sysctl.clock_tree();
+        sysctl.clock_change(|| {
+            cs(|| {
+                sysctl.clock_set_clock_select(clock_select::ACLK, 0)?;
+
+                for &pll in &[pll::PLL0, pll::PLL1, pll::PLL2] {
+                    let source = match pll {
+                        pll::PLL2 => sysctl::pll2_cksel_to_source(
+                            self.tree.get_clock_select(clock_select::PLL2),
+                        )?,
+                        _ => clock_source::IN0,
+                    };
+                    let unchanged = self.tree.get_pll(pll) == current.get_pll(pll)
+                        && (pll != pll::PLL2
+                            || self.tree.get_clock_select(clock_select::PLL2)
+                                == current.get_clock_select(clock_select::PLL2));
+                    /* A PLL a profile powered down comes back up when the plan needs it */
+                    let revive = !sysctl.pll_is_powered(pll) && self.needs_pll(pll);
+                    if !unchanged || revive {
+                        sysctl.pll_set_params(pll, source, self.tree.get_pll(pll))?;
+                        /* pll_set_params hands ACLK back to PLL0 */
+                        sysctl.clock_set_clock_select(clock_select::ACLK, 0)?;
+                    }
+                }
+
+                for &which in threshold::ALL {
+                    sysctl.clock_set_threshold(which, self.tree.get_threshold(which))?;
+                }
+                for &which in clock_select::ALL {
+                    match which {
+                        clock_select::ACLK | clock_select::PLL2 => {}
+                        _ => sysctl
+                            .clock_set_clock_select(which, self.tree.get_clock_select(which))?,
+                    }
+                }
+
+                sysctl.clock_set_clock_select(
+                    clock_select::ACLK,
+                    self.tree.get_clock_select(clock_select::ACLK),
+                )
+            })
+        })
+    }
+}
+
+/** Solve `targets` against the current hardware configuration */
+pub fn plan(targets: &[Target]) -> Result<ClockPlan<'_>, SysctlError> {
+    ClockPlan::solve(&sysctl::clock_tree(), targets)
+}
+
+/** |achieved - target| / target in parts per million */
+pub fn error_ppm(target: u32, achieved: u32) -> u32 {
+    if target == 0 {
+        return u32::max_value();
+    }
+    let diff = u64::from(if achieved > target {
+        achieved - target
+    } else {
+        target - achieved
+    });
+    let ppm = (diff * 1_000_000 + u64::from(target) / 2) / u64::from(target);
+    if ppm > u64::from(u32::max_value()) {
+        u32::max_value()
+    } else {
+        ppm as u32
+    }
+}
+
+/** Clocks that are ACLK itself */
+fn is_aclk(clock: clock) -> bool {
+    match clock {
+        clock::CPU | clock::DMA | clock::FFT | clock::ACLK | clock::HCLK => true,
+        _ => false,
+    }
+}
+
+fn pll_clock(pll: pll) -> clock {
+    match pll {
+        pll::PLL0 => clock::PLL0,
+        pll::PLL1 => clock::PLL1,
+        pll::PLL2 => clock::PLL2,
+    }
+}
+
+fn pll_source(pll: pll) -> clock_source {
+    match pll {
+        pll::PLL0 => clock_source::PLL0,
+        pll::PLL1 => clock_source::PLL1,
+        pll::PLL2 => clock_source::PLL2,
+    }
+}
+
+/** Summed error of the targets matching `filter` */
+fn error_sum<F>(tree: &ClockTree, targets: &[Target], filter: F) -> Result<u64, SysctlError>
+where
+    F: Fn(&Target) -> bool,
+{
+    let mut sum = 0;
+    for target in targets.iter().filter(|target| filter(target)) {
+        sum += u64::from(error_ppm(target.freq, tree.freq(target.clock)?));
+    }
+    Ok(sum)
+}
+
+/**
+ * Call `f` with every PLL frequency that would give `target` exactly for some divider setting
+ * between `pll` and the target clock. Candidates outside of what the planner may pick are
+ * skipped.
+ */
+fn for_each_candidate<F>(target: &Target, pll: pll, mut f: F) -> Result<(), SysctlError>
+where
+    F: FnMut(u32) -> Result<(), SysctlError>,
+{
+    let (parent, divider) = route(target.clock);
+    let max = divider.threshold().map_or(0, |which| which.max());
+    /* Fixed division between the PLL and the divider of the clock */
+    let pre: &[u32] = match parent {
+        parent::SOURCE(source) if source == pll_source(pll) => &[1],
+        /* ACLK divides PLL0 by 2 << threshold */
+        parent::SOURCE(clock_source::ACLK) if pll == pll::PLL0 => &[2, 4, 8, 16],
+        parent::MUX(_) if pll == pll::PLL0 => &[1],
+        _ => &[],
+    };
+    for &pre in pre {
+        for value in 0..=max {
+            let freq = u64::from(target.freq) * u64::from(pre) * u64::from(divider.factor(value));
+            if freq > u64::from(PLL_MAX) {
+                break;
+            }
+            if freq >= u64::from(pll_compute::OUT_MIN) {
+                f(freq as u32)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/** Pick parameters for one PLL */
+fn solve_pll(tree: &mut ClockTree, targets: &[Target], pll: pll) -> Result<(), SysctlError> {
+    if let Some(target) = targets.iter().find(|target| target.clock == pll_clock(pll)) {
+        let freq_in = tree.pll_input_freq(pll)?;
+        let params = pll_compute::compute_params(freq_in, target.freq)
+            .ok_or(SysctlError::PllOutOfRange(pll, target.freq))?;
+        tree.set_pll(pll, params);
+        return Ok(());
+    }
+
+    /* Only PLL2 has a selectable input: IN0, PLL0 or PLL1 */
+    let selects: &[u8] = match pll {
+        pll::PLL2 => &[0, 1, 2],
+        _ => &[0],
+    };
+
+    /* (total error, frequency, tree) of the best candidate so far */
+    let mut best: Option<(u64, u32, ClockTree)> = None;
+    for &select in selects {
+        let mut trial = *tree;
+        if pll == pll::PLL2 {
+            trial.set_clock_select(clock_select::PLL2, select);
+        }
+        let freq_in = trial.pll_input_freq(pll)?;
+        for target in targets {
+            for_each_candidate(target, pll, |freq| {
+                let params = match pll_compute::compute_params(freq_in, freq) {
+                    Some(params) => params,
+                    None => return Ok(()),
+                };
+                trial.set_pll(pll, params);
+                fit(&mut trial, targets)?;
+                let error = error_sum(&trial, targets, |_| true)?;
+                let achieved = trial.pll_freq(pll)?;
+                /* On a tie, prefer the faster PLL */
+                let better = match best {
+                    Some((best_error, best_freq, _)) => {
+                        error < best_error || (error == best_error && achieved > best_freq)
+                    }
+                    None => true,
+                };
+                if better {
+                    best = Some((error, achieved, trial));
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    if let Some((error, _, found)) = best {
+        /* Keep the PLL as it is unless retuning it helps, e.g. when no target ends up behind it */
+        let mut kept = *tree;
+        let keep = fit(&mut kept, targets)
+            .and_then(|_| error_sum(&kept, targets, |_| true))
+            .map_or(false, |kept| kept <= error);
+        if !keep {
+            *tree = found;
+        }
+    }
+    Ok(())
+}
+
+/** Pick the dividers and muxes of all targets for the current PLL settings */
+fn fit(tree: &mut ClockTree, targets: &[Target]) -> Result<(), SysctlError> {
+    /* ACLK feeds the APB and memory dividers, settle it first */
+    if targets.iter().any(|target| is_aclk(target.clock)) {
+        let mut best: Option<(u64, u8, u32)> = None;
+        for select in 0..=clock_select::ACLK.max() {
+            let max = if select == 0 {
+                0
+            } else {
+                threshold::ACLK.max()
+            };
+            for value in 0..=max {
+                tree.set_clock_select(clock_select::ACLK, select);
+                tree.set_threshold(threshold::ACLK, value);
+                let error = error_sum(tree, targets, |target| is_aclk(target.clock))?;
+                if best.map_or(true, |(best_error, _, _)| error < best_error) {
+                    best = Some((error, select, value));
+                }
+            }
+        }
+        if let Some((_, select, value)) = best {
+            tree.set_clock_select(clock_select::ACLK, select);
+            tree.set_threshold(threshold::ACLK, value);
+        }
+    }
+
+    for &which in threshold::ALL {
+        let first = match targets
+            .iter()
+            .find(|target| route(target.clock).1.threshold() == Some(which))
+        {
+            Some(target) => target,
+            None => continue,
+        };
+        match route(first.clock).0 {
+            parent::MUX(mux) => {
+                let mut best: Option<(u64, u8, u32)> = None;
+                for select in 0..=mux.max() {
+                    tree.set_clock_select(mux, select);
+                    let (error, value) = fit_threshold(tree, targets, which)?;
+                    if best.map_or(true, |(best_error, _, _)| error < best_error) {
+                        best = Some((error, select, value));
+                    }
+                }
+                if let Some((_, select, value)) = best {
+                    tree.set_clock_select(mux, select);
+                    tree.set_threshold(which, value);
+                }
+            }
+            parent::SOURCE(_) => {
+                let (_, value) = fit_threshold(tree, targets, which)?;
+                tree.set_threshold(which, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/** Best value for one divider shared by all targets behind it, returns (error, value) */
+fn fit_threshold(
+    tree: &mut ClockTree,
+    targets: &[Target],
+    which: threshold,
+) -> Result<(u64, u32), SysctlError> {
+    let behind = |target: &Target| route(target.clock).1.threshold() == Some(which);
+    let original = tree.get_threshold(which);
+    let mut best: Option<(u64, u32)> = None;
+
+    for target in targets.iter().filter(|target| behind(target)) {
+        let (parent, divider) = route(target.clock);
+        let per_step = match divider {
+            divider::DOUBLE(_) => 2,
+            _ => 1,
+        };
+        /* Closest divider, then its neighbours in case rounding picked the wrong side */
+        let freq_in = u64::from(tree.parent_freq(parent)?);
+        let step = u64::from(target.freq) * per_step;
+        let ideal = ((freq_in + step / 2) / step).max(1) - 1;
+        let ideal = if ideal > u64::from(which.max()) {
+            which.max()
+        } else {
+            ideal as u32
+        };
+        for value in ideal.saturating_sub(1)..=(ideal + 1).min(which.max()) {
+            tree.set_threshold(which, value);
+            let error = error_sum(tree, targets, &behind)?;
+            if best.map_or(true, |(best_error, _)| error < best_error) {
+                best = Some((error, value));
+            }
+        }
+    }
+
+    tree.set_threshold(which, original);
+    Ok(best.unwrap_or((0, original)))
+}
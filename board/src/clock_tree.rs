@@ -0,0 +1,243 @@
+//! Host-computable model of the SYSCTL clock tree
+//!
+//! A `ClockTree` holds the raw values of the PLL, clock select and threshold registers. All
+//! frequencies are derived from those values without touching the hardware, so clock
+//! configurations can be evaluated (and tested) off-target. `sysctl::clock_tree` takes a
+//! snapshot of the live registers.
+use crate::pll_compute::Params;
+use crate::sysctl::{
+    clock, clock_select, clock_source, pll, pll2_cksel_to_source, threshold, SysctlError,
+};
+
+/** Number of `threshold` variants */
+pub const THRESHOLD_COUNT: usize = 27;
+
+/** Number of `clock_select` variants */
+pub const CLOCK_SELECT_COUNT: usize = 10;
+
+/** Where a clock takes its input from */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum parent {
+    /** Fixed clock source */
+    SOURCE(clock_source),
+    /** Mux between IN0 (select 0) and PLL0 (select 1) */
+    MUX(clock_select),
+}
+
+/** How a clock divides the frequency of its parent */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum divider {
+    /** Parent frequency is passed through */
+    NONE,
+    /** parent / (threshold + 1) */
+    ADD_ONE(threshold),
+    /** parent / ((threshold + 1) * 2) */
+    DOUBLE(threshold),
+}
+
+impl divider {
+    /** Division factor for a given threshold register value */
+    pub fn factor(self, value: u32) -> u32 {
+        match self {
+            divider::NONE => 1,
+            divider::ADD_ONE(_) => value + 1,
+            divider::DOUBLE(_) => (value + 1) * 2,
+        }
+    }
+
+    /** Threshold register controlling this divider, if any */
+    pub fn threshold(self) -> Option<threshold> {
+        match self {
+            divider::NONE => None,
+            divider::ADD_ONE(which) | divider::DOUBLE(which) => Some(which),
+        }
+    }
+}
+
+/** Parent and divider of every clock
+ *
+ * ACLK itself is special: it is muxed between IN0 and PLL0 and only divided (by `2 << threshold`)
+ * when running from PLL0, see `ClockTree::source_freq`.
+ */
+pub fn route(clock: clock) -> (parent, divider) {
+    use self::divider::*;
+    use self::parent::*;
+    match clock {
+        clock::IN0 | clock::RTC => (SOURCE(clock_source::IN0), NONE),
+        clock::PLL0 => (SOURCE(clock_source::PLL0), NONE),
+        clock::PLL1 => (SOURCE(clock_source::PLL1), NONE),
+        clock::PLL2 => (SOURCE(clock_source::PLL2), NONE),
+        clock::CPU | clock::DMA | clock::FFT | clock::ACLK | clock::HCLK => {
+            (SOURCE(clock_source::ACLK), NONE)
+        }
+        clock::SRAM0 => (SOURCE(clock_source::ACLK), ADD_ONE(threshold::SRAM0)),
+        clock::SRAM1 => (SOURCE(clock_source::ACLK), ADD_ONE(threshold::SRAM1)),
+        clock::ROM => (SOURCE(clock_source::ACLK), ADD_ONE(threshold::ROM)),
+        clock::DVP => (SOURCE(clock_source::ACLK), ADD_ONE(threshold::DVP)),
+        clock::APB0
+        | clock::GPIO
+        | clock::UART1
+        | clock::UART2
+        | clock::UART3
+        | clock::FPIOA
+        | clock::SHA => (SOURCE(clock_source::ACLK), ADD_ONE(threshold::APB0)),
+        clock::APB1 | clock::AES | clock::OTP => {
+            (SOURCE(clock_source::ACLK), ADD_ONE(threshold::APB1))
+        }
+        clock::APB2 => (SOURCE(clock_source::ACLK), ADD_ONE(threshold::APB2)),
+        clock::AI => (SOURCE(clock_source::PLL1), ADD_ONE(threshold::AI)),
+        clock::I2S0 => (SOURCE(clock_source::PLL2), DOUBLE(threshold::I2S0)),
+        clock::I2S1 => (SOURCE(clock_source::PLL2), DOUBLE(threshold::I2S1)),
+        clock::I2S2 => (SOURCE(clock_source::PLL2), DOUBLE(threshold::I2S2)),
+        clock::WDT0 => (SOURCE(clock_source::IN0), DOUBLE(threshold::WDT0)),
+        clock::WDT1 => (SOURCE(clock_source::IN0), DOUBLE(threshold::WDT1)),
+        clock::SPI0 => (SOURCE(clock_source::PLL0), DOUBLE(threshold::SPI0)),
+        clock::SPI1 => (SOURCE(clock_source::PLL0), DOUBLE(threshold::SPI1)),
+        clock::SPI2 => (SOURCE(clock_source::PLL0), DOUBLE(threshold::SPI2)),
+        clock::I2C0 => (SOURCE(clock_source::PLL0), DOUBLE(threshold::I2C0)),
+        clock::I2C1 => (SOURCE(clock_source::PLL0), DOUBLE(threshold::I2C1)),
+        clock::I2C2 => (SOURCE(clock_source::PLL0), DOUBLE(threshold::I2C2)),
+        clock::SPI3 => (MUX(clock_select::SPI3), DOUBLE(threshold::SPI3)),
+        clock::TIMER0 => (MUX(clock_select::TIMER0), DOUBLE(threshold::TIMER0)),
+        clock::TIMER1 => (MUX(clock_select::TIMER1), DOUBLE(threshold::TIMER1)),
+        clock::TIMER2 => (MUX(clock_select::TIMER2), DOUBLE(threshold::TIMER2)),
+    }
+}
+
+/** Snapshot of the registers that determine every clock frequency */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClockTree {
+    /** Frequency of the IN0 oscillator in Hz */
+    pub in0: u32,
+    /** Raw r/f/od register values of PLL0, PLL1 and PLL2 */
+    pub pll: [Params; 3],
+    /** Raw clock select values, indexed by `clock_select` */
+    pub select: [u8; CLOCK_SELECT_COUNT],
+    /** Raw divider values, indexed by `threshold` */
+    pub threshold: [u32; THRESHOLD_COUNT],
+}
+
+impl ClockTree {
+    /** Clock tree with all PLLs, selects and dividers at zero */
+    pub const fn new(in0: u32) -> ClockTree {
+        const ZERO: Params = Params {
+            clkr: 0,
+            clkf: 0,
+            clkod: 0,
+            bwadj: 0,
+        };
+        ClockTree {
+            in0,
+            pll: [ZERO; 3],
+            select: [0; CLOCK_SELECT_COUNT],
+            threshold: [0; THRESHOLD_COUNT],
+        }
+    }
+
+    pub fn get_threshold(&self, which: threshold) -> u32 {
+        self.threshold[which as usize]
+    }
+
+    pub fn set_threshold(&mut self, which: threshold, value: u32) {
+        self.threshold[which as usize] = value;
+    }
+
+    pub fn get_clock_select(&self, which: clock_select) -> u8 {
+        self.select[which as usize]
+    }
+
+    pub fn set_clock_select(&mut self, which: clock_select, select: u8) {
+        self.select[which as usize] = select;
+    }
+
+    pub fn get_pll(&self, pll: pll) -> &Params {
+        &self.pll[pll as usize]
+    }
+
+    pub fn set_pll(&mut self, pll: pll, params: Params) {
+        self.pll[pll as usize] = params;
+    }
+
+    /** Input frequency of a PLL */
+    pub fn pll_input_freq(&self, pll: pll) -> Result<u32, SysctlError> {
+        match pll {
+            pll::PLL0 | pll::PLL1 => Ok(self.in0),
+            pll::PLL2 => self.source_freq(pll2_cksel_to_source(
+                self.get_clock_select(clock_select::PLL2),
+            )?),
+        }
+    }
+
+    pub fn pll_freq(&self, pll: pll) -> Result<u32, SysctlError> {
+        let params = self.get_pll(pll);
+        let nr = u64::from(params.clkr) + 1;
+        let nf = u64::from(params.clkf) + 1;
+        let od = u64::from(params.clkod) + 1;
+
+        /*
+         * Get final PLL output freq
+         * FOUT = FIN / NR * NF / OD
+         * (rewritten as integer expression)
+         */
+        Ok(((u64::from(self.pll_input_freq(pll)?) * nf) / (nr * od)) as u32)
+    }
+
+    pub fn source_freq(&self, source: clock_source) -> Result<u32, SysctlError> {
+        match source {
+            clock_source::IN0 => Ok(self.in0),
+            clock_source::PLL0 => self.pll_freq(pll::PLL0),
+            clock_source::PLL1 => self.pll_freq(pll::PLL1),
+            clock_source::PLL2 => self.pll_freq(pll::PLL2),
+            clock_source::ACLK => match self.get_clock_select(clock_select::ACLK) {
+                0 => Ok(self.in0),
+                1 => Ok(self.pll_freq(pll::PLL0)? / (2 << self.get_threshold(threshold::ACLK))),
+                select => Err(SysctlError::InvalidSelect(clock_select::ACLK, select)),
+            },
+        }
+    }
+
+    /** Frequency of the input of a clock, before its divider */
+    pub fn parent_freq(&self, parent: parent) -> Result<u32, SysctlError> {
+        match parent {
+            parent::SOURCE(source) => self.source_freq(source),
+            parent::MUX(which) => match self.get_clock_select(which) {
+                0 => Ok(self.in0),
+                1 => self.pll_freq(pll::PLL0),
+                select => Err(SysctlError::InvalidSelect(which, select)),
+            },
+        }
+    }
+
+    pub fn freq(&self, clock: clock) -> Result<u32, SysctlError> {
+        let (parent, divider) = route(clock);
+        let value = divider
+            .threshold()
+            .map_or(0, |which| self.get_threshold(which));
+        Ok(self.parent_freq(parent)? / divider.factor(value))
+    }
+
+    /** Whether `source` is the output of `pll` or runs from it */
+    pub fn source_uses_pll(&self, source: clock_source, pll: pll) -> bool {
+        match source {
+            clock_source::IN0 => false,
+            clock_source::PLL0 => pll == pll::PLL0,
+            clock_source::PLL1 => pll == pll::PLL1,
+            clock_source::PLL2 => {
+                pll == pll::PLL2
+                    || pll2_cksel_to_source(self.get_clock_select(clock_select::PLL2))
+                        .map_or(false, |input| self.source_uses_pll(input, pll))
+            }
+            clock_source::ACLK => {
+                pll == pll::PLL0 && self.get_clock_select(clock_select::ACLK) == 1
+            }
+        }
+    }
+
+    /** Whether `clock` runs from `pll`, directly or through PLL2 or ACLK */
+    pub fn uses_pll(&self, clock: clock, pll: pll) -> bool {
+        match route(clock).0 {
+            parent::SOURCE(source) => self.source_uses_pll(source, pll),
+            parent::MUX(which) => pll == pll::PLL0 && self.get_clock_select(which) == 1,
+        }
+    }
+}
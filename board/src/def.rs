@@ -9,7 +9,7 @@ pub const DISP_HEIGHT: u16 = 240;
 /** Number of pixels on the screen */
 pub const DISP_PIXELS: usize = (DISP_WIDTH as usize) * (DISP_HEIGHT as usize);
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum io {
     /** JTAG TCK */
     JTAG_TCK = 0,
@@ -109,6 +109,60 @@ pub enum io {
     DVP_PCLK = 47,
 }
 
+impl io {
+    /** Every pad, by number */
+    pub const ALL: &'static [io] = &[
+        io::JTAG_TCK,
+        io::JTAG_TDI,
+        io::JTAG_TMS,
+        io::JTAG_TDO,
+        io::ISP_RX,
+        io::ISP_TX,
+        io::WIFI_TX,
+        io::WIFI_RX,
+        io::WIFI_EN,
+        io::IO9,
+        io::IO10,
+        io::IO11,
+        io::IO12,
+        io::IO13,
+        io::IO14,
+        io::IO15,
+        io::BOOT,
+        io::IO17,
+        io::IO18,
+        io::IO19,
+        io::IO20,
+        io::IO21,
+        io::RGB_LED_R,
+        io::RGB_LED_B,
+        io::RGB_LED_G,
+        io::IO25,
+        io::IO26,
+        io::IO27,
+        io::IO28,
+        io::IO29,
+        io::IO30,
+        io::IO31,
+        io::IO32,
+        io::IO33,
+        io::IO34,
+        io::IO35,
+        io::LCD_CS,
+        io::LCD_RST,
+        io::LCD_DC,
+        io::LCD_WR,
+        io::DVP_SDA,
+        io::DVP_SCL,
+        io::DVP_RST,
+        io::DVP_VSYNC,
+        io::DVP_PWDN,
+        io::DVP_HSYNC,
+        io::DVP_XCLK,
+        io::DVP_PCLK,
+    ];
+}
+
 impl From<io> for usize {
     fn from(io: io) -> Self {
         io as usize
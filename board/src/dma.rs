@@ -0,0 +1,100 @@
+//! DMA channel allocation
+//!
+//! The six DMAC channels are handed out as `DmaChannel` tokens. Claiming a channel programs its
+//! handshake (`Sysctl::dma_select`) for the requesting peripheral, dropping the token makes the
+//! channel available again.
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::regs::{Mmio, Registers};
+use crate::sysctl::{dma_channel, dma_select, Sysctl};
+
+/** Bit per claimed channel, indexed by `dma_channel` */
+static CLAIMED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DmaError {
+    /** All channels are in use */
+    NoFreeChannel,
+    /** The channel is in use */
+    Claimed(dma_channel),
+}
+
+impl fmt::Display for DmaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DmaError::NoFreeChannel => write!(f, "no free DMA channel"),
+            DmaError::Claimed(channel) => write!(f, "DMA {:?} is in use", channel),
+        }
+    }
+}
+
+/** Exclusive use of a DMA channel, with its handshake set up for one peripheral */
+pub struct DmaChannel {
+    channel: dma_channel,
+    select: dma_select,
+}
+
+impl DmaChannel {
+    pub fn channel(&self) -> dma_channel {
+        self.channel
+    }
+
+    pub fn select(&self) -> dma_select {
+        self.select
+    }
+
+    pub fn idx(&self) -> usize {
+        self.channel.idx()
+    }
+}
+
+impl Drop for DmaChannel {
+    fn drop(&mut self) {
+        let bit = 1 << self.channel.idx();
+        CLAIMED.fetch_and(!bit, Ordering::AcqRel);
+    }
+}
+
+/** Claim a specific channel for the peripheral request `select` */
+pub fn claim_channel(channel: dma_channel, select: dma_select) -> Result<DmaChannel, DmaError> {
+    claim_channel_on(&Sysctl::new(Mmio), channel, select)
+}
+
+/** Claim the lowest free channel for the peripheral request `select` */
+pub fn claim(select: dma_select) -> Result<DmaChannel, DmaError> {
+    claim_on(&Sysctl::new(Mmio), select)
+}
+
+/** `claim_channel`, programming the handshake through `sysctl` */
+pub fn claim_channel_on<R: Registers>(
+    sysctl: &Sysctl<R>,
+    channel: dma_channel,
+    select: dma_select,
+) -> Result<DmaChannel, DmaError> {
+    let bit = 1 << channel.idx();
+    if CLAIMED.fetch_or(bit, Ordering::AcqRel) & bit != 0 {
+        return Err(DmaError::Claimed(channel));
+    }
+    sysctl.dma_select(channel, select);
+    Ok(DmaChannel { channel, select })
+}
+
+/** `claim`, programming the handshake through `sysctl` */
+pub fn claim_on<R: Registers>(
+    sysctl: &Sysctl<R>,
+    select: dma_select,
+) -> Result<DmaChannel, DmaError> {
+    for &channel in dma_channel::ALL {
+        match claim_channel_on(sysctl, channel, select) {
+            Err(DmaError::Claimed(_)) => continue,
+            result => return result,
+        }
+    }
+    Err(DmaError::NoFreeChannel)
+}
+
+/** Whether a channel is currently claimed */
+pub fn is_claimed(channel: dma_channel) -> bool {
+    CLAIMED.load(Ordering::Relaxed) & (1 << channel.idx()) != 0
+}
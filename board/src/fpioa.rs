@@ -1,7 +1,13 @@
 //! FPIOA pheripherals
-use k210_hal::pac;
+use core::fmt;
+use core::mem;
 
-#[derive(Copy, Clone)]
+use crate::def::io;
+use crate::regs::{Mmio, Registers};
+use crate::utils::column;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
 pub enum function {
     JTAG_TCLK = 0,        /* JTAG Test Clock */
     JTAG_TDI = 1,         /* JTAG Test Data In */
@@ -262,6 +268,12 @@ pub enum function {
 }
 
 impl function {
+    /** Function with the channel number `n` */
+    pub const fn from_u8(n: u8) -> function {
+        /* The variants cover 0..=255 without gaps, so every value is valid */
+        unsafe { mem::transmute(n) }
+    }
+
     pub fn gpiohs(num: u8) -> function {
         use function::*;
         match num {
@@ -302,13 +314,196 @@ impl function {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum pull {
     NONE,
     DOWN,
     UP,
 }
 
+/** Output drive strength, from weakest to strongest */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum drive {
+    DS0,
+    DS1,
+    DS2,
+    DS3,
+    DS4,
+    DS5,
+    DS6,
+    DS7,
+    DS8,
+    DS9,
+    DS10,
+    DS11,
+    DS12,
+    DS13,
+    DS14,
+    DS15,
+}
+
+impl drive {
+    pub const ALL: &'static [drive] = &[
+        drive::DS0,
+        drive::DS1,
+        drive::DS2,
+        drive::DS3,
+        drive::DS4,
+        drive::DS5,
+        drive::DS6,
+        drive::DS7,
+        drive::DS8,
+        drive::DS9,
+        drive::DS10,
+        drive::DS11,
+        drive::DS12,
+        drive::DS13,
+        drive::DS14,
+        drive::DS15,
+    ];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum slew {
+    FAST,
+    SLOW,
+}
+
+/* Fields of an IO register (fpioa_io_config_t) */
+const CH_SEL_MASK: u32 = 0xff;
+const DS_SHIFT: u32 = 8;
+const DS_MASK: u32 = 0xf << DS_SHIFT;
+const OE_EN: u32 = 1 << 12;
+const OE_INV: u32 = 1 << 13;
+const DO_SEL: u32 = 1 << 14;
+const DO_INV: u32 = 1 << 15;
+const PU: u32 = 1 << 16;
+const PD: u32 = 1 << 17;
+const SL: u32 = 1 << 19;
+const IE_EN: u32 = 1 << 20;
+const IE_INV: u32 = 1 << 21;
+const DI_INV: u32 = 1 << 22;
+const ST: u32 = 1 << 23;
+const TIE_EN: u32 = 1 << 24;
+const TIE_VAL: u32 = 1 << 25;
+const PAD_DI: u32 = 1 << 31;
+
+/**
+ * Electrical setup of a pad, everything in its IO register except the function. Start from
+ * `PadConfig::default_for(function)` and change what needs tuning, e.g. a stronger drive and fast
+ * slew for a long SPI clock line, or the Schmitt trigger on a noisy clock input.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PadConfig {
+    pub drive: drive,
+    /** Output enable (OE) of the pad */
+    pub output_enable: bool,
+    /** Invert the OE signal of the function */
+    pub output_enable_invert: bool,
+    /** Drive the pad with the OE signal of the function instead of its data */
+    pub output_select_oe: bool,
+    /** Invert the data going out */
+    pub output_invert: bool,
+    pub pull: pull,
+    pub slew: slew,
+    /** Input enable (IE) of the pad */
+    pub input_enable: bool,
+    /** Invert the IE signal of the function */
+    pub input_enable_invert: bool,
+    /** Invert the data coming in */
+    pub input_invert: bool,
+    /** Schmitt trigger on the input */
+    pub schmitt: bool,
+    /** Feed the function a constant instead of the pad level */
+    pub tie: Option<bool>,
+}
+
+impl PadConfig {
+    /** Decode an IO register, the function and pad level bits are ignored */
+    pub const fn from_bits(bits: u32) -> PadConfig {
+        PadConfig {
+            drive: drive::ALL[((bits & DS_MASK) >> DS_SHIFT) as usize],
+            output_enable: bits & OE_EN != 0,
+            output_enable_invert: bits & OE_INV != 0,
+            output_select_oe: bits & DO_SEL != 0,
+            output_invert: bits & DO_INV != 0,
+            /* Both pulls at once is not a setting anyone wants, it reads as pull-up */
+            pull: if bits & PU != 0 {
+                pull::UP
+            } else if bits & PD != 0 {
+                pull::DOWN
+            } else {
+                pull::NONE
+            },
+            slew: if bits & SL != 0 {
+                slew::SLOW
+            } else {
+                slew::FAST
+            },
+            input_enable: bits & IE_EN != 0,
+            input_enable_invert: bits & IE_INV != 0,
+            input_invert: bits & DI_INV != 0,
+            schmitt: bits & ST != 0,
+            tie: if bits & TIE_EN != 0 {
+                Some(bits & TIE_VAL != 0)
+            } else {
+                None
+            },
+        }
+    }
+
+    /** IO register bits of the config, with the function field left zero */
+    pub const fn bits(&self) -> u32 {
+        const fn flag(set: bool, bit: u32) -> u32 {
+            if set {
+                bit
+            } else {
+                0
+            }
+        }
+        ((self.drive as u32) << DS_SHIFT)
+            | flag(self.output_enable, OE_EN)
+            | flag(self.output_enable_invert, OE_INV)
+            | flag(self.output_select_oe, DO_SEL)
+            | flag(self.output_invert, DO_INV)
+            | flag(self.pull as usize == pull::UP as usize, PU)
+            | flag(self.pull as usize == pull::DOWN as usize, PD)
+            | flag(self.slew as usize == slew::SLOW as usize, SL)
+            | flag(self.input_enable, IE_EN)
+            | flag(self.input_enable_invert, IE_INV)
+            | flag(self.input_invert, DI_INV)
+            | flag(self.schmitt, ST)
+            | match self.tie {
+                None => 0,
+                Some(value) => TIE_EN | flag(value, TIE_VAL),
+            }
+    }
+
+    /** Setup `set_function` gives a pad for `function` */
+    pub fn default_for(function: function) -> PadConfig {
+        PadConfig::from_bits(FUNCTION_DEFAULTS[function as usize])
+    }
+}
+
+/** What an IO register says about its pad */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PadState {
+    pub function: function,
+    pub config: PadConfig,
+    /** Level at the input buffer */
+    pub level: bool,
+}
+
+impl PadState {
+    pub const fn from_bits(bits: u32) -> PadState {
+        PadState {
+            function: function::from_u8((bits & CH_SEL_MASK) as u8),
+            config: PadConfig::from_bits(bits),
+            level: bits & PAD_DI != 0,
+        }
+    }
+}
+
 /** Defaults per function (from Kendryte fpioa.c)
  *  Here is the memomy mapped of each internal function
  */
@@ -348,20 +543,182 @@ static FUNCTION_DEFAULTS: &[u32] = &[
     0x00001ff8, 0x00001ff9, 0x00001ffa, 0x00001ffb, 0x00001ffc, 0x00001ffd, 0x00001ffe, 0x00001fff,
 ];
 
-pub fn set_function<N: Into<usize>>(io: N, function: function) {
-    unsafe {
-        let ptr = pac::FPIOA::ptr();
-        (*ptr).io[io.into()].write(|w| w.bits(FUNCTION_DEFAULTS[function as usize]));
-    }
+/** Number of IO pads */
+pub const IO_COUNT: usize = 48;
+
+/** FPIOA driver on top of a register backend */
+pub struct Fpioa<R> {
+    regs: R,
 }
 
-pub fn set_io_pull<N: Into<usize>>(io: N, pull: pull) {
-    unsafe {
-        let ptr = pac::FPIOA::ptr();
-        (*ptr).io[io.into()].modify(|_, w| match pull {
-            pull::NONE => w.pu().bit(false).pd().bit(false),
-            pull::DOWN => w.pu().bit(false).pd().bit(true),
-            pull::UP => w.pu().bit(true).pd().bit(false),
-        })
+impl<R: Registers> Fpioa<R> {
+    pub fn new(regs: R) -> Self {
+        Fpioa { regs }
+    }
+
+    pub(crate) fn set_function<N: Into<usize>>(&self, io: N, function: function) {
+        unsafe {
+            let ptr = self.regs.fpioa();
+            (*ptr).io[io.into()].write(|w| w.bits(FUNCTION_DEFAULTS[function as usize]));
+        }
+    }
+
+    pub(crate) fn set_io_pull<N: Into<usize>>(&self, io: N, pull: pull) {
+        unsafe {
+            let ptr = self.regs.fpioa();
+            (*ptr).io[io.into()].modify(|_, w| match pull {
+                pull::NONE => w.pu().bit(false).pd().bit(false),
+                pull::DOWN => w.pu().bit(false).pd().bit(true),
+                pull::UP => w.pu().bit(true).pd().bit(false),
+            })
+        }
+    }
+
+    /** Route `function` to a pad with a custom setup instead of the defaults */
+    pub(crate) fn set_function_with<N: Into<usize>>(
+        &self,
+        io: N,
+        function: function,
+        config: &PadConfig,
+    ) {
+        unsafe {
+            let ptr = self.regs.fpioa();
+            (*ptr).io[io.into()].write(|w| w.bits(config.bits() | function as u32));
+        }
+    }
+
+    /** Change the setup of a pad, keeping its function */
+    pub(crate) fn set_pad_config<N: Into<usize>>(&self, io: N, config: &PadConfig) {
+        unsafe {
+            let ptr = self.regs.fpioa();
+            (*ptr).io[io.into()].modify(|r, w| w.bits(config.bits() | (r.bits() & CH_SEL_MASK)));
+        }
+    }
+
+    pub fn pad_config<N: Into<usize>>(&self, io: N) -> PadConfig {
+        unsafe {
+            let ptr = self.regs.fpioa();
+            PadConfig::from_bits((*ptr).io[io.into()].read().bits())
+        }
+    }
+
+    /** Change part of the setup of a pad, e.g. `|c| PadConfig { schmitt: true, ..c }` */
+    pub(crate) fn modify_pad_config<N: Into<usize>, F: FnOnce(PadConfig) -> PadConfig>(
+        &self,
+        io: N,
+        f: F,
+    ) {
+        let io = io.into();
+        self.set_pad_config(io, &f(self.pad_config(io)));
     }
+
+    pub(crate) fn set_io_driving<N: Into<usize>>(&self, io: N, drive: drive) {
+        self.modify_pad_config(io, |config| PadConfig { drive, ..config })
+    }
+
+    /** Level of the pad as seen by the input buffer */
+    pub fn pad_level<N: Into<usize>>(&self, io: N) -> bool {
+        self.pad_state(io).level
+    }
+
+    /** Decode the IO register of a pad */
+    pub fn pad_state<N: Into<usize>>(&self, io: N) -> PadState {
+        unsafe {
+            let ptr = self.regs.fpioa();
+            PadState::from_bits((*ptr).io[io.into()].read().bits())
+        }
+    }
+
+    /** Function currently routed to a pad */
+    pub fn function_of<N: Into<usize>>(&self, io: N) -> function {
+        self.pad_state(io).function
+    }
+
+    /**
+     * Write a table of all pads: function, pull, drive, output and input enable, inversions
+     * (`o` out, `i` in), Schmitt trigger, slew and the level the pad reads.
+     */
+    pub fn report<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        let yes = |state: bool| if state { "yes" } else { "no" };
+
+        writeln!(
+            w,
+            "pad  name       function       pull  drive  oe   ie   inv  st   slew  in"
+        )?;
+        for &pad in io::ALL {
+            let state = self.pad_state(pad);
+            let config = &state.config;
+            write!(w, "{:<4} ", pad as usize)?;
+            column(w, pad, 11)?;
+            column(w, state.function, 15)?;
+            column(w, config.pull, 6)?;
+            column(w, config.drive, 7)?;
+            let inv = match (config.output_invert, config.input_invert) {
+                (false, false) => "-",
+                (true, false) => "o",
+                (false, true) => "i",
+                (true, true) => "oi",
+            };
+            write!(
+                w,
+                "{:<4} {:<4} {:<4} {:<4} ",
+                yes(config.output_enable),
+                yes(config.input_enable),
+                inv,
+                yes(config.schmitt)
+            )?;
+            column(w, config.slew, 6)?;
+            writeln!(w, "{}", state.level as u8)?;
+        }
+        Ok(())
+    }
+}
+
+/** FPIOA of the running SoC */
+fn fpioa() -> Fpioa<Mmio> {
+    Fpioa::new(Mmio)
+}
+
+pub(crate) fn set_function<N: Into<usize>>(io: N, function: function) {
+    fpioa().set_function(io, function)
+}
+
+pub(crate) fn set_io_pull<N: Into<usize>>(io: N, pull: pull) {
+    fpioa().set_io_pull(io, pull)
+}
+
+pub(crate) fn set_function_with<N: Into<usize>>(io: N, function: function, config: &PadConfig) {
+    fpioa().set_function_with(io, function, config)
+}
+
+pub(crate) fn set_pad_config<N: Into<usize>>(io: N, config: &PadConfig) {
+    fpioa().set_pad_config(io, config)
+}
+
+pub fn pad_config<N: Into<usize>>(io: N) -> PadConfig {
+    fpioa().pad_config(io)
+}
+
+pub(crate) fn modify_pad_config<N: Into<usize>, F: FnOnce(PadConfig) -> PadConfig>(io: N, f: F) {
+    fpioa().modify_pad_config(io, f)
+}
+
+pub(crate) fn set_io_driving<N: Into<usize>>(io: N, drive: drive) {
+    fpioa().set_io_driving(io, drive)
+}
+
+pub fn pad_level<N: Into<usize>>(io: N) -> bool {
+    fpioa().pad_level(io)
+}
+
+pub fn pad_state<N: Into<usize>>(io: N) -> PadState {
+    fpioa().pad_state(io)
+}
+
+pub fn function_of<N: Into<usize>>(io: N) -> function {
+    fpioa().function_of(io)
+}
+
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    fpioa().report(w)
 }
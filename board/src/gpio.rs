@@ -1,4 +1,323 @@
-//! GPIO types
+//! GPIO peripheral
+//!
+//! The eight low-speed GPIO pins. Unlike GPIOHS, all of them share one interrupt source, the
+//! handlers registered with `set_irq` are called for the pins it reports.
+use core::cell::UnsafeCell;
+use core::convert::Infallible;
+
+use embedded_hal::digital::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
 use k210_hal::pac;
 
+use crate::plic;
+use crate::regs::{Mmio, Registers};
+use crate::utils::{cs, get_bit, set_bit};
+
 pub type direction = pac::gpio::direction::PIN0_A;
+
+/** Number of GPIO pins */
+pub const GPIO_PINS: usize = 8;
+
+/** What makes a pin interrupt */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum edge {
+    NONE,
+    RISING,
+    FALLING,
+    BOTH,
+    /** Keeps firing while the pin is high */
+    HIGH,
+    /** Keeps firing while the pin is low */
+    LOW,
+}
+
+/** Called with the pin that interrupted */
+pub type gpio_handler = fn(u8);
+
+/** GPIO driver on top of a register backend */
+pub struct Gpio<R> {
+    regs: R,
+}
+
+impl<R: Registers> Gpio<R> {
+    pub fn new(regs: R) -> Self {
+        Gpio { regs }
+    }
+
+    pub fn set_direction(&self, pin: u8, direction: direction) {
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr)
+                .direction
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, direction == direction::OUTPUT)));
+        }
+    }
+
+    pub fn set_pin(&self, pin: u8, value: bool) {
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr)
+                .data_output
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, value)));
+        }
+    }
+
+    pub fn get_pin(&self, pin: u8) -> bool {
+        get_bit(self.get_pins(), pin)
+    }
+
+    /** Level `pin` is driven to */
+    pub fn get_output(&self, pin: u8) -> bool {
+        unsafe {
+            let ptr = self.regs.gpio();
+            get_bit((*ptr).data_output.read().bits(), pin)
+        }
+    }
+
+    pub fn toggle_pin(&self, pin: u8) {
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr)
+                .data_output
+                .modify(|r, w| w.bits(r.bits() ^ (1 << pin)));
+        }
+    }
+
+    /** Set the pins in `mask` to the matching bits of `value`, the others keep their level */
+    pub fn set_pins(&self, mask: u8, value: u8) {
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr)
+                .data_output
+                .modify(|r, w| w.bits((r.bits() & !u32::from(mask)) | u32::from(value & mask)));
+        }
+    }
+
+    /** Levels of all pins, bit `n` is pin `n` */
+    pub fn get_pins(&self) -> u32 {
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr).data_input.read().bits()
+        }
+    }
+
+    /** Select what makes `pin` interrupt, pending interrupts of the pin are dropped */
+    pub fn set_irq_mode(&self, pin: u8, edge: edge) {
+        /* Level (0) or edge (1), active low/falling (0) or high/rising (1), and both edges */
+        let (enable, edge_triggered, high, both) = match edge {
+            edge::NONE => (false, false, false, false),
+            edge::RISING => (true, true, true, false),
+            edge::FALLING => (true, true, false, false),
+            edge::BOTH => (true, true, false, true),
+            edge::HIGH => (true, false, true, false),
+            edge::LOW => (true, false, false, false),
+        };
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr)
+                .interrupt_enable
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, false)));
+            (*ptr)
+                .interrupt_level
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, edge_triggered)));
+            (*ptr)
+                .interrupt_polarity
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, high)));
+            (*ptr)
+                .interrupt_bothedge
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, both)));
+            (*ptr)
+                .interrupt_mask
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, false)));
+        }
+        self.clear_irq(pin);
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr)
+                .interrupt_enable
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, enable)));
+        }
+    }
+
+    /** Pins with a pending interrupt, bit `n` is pin `n` */
+    pub fn irq_status(&self) -> u32 {
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr).interrupt_status.read().bits()
+        }
+    }
+
+    /** Acknowledge the edge interrupt of `pin`, level interrupts end with the level */
+    pub fn clear_irq(&self, pin: u8) {
+        unsafe {
+            let ptr = self.regs.gpio();
+            (*ptr).interrupt_clear.write(|w| w.bits(1 << pin));
+        }
+    }
+
+    /** Make `pin` an output, starting at `level`, and hand it out as a pin of its own */
+    pub fn output_pin(self, pin: u8, level: bool) -> GpioPin<R> {
+        self.set_pin(pin, level);
+        self.set_direction(pin, direction::OUTPUT);
+        GpioPin { gpio: self, pin }
+    }
+
+    pub fn input_pin(self, pin: u8) -> GpioPin<R> {
+        self.set_direction(pin, direction::INPUT);
+        GpioPin { gpio: self, pin }
+    }
+}
+
+/** GPIO of the running SoC */
+fn gpio() -> Gpio<Mmio> {
+    Gpio::new(Mmio)
+}
+
+pub fn set_direction(pin: u8, direction: direction) {
+    gpio().set_direction(pin, direction)
+}
+
+pub fn set_pin(pin: u8, value: bool) {
+    gpio().set_pin(pin, value)
+}
+
+pub fn get_pin(pin: u8) -> bool {
+    gpio().get_pin(pin)
+}
+
+pub fn get_output(pin: u8) -> bool {
+    gpio().get_output(pin)
+}
+
+pub fn toggle_pin(pin: u8) {
+    gpio().toggle_pin(pin)
+}
+
+pub fn set_pins(mask: u8, value: u8) {
+    gpio().set_pins(mask, value)
+}
+
+pub fn get_pins() -> u32 {
+    gpio().get_pins()
+}
+
+pub fn set_irq_mode(pin: u8, edge: edge) {
+    gpio().set_irq_mode(pin, edge)
+}
+
+pub fn clear_irq(pin: u8) {
+    gpio().clear_irq(pin)
+}
+
+struct Handlers(UnsafeCell<[Option<gpio_handler>; GPIO_PINS]>);
+
+unsafe impl Sync for Handlers {}
+
+static HANDLERS: Handlers = Handlers(UnsafeCell::new([None; GPIO_PINS]));
+
+/** PLIC handler of the shared GPIO source */
+fn dispatch(_irq: usize) {
+    let gpio = gpio();
+    let status = gpio.irq_status();
+    for pin in 0..GPIO_PINS as u8 {
+        if !get_bit(status, pin) {
+            continue;
+        }
+        /* Acknowledge first, so an edge during the handler is not lost */
+        gpio.clear_irq(pin);
+        let handler = unsafe { (*HANDLERS.0.get())[usize::from(pin)] };
+        if let Some(handler) = handler {
+            handler(pin);
+        }
+    }
+}
+
+/**
+ * Call `handler` when `pin` sees `edge`. The PLIC source is shared by all pins, `priority` is
+ * set for all of them. The pin has to be routed and set to input already, and interrupts have
+ * to be on (`plic::enable_interrupts`).
+ */
+pub fn set_irq(pin: u8, edge: edge, priority: u32, handler: gpio_handler) {
+    cs(|| unsafe {
+        (*HANDLERS.0.get())[usize::from(pin)] = Some(handler);
+    });
+    set_irq_mode(pin, edge);
+    plic::register(plic::IRQ_GPIO, dispatch);
+    plic::set_priority(plic::IRQ_GPIO, priority);
+    plic::enable(plic::IRQ_GPIO);
+}
+
+/** Stop interrupts of `pin` and forget its handler, the shared source stays enabled */
+pub fn clear_irq_handler(pin: u8) {
+    set_irq_mode(pin, edge::NONE);
+    cs(|| unsafe {
+        (*HANDLERS.0.get())[usize::from(pin)] = None;
+    });
+}
+
+/** One GPIO pin, for drivers written against the embedded-hal digital traits */
+pub struct GpioPin<R = Mmio> {
+    gpio: Gpio<R>,
+    pin: u8,
+}
+
+impl GpioPin {
+    /** Make `pin` an output, starting at `level` */
+    pub fn output(pin: u8, level: bool) -> GpioPin {
+        gpio().output_pin(pin, level)
+    }
+
+    pub fn input(pin: u8) -> GpioPin {
+        gpio().input_pin(pin)
+    }
+}
+
+impl<R: Registers> GpioPin<R> {
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+}
+
+impl<R: Registers> OutputPin for GpioPin<R> {
+    type Error = Infallible;
+
+    fn try_set_low(&mut self) -> Result<(), Infallible> {
+        self.gpio.set_pin(self.pin, false);
+        Ok(())
+    }
+
+    fn try_set_high(&mut self) -> Result<(), Infallible> {
+        self.gpio.set_pin(self.pin, true);
+        Ok(())
+    }
+}
+
+impl<R: Registers> StatefulOutputPin for GpioPin<R> {
+    fn try_is_set_high(&self) -> Result<bool, Infallible> {
+        Ok(self.gpio.get_output(self.pin))
+    }
+
+    fn try_is_set_low(&self) -> Result<bool, Infallible> {
+        Ok(!self.gpio.get_output(self.pin))
+    }
+}
+
+impl<R: Registers> ToggleableOutputPin for GpioPin<R> {
+    type Error = Infallible;
+
+    fn try_toggle(&mut self) -> Result<(), Infallible> {
+        self.gpio.toggle_pin(self.pin);
+        Ok(())
+    }
+}
+
+impl<R: Registers> InputPin for GpioPin<R> {
+    type Error = Infallible;
+
+    fn try_is_high(&self) -> Result<bool, Infallible> {
+        Ok(self.gpio.get_pin(self.pin))
+    }
+
+    fn try_is_low(&self) -> Result<bool, Infallible> {
+        Ok(!self.gpio.get_pin(self.pin))
+    }
+}
@@ -1,26 +1,270 @@
 //! GPIOHS peripheral
-use k210_hal::pac;
+use core::cell::UnsafeCell;
+use core::convert::Infallible;
+
+use embedded_hal::digital::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
 
 use crate::gpio;
-use crate::utils::set_bit;
+pub use crate::gpio::edge;
+use crate::plic;
+use crate::regs::{Mmio, Registers};
+use crate::utils::{cs, get_bit, set_bit};
 
-pub fn set_direction(pin: u8, direction: gpio::direction) {
-    unsafe {
-        let ptr = pac::GPIOHS::ptr();
-        (*ptr)
-            .output_en
-            .modify(|r, w| w.bits(set_bit(r.bits(), pin, direction == gpio::direction::OUTPUT)));
-        (*ptr)
-            .input_en
-            .modify(|r, w| w.bits(set_bit(r.bits(), pin, direction == gpio::direction::INPUT)));
+/** Number of GPIOHS pins */
+pub const GPIOHS_PINS: usize = 32;
+
+/** Called with the pin that interrupted */
+pub type gpiohs_handler = fn(u8);
+
+/** GPIOHS driver on top of a register backend */
+pub struct Gpiohs<R> {
+    regs: R,
+}
+
+impl<R: Registers> Gpiohs<R> {
+    pub fn new(regs: R) -> Self {
+        Gpiohs { regs }
+    }
+
+    pub fn set_direction(&self, pin: u8, direction: gpio::direction) {
+        unsafe {
+            let ptr = self.regs.gpiohs();
+            (*ptr).output_en.modify(|r, w| {
+                w.bits(set_bit(r.bits(), pin, direction == gpio::direction::OUTPUT))
+            });
+            (*ptr)
+                .input_en
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, direction == gpio::direction::INPUT)));
+        }
+    }
+
+    pub fn set_pin(&self, pin: u8, value: bool) {
+        unsafe {
+            let ptr = self.regs.gpiohs();
+            (*ptr)
+                .output_val
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, value)));
+        }
+    }
+
+    pub fn get_pin(&self, pin: u8) -> bool {
+        unsafe {
+            let ptr = self.regs.gpiohs();
+            get_bit((*ptr).input_val.read().bits(), pin)
+        }
+    }
+
+    /** Level `pin` is driven to */
+    pub fn get_output(&self, pin: u8) -> bool {
+        unsafe {
+            let ptr = self.regs.gpiohs();
+            get_bit((*ptr).output_val.read().bits(), pin)
+        }
+    }
+
+    pub fn toggle_pin(&self, pin: u8) {
+        unsafe {
+            let ptr = self.regs.gpiohs();
+            (*ptr)
+                .output_val
+                .modify(|r, w| w.bits(r.bits() ^ (1 << pin)));
+        }
+    }
+
+    /** Select what makes `pin` interrupt, pending interrupts of the pin are dropped */
+    pub fn set_irq_mode(&self, pin: u8, edge: edge) {
+        let (rise, fall, high, low) = match edge {
+            edge::NONE => (false, false, false, false),
+            edge::RISING => (true, false, false, false),
+            edge::FALLING => (false, true, false, false),
+            edge::BOTH => (true, true, false, false),
+            edge::HIGH => (false, false, true, false),
+            edge::LOW => (false, false, false, true),
+        };
+        unsafe {
+            let ptr = self.regs.gpiohs();
+            (*ptr)
+                .rise_ie
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, rise)));
+            (*ptr)
+                .fall_ie
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, fall)));
+            (*ptr)
+                .high_ie
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, high)));
+            (*ptr)
+                .low_ie
+                .modify(|r, w| w.bits(set_bit(r.bits(), pin, low)));
+        }
+        self.clear_irq(pin);
+    }
+
+    /** Acknowledge the interrupt of `pin` */
+    pub fn clear_irq(&self, pin: u8) {
+        /* Pending bits clear when written with one, zeros leave the other pins alone */
+        unsafe {
+            let ptr = self.regs.gpiohs();
+            (*ptr).rise_ip.write(|w| w.bits(1 << pin));
+            (*ptr).fall_ip.write(|w| w.bits(1 << pin));
+            (*ptr).high_ip.write(|w| w.bits(1 << pin));
+            (*ptr).low_ip.write(|w| w.bits(1 << pin));
+        }
     }
+
+    /** Make `pin` an output, starting at `level`, and hand it out as a pin of its own */
+    pub fn output_pin(self, pin: u8, level: bool) -> GpiohsPin<R> {
+        self.set_pin(pin, level);
+        self.set_direction(pin, gpio::direction::OUTPUT);
+        GpiohsPin { gpiohs: self, pin }
+    }
+
+    pub fn input_pin(self, pin: u8) -> GpiohsPin<R> {
+        self.set_direction(pin, gpio::direction::INPUT);
+        GpiohsPin { gpiohs: self, pin }
+    }
+}
+
+/** GPIOHS of the running SoC */
+fn gpiohs() -> Gpiohs<Mmio> {
+    Gpiohs::new(Mmio)
+}
+
+pub fn set_direction(pin: u8, direction: gpio::direction) {
+    gpiohs().set_direction(pin, direction)
 }
 
 pub fn set_pin(pin: u8, value: bool) {
-    unsafe {
-        let ptr = pac::GPIOHS::ptr();
-        (*ptr)
-            .output_val
-            .modify(|r, w| w.bits(set_bit(r.bits(), pin, value)));
+    gpiohs().set_pin(pin, value)
+}
+
+pub fn get_pin(pin: u8) -> bool {
+    gpiohs().get_pin(pin)
+}
+
+pub fn get_output(pin: u8) -> bool {
+    gpiohs().get_output(pin)
+}
+
+pub fn toggle_pin(pin: u8) {
+    gpiohs().toggle_pin(pin)
+}
+
+pub fn set_irq_mode(pin: u8, edge: edge) {
+    gpiohs().set_irq_mode(pin, edge)
+}
+
+pub fn clear_irq(pin: u8) {
+    gpiohs().clear_irq(pin)
+}
+
+struct Handlers(UnsafeCell<[Option<gpiohs_handler>; GPIOHS_PINS]>);
+
+unsafe impl Sync for Handlers {}
+
+static HANDLERS: Handlers = Handlers(UnsafeCell::new([None; GPIOHS_PINS]));
+
+/** PLIC handler of all GPIOHS sources */
+fn dispatch(irq: usize) {
+    let pin = (irq - plic::IRQ_GPIOHS0) as u8;
+    /* Acknowledge first, so an edge during the handler is not lost */
+    clear_irq(pin);
+    let handler = unsafe { (*HANDLERS.0.get())[usize::from(pin)] };
+    if let Some(handler) = handler {
+        handler(pin);
+    }
+}
+
+/**
+ * Call `handler` when `pin` sees `edge`, at the given PLIC priority. The pin has to be routed
+ * and set to input already, and interrupts have to be on (`plic::enable_interrupts`). A level
+ * handler has to change the level or the mode, or it is called again right away.
+ */
+pub fn set_irq(pin: u8, edge: edge, priority: u32, handler: gpiohs_handler) {
+    let irq = plic::IRQ_GPIOHS0 + usize::from(pin);
+    cs(|| unsafe {
+        (*HANDLERS.0.get())[usize::from(pin)] = Some(handler);
+    });
+    set_irq_mode(pin, edge);
+    plic::register(irq, dispatch);
+    plic::set_priority(irq, priority);
+    plic::enable(irq);
+}
+
+/** Stop interrupts of `pin` and forget its handler */
+pub fn clear_irq_handler(pin: u8) {
+    let irq = plic::IRQ_GPIOHS0 + usize::from(pin);
+    plic::disable(irq);
+    set_irq_mode(pin, edge::NONE);
+    plic::unregister(irq);
+    cs(|| unsafe {
+        (*HANDLERS.0.get())[usize::from(pin)] = None;
+    });
+}
+
+/** One GPIOHS pin, for drivers written against the embedded-hal digital traits */
+pub struct GpiohsPin<R = Mmio> {
+    gpiohs: Gpiohs<R>,
+    pin: u8,
+}
+
+impl GpiohsPin {
+    /** Make `pin` an output, starting at `level` */
+    pub fn output(pin: u8, level: bool) -> GpiohsPin {
+        gpiohs().output_pin(pin, level)
+    }
+
+    pub fn input(pin: u8) -> GpiohsPin {
+        gpiohs().input_pin(pin)
+    }
+}
+
+impl<R: Registers> GpiohsPin<R> {
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+}
+
+impl<R: Registers> OutputPin for GpiohsPin<R> {
+    type Error = Infallible;
+
+    fn try_set_low(&mut self) -> Result<(), Infallible> {
+        self.gpiohs.set_pin(self.pin, false);
+        Ok(())
+    }
+
+    fn try_set_high(&mut self) -> Result<(), Infallible> {
+        self.gpiohs.set_pin(self.pin, true);
+        Ok(())
+    }
+}
+
+impl<R: Registers> StatefulOutputPin for GpiohsPin<R> {
+    fn try_is_set_high(&self) -> Result<bool, Infallible> {
+        Ok(self.gpiohs.get_output(self.pin))
+    }
+
+    fn try_is_set_low(&self) -> Result<bool, Infallible> {
+        Ok(!self.gpiohs.get_output(self.pin))
+    }
+}
+
+impl<R: Registers> ToggleableOutputPin for GpiohsPin<R> {
+    type Error = Infallible;
+
+    fn try_toggle(&mut self) -> Result<(), Infallible> {
+        self.gpiohs.toggle_pin(self.pin);
+        Ok(())
+    }
+}
+
+impl<R: Registers> InputPin for GpiohsPin<R> {
+    type Error = Infallible;
+
+    fn try_is_high(&self) -> Result<bool, Infallible> {
+        Ok(self.gpiohs.get_pin(self.pin))
+    }
+
+    fn try_is_low(&self) -> Result<bool, Infallible> {
+        Ok(!self.gpiohs.get_pin(self.pin))
     }
 }
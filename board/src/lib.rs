@@ -1,14 +1,31 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![allow(dead_code)]
 #![allow(non_camel_case_types)]
 
+pub mod baud;
+pub mod button;
+pub mod clock_gate;
+pub mod clock_plan;
+pub mod clock_tree;
 pub mod def;
+pub mod dma;
 pub mod fpioa;
 pub mod gpio;
 pub mod gpiohs;
 pub mod lcd;
+pub mod pads;
+#[cfg(all(feature = "panic-handler", not(feature = "std")))]
 pub mod panic;
+pub mod pin_map;
+pub mod pins;
+pub mod plic;
 pub mod pll_compute;
+pub mod power;
+pub mod profile;
+pub mod pwm;
+pub mod regs;
+pub mod rgb_led;
 pub mod sleep;
+pub mod status;
 pub mod sysctl;
 pub mod utils;
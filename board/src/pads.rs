@@ -0,0 +1,214 @@
+//! Pad ownership
+//!
+//! Every pad of `def::io` has a zero-sized type here. `Pads::take` hands all of them out once,
+//! and routing a function to a pad consumes its type and returns a `Routed` handle, so only the
+//! code holding the pad can change its function or setup later. `pins::apply` takes the pads of
+//! the subsystems it routes by the board table and hands back the rest.
+//!
+//! ```ignore
+//! let mut pads = Pads::take().unwrap();
+//! let cs = pads.io29.take().unwrap().into_function(function::GPIOHS0);
+//! ```
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::def::io;
+use crate::fpioa::{function, pull, Fpioa, PadConfig};
+use crate::regs::{Mmio, Registers};
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/** A pad that has not been given a function yet */
+pub trait Pad: Sized {
+    const IO: io;
+
+    /** Route `function` to the pad, with the default setup for the function */
+    fn into_function(self, function: function) -> Routed<Self> {
+        Fpioa::new(Mmio).route(self, function)
+    }
+
+    /** Route `function` to the pad with a custom setup */
+    fn into_function_with(self, function: function, config: &PadConfig) -> Routed<Self> {
+        Fpioa::new(Mmio).route_with(self, function, config)
+    }
+}
+
+impl<R: Registers> Fpioa<R> {
+    /** Route `function` to `pad`, with the default setup for the function */
+    pub fn route<P: Pad>(self, pad: P, function: function) -> Routed<P, R> {
+        self.set_function(P::IO, function);
+        Routed {
+            pad,
+            function,
+            fpioa: self,
+        }
+    }
+
+    /** Route `function` to `pad` with a custom setup */
+    pub fn route_with<P: Pad>(
+        self,
+        pad: P,
+        function: function,
+        config: &PadConfig,
+    ) -> Routed<P, R> {
+        self.set_function_with(P::IO, function, config);
+        Routed {
+            pad,
+            function,
+            fpioa: self,
+        }
+    }
+}
+
+/** A pad with a function routed to it */
+pub struct Routed<P: Pad, R = Mmio> {
+    pad: P,
+    function: function,
+    fpioa: Fpioa<R>,
+}
+
+impl<P: Pad, R: Registers> Routed<P, R> {
+    pub fn io(&self) -> io {
+        P::IO
+    }
+
+    pub fn function(&self) -> function {
+        self.function
+    }
+
+    pub fn set_pull(&mut self, pull: pull) {
+        self.fpioa.set_io_pull(P::IO, pull);
+    }
+
+    pub fn set_pad_config(&mut self, config: &PadConfig) {
+        self.fpioa.set_pad_config(P::IO, config);
+    }
+
+    pub fn modify_pad_config<F: FnOnce(PadConfig) -> PadConfig>(&mut self, f: F) {
+        self.fpioa.modify_pad_config(P::IO, f);
+    }
+
+    /** Level the pad reads */
+    pub fn level(&self) -> bool {
+        self.fpioa.pad_level(P::IO)
+    }
+
+    /** Route a different function to the pad */
+    pub fn into_function(self, function: function) -> Routed<P, R> {
+        self.fpioa.route(self.pad, function)
+    }
+
+    /** Give the pad back, its routing stays as it is */
+    pub fn release(self) -> P {
+        self.pad
+    }
+}
+
+macro_rules! pads {
+    ($($field:ident: $pad:ident,)+) => {
+        $(
+            /** Ownership of the pad of the same name in `def::io` */
+            pub struct $pad {
+                _private: (),
+            }
+
+            impl Pad for $pad {
+                const IO: io = io::$pad;
+            }
+        )+
+
+        /** Pads of the board, `None` where one was taken out to be routed */
+        pub struct Pads {
+            $(pub $field: Option<$pad>,)+
+        }
+
+        impl Pads {
+            /** The pads, once; later calls return `None` */
+            pub fn take() -> Option<Pads> {
+                if TAKEN.swap(true, Ordering::AcqRel) {
+                    None
+                } else {
+                    Some(unsafe { Pads::steal() })
+                }
+            }
+
+            /**
+             * The pads, even if they were taken before.
+             *
+             * # Safety
+             *
+             * Two owners of a pad can reroute it under each other, so the pads handed out before
+             * must not be used anymore.
+             */
+            pub unsafe fn steal() -> Pads {
+                Pads {
+                    $($field: Some($pad { _private: () }),)+
+                }
+            }
+
+            /** Whether `pad` is still here */
+            pub fn has(&self, pad: io) -> bool {
+                match pad {
+                    $(io::$pad => self.$field.is_some(),)+
+                }
+            }
+
+            /** Give up `pad`, for routing it by number */
+            pub(crate) fn remove(&mut self, pad: io) {
+                match pad {
+                    $(io::$pad => self.$field = None,)+
+                }
+            }
+        }
+    };
+}
+
+pads! {
+    jtag_tck: JTAG_TCK,
+    jtag_tdi: JTAG_TDI,
+    jtag_tms: JTAG_TMS,
+    jtag_tdo: JTAG_TDO,
+    isp_rx: ISP_RX,
+    isp_tx: ISP_TX,
+    wifi_tx: WIFI_TX,
+    wifi_rx: WIFI_RX,
+    wifi_en: WIFI_EN,
+    io9: IO9,
+    io10: IO10,
+    io11: IO11,
+    io12: IO12,
+    io13: IO13,
+    io14: IO14,
+    io15: IO15,
+    boot: BOOT,
+    io17: IO17,
+    io18: IO18,
+    io19: IO19,
+    io20: IO20,
+    io21: IO21,
+    rgb_led_r: RGB_LED_R,
+    rgb_led_b: RGB_LED_B,
+    rgb_led_g: RGB_LED_G,
+    io25: IO25,
+    io26: IO26,
+    io27: IO27,
+    io28: IO28,
+    io29: IO29,
+    io30: IO30,
+    io31: IO31,
+    io32: IO32,
+    io33: IO33,
+    io34: IO34,
+    io35: IO35,
+    lcd_cs: LCD_CS,
+    lcd_rst: LCD_RST,
+    lcd_dc: LCD_DC,
+    lcd_wr: LCD_WR,
+    dvp_sda: DVP_SDA,
+    dvp_scl: DVP_SCL,
+    dvp_rst: DVP_RST,
+    dvp_vsync: DVP_VSYNC,
+    dvp_pwdn: DVP_PWDN,
+    dvp_hsync: DVP_HSYNC,
+    dvp_xclk: DVP_XCLK,
+    dvp_pclk: DVP_PCLK,
+}
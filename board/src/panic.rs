@@ -1,12 +1,9 @@
 use core::panic::PanicInfo;
-use core::sync::atomic::{self, Ordering};
 
-/** Send panic messages to UARTHS at 115200 baud */
+use crate::status::{self, fault};
+
+/** Blink the recorded fault on the RGB LED, or `fault::PANIC` if there is none */
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
-    loop {
-        // add some side effect to prevent this from turning into a UDF instruction
-        // see rust-lang/rust#28728 for details
-        atomic::compiler_fence(Ordering::SeqCst)
-    }
+    status::halt(status::recorded().unwrap_or(fault::PANIC))
 }
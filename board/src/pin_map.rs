@@ -0,0 +1,264 @@
+//! Pin maps
+//!
+//! `fpioa::set_function` routes whatever it is given, so two functions on one pad or one function
+//! on two pads silently break a peripheral. A `PinMap` records the assignments first and keeps the
+//! first conflict it sees. Copies of an output for probing are made explicit with `mirror`. All
+//! builder methods are `const fn`, so a map can be checked at compile time with `validate`:
+//!
+//! ```ignore
+//! const SDCARD: PinMap = PinMap::new()
+//!     .assign(io::IO26, function::SPI1_D1)
+//!     .assign(io::IO27, function::SPI1_SCLK)
+//!     .assign(io::IO28, function::SPI1_D0)
+//!     .assign_pull(io::IO29, function::GPIOHS0, pull::UP)
+//!     .validate();
+//! ```
+use core::fmt;
+
+use crate::def::io;
+use crate::fpioa::{function, pull, Fpioa, IO_COUNT};
+use crate::regs::Registers;
+
+/** Function and pull of one pad */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pin {
+    pub pad: io,
+    pub function: function,
+    pub pull: pull,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PinError {
+    /** The pad already has a (different) function */
+    PadInUse(io, function, function),
+    /** The function is already routed to another pad */
+    FunctionInUse(function, io, io),
+    /** The pad already has a different pull */
+    PullConflict(io, pull, pull),
+    /** Pull set on a pad without a function */
+    Unassigned(io),
+    /** The pad was taken out of `Pads` before */
+    NotOwned(io),
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PinError::PadInUse(pad, old, new) => {
+                write!(f, "{:?} is {:?}, cannot also be {:?}", pad, old, new)
+            }
+            PinError::FunctionInUse(function, old, new) => {
+                write!(
+                    f,
+                    "{:?} is on {:?}, cannot also be on {:?}",
+                    function, old, new
+                )
+            }
+            PinError::PullConflict(pad, old, new) => {
+                write!(
+                    f,
+                    "{:?} is pulled {:?}, cannot also be pulled {:?}",
+                    pad, old, new
+                )
+            }
+            PinError::Unassigned(pad) => write!(f, "{:?} has no function", pad),
+            PinError::NotOwned(pad) => write!(f, "{:?} is owned elsewhere", pad),
+        }
+    }
+}
+
+/** Function assignments of the IO pads, with the first conflict among them */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PinMap {
+    pins: [Option<Pin>; IO_COUNT],
+    /** Bit per pad that carries a copy of a function routed elsewhere */
+    mirrors: u64,
+    error: Option<PinError>,
+}
+
+impl Default for PinMap {
+    fn default() -> PinMap {
+        PinMap::new()
+    }
+}
+
+impl PinMap {
+    /** Map without assignments */
+    pub const fn new() -> PinMap {
+        PinMap {
+            pins: [None; IO_COUNT],
+            mirrors: 0,
+            error: None,
+        }
+    }
+
+    /** Route `function` to `pad` without pull */
+    pub const fn assign(self, pad: io, function: function) -> PinMap {
+        self.assign_pull(pad, function, pull::NONE)
+    }
+
+    /**
+     * Route `function` to `pad` with a pull. Assigning the same function and pull to a pad again
+     * is allowed, so maps of separate subsystems can be merged.
+     */
+    pub const fn assign_pull(mut self, pad: io, function: function, pull: pull) -> PinMap {
+        let mut i = 0;
+        while i < IO_COUNT {
+            if let Some(pin) = self.pins[i] {
+                if pin.function as usize == function as usize
+                    && pin.pad as usize != pad as usize
+                    && !self.is_mirror(pin.pad)
+                {
+                    return self.fail(PinError::FunctionInUse(function, pin.pad, pad));
+                }
+            }
+            i += 1;
+        }
+        match self.pins[pad as usize] {
+            Some(pin) if pin.function as usize != function as usize => {
+                self.fail(PinError::PadInUse(pad, pin.function, function))
+            }
+            Some(pin) if pin.pull as usize != pull as usize => {
+                self.fail(PinError::PullConflict(pad, pin.pull, pull))
+            }
+            _ => {
+                self.pins[pad as usize] = Some(Pin {
+                    pad,
+                    function,
+                    pull,
+                });
+                self.mirrors &= !(1 << pad as usize);
+                self
+            }
+        }
+    }
+
+    /**
+     * Route `function` to `pad` as well, without pull. Unlike with `assign` the function may be on
+     * another pad already, its output then shows on both, e.g. for a logic analyzer.
+     */
+    pub const fn mirror(mut self, pad: io, function: function) -> PinMap {
+        match self.pins[pad as usize] {
+            Some(pin) if pin.function as usize != function as usize => {
+                self.fail(PinError::PadInUse(pad, pin.function, function))
+            }
+            Some(_) => self,
+            None => {
+                self.pins[pad as usize] = Some(Pin {
+                    pad,
+                    function,
+                    pull: pull::NONE,
+                });
+                self.mirrors |= 1 << pad as usize;
+                self
+            }
+        }
+    }
+
+    /** Whether the pad carries a copy made with `mirror` */
+    pub const fn is_mirror(&self, pad: io) -> bool {
+        self.mirrors & (1 << pad as usize) != 0
+    }
+
+    /** Set the pull of an assigned pad that has none yet */
+    pub const fn pull(mut self, pad: io, pull: pull) -> PinMap {
+        match self.pins[pad as usize] {
+            None => self.fail(PinError::Unassigned(pad)),
+            Some(pin) => {
+                if pin.pull as usize != pull::NONE as usize && pin.pull as usize != pull as usize {
+                    return self.fail(PinError::PullConflict(pad, pin.pull, pull));
+                }
+                self.pins[pad as usize] = Some(Pin {
+                    pad,
+                    function: pin.function,
+                    pull,
+                });
+                self
+            }
+        }
+    }
+
+    /** Add all assignments of `other`, they have to agree with the ones already made */
+    pub const fn merge(mut self, other: &PinMap) -> PinMap {
+        if let Some(error) = other.error {
+            return self.fail(error);
+        }
+        let mut i = 0;
+        while i < IO_COUNT {
+            if let Some(pin) = other.pins[i] {
+                self = if other.is_mirror(pin.pad) {
+                    self.mirror(pin.pad, pin.function).pull(pin.pad, pin.pull)
+                } else {
+                    self.assign_pull(pin.pad, pin.function, pin.pull)
+                };
+            }
+            i += 1;
+        }
+        self
+    }
+
+    /** Keep the first error only, later ones are usually caused by it */
+    const fn fail(self, error: PinError) -> PinMap {
+        match self.error {
+            None => PinMap {
+                error: Some(error),
+                ..self
+            },
+            Some(_) => self,
+        }
+    }
+
+    /** First conflict found while building the map */
+    pub const fn check(&self) -> Result<(), PinError> {
+        match self.error {
+            None => Ok(()),
+            Some(error) => Err(error),
+        }
+    }
+
+    /** Pass the map through unchanged, or fail const evaluation when it has a conflict */
+    pub const fn validate(self) -> PinMap {
+        match self.error {
+            None => self,
+            Some(PinError::PadInUse(..)) => panic!("pin map routes two functions to one pad"),
+            Some(PinError::FunctionInUse(..)) => panic!("pin map routes a function to two pads"),
+            Some(PinError::PullConflict(..)) => panic!("pin map has conflicting pulls"),
+            Some(PinError::Unassigned(..)) => panic!("pin map pulls a pad without function"),
+            Some(PinError::NotOwned(..)) => panic!("pin map routes a pad owned elsewhere"),
+        }
+    }
+
+    /** Assignment of a pad */
+    pub const fn get(&self, pad: io) -> Option<Pin> {
+        self.pins[pad as usize]
+    }
+
+    /** Pad a function is routed to, not counting mirrors */
+    pub const fn pad_of(&self, function: function) -> Option<io> {
+        let mut i = 0;
+        while i < IO_COUNT {
+            if let Some(pin) = self.pins[i] {
+                if pin.function as usize == function as usize && !self.is_mirror(pin.pad) {
+                    return Some(pin.pad);
+                }
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /** All assignments, by pad */
+    pub fn pins(&self) -> impl Iterator<Item = &Pin> {
+        self.pins.iter().filter_map(Option::as_ref)
+    }
+
+    /** Program the assignments into `fpioa`, if the map has no conflicts */
+    pub(crate) fn apply_to<R: Registers>(&self, fpioa: &Fpioa<R>) -> Result<(), PinError> {
+        self.check()?;
+        for pin in self.pins() {
+            fpioa.set_function(pin.pad, pin.function);
+            fpioa.set_io_pull(pin.pad, pin.pull);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! CorgiDude pin map
+//!
+//! What every `def::io` pad of the board is wired to, grouped by subsystem. Binaries hand their
+//! `Pads` to `apply` with the subsystems they use, instead of routing them pad by pad. The table as
+//! a whole is checked for conflicts at compile time, see `CORGIDUDE`.
+use crate::def::io;
+use crate::fpioa::{function, pull, Fpioa};
+use crate::pads::Pads;
+use crate::pin_map::{PinError, PinMap};
+use crate::regs::{Mmio, Registers};
+use crate::sysctl::{clock, Sysctl};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum subsystem {
+    /** JTAG debug port */
+    JTAG,
+    /** UARTHS to the STM32 used for flashing and the console */
+    ISP,
+    /** ESP8285 on UART1, enable on GPIOHS4 */
+    WIFI,
+    /** Single color LEDs on GPIO3-5 and the RGB LED on GPIO0-2 */
+    LEDS,
+    /** Keys on GPIOHS5-7, pulled up */
+    KEYS,
+    /** Microphone array on I2S0 */
+    MIC,
+    /** DAC on I2S2 */
+    DAC,
+    /** I2C bus 1 (NS2009, MSA300) */
+    I2C,
+    /** SD card on SPI1, chip select on GPIOHS0 */
+    SD,
+    /** LCD on SPI0, D/C on GPIOHS2 and reset on GPIOHS3 */
+    LCD,
+    /** OV2640 camera, data pins bypass the FPIOA */
+    DVP,
+    /** Not connected, or nothing to route */
+    FREE,
+    /**
+     * Logic analyzer probes mirroring the LCD bus, see `PROBE_PADS`. They share pads with `LEDS`
+     * and `KEYS`, so this is not part of `ALL` and has to be asked for.
+     */
+    PROBES,
+}
+
+impl subsystem {
+    pub const ALL: &'static [subsystem] = &[
+        subsystem::JTAG,
+        subsystem::ISP,
+        subsystem::WIFI,
+        subsystem::LEDS,
+        subsystem::KEYS,
+        subsystem::MIC,
+        subsystem::DAC,
+        subsystem::I2C,
+        subsystem::SD,
+        subsystem::LCD,
+        subsystem::DVP,
+        subsystem::FREE,
+    ];
+
+    /** Pin map of this subsystem alone */
+    pub const fn map(self) -> PinMap {
+        map(&[self])
+    }
+}
+
+/** What a pad is used for */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Role {
+    pub pad: io,
+    pub subsystem: subsystem,
+    /** Function to route to the pad, `None` leaves it at its reset function */
+    pub function: Option<function>,
+    pub pull: pull,
+}
+
+impl Role {
+    const fn new(pad: io, subsystem: subsystem, function: function, pull: pull) -> Role {
+        Role {
+            pad,
+            subsystem,
+            function: Some(function),
+            pull,
+        }
+    }
+
+    const fn unrouted(pad: io, subsystem: subsystem) -> Role {
+        Role {
+            pad,
+            subsystem,
+            function: None,
+            pull: pull::NONE,
+        }
+    }
+}
+
+/** Every pad of the board, by pad number */
+#[rustfmt::skip]
+pub const PINS: &[Role] = &[
+    Role::new(io::JTAG_TCK, subsystem::JTAG, function::JTAG_TCLK, pull::NONE),
+    Role::new(io::JTAG_TDI, subsystem::JTAG, function::JTAG_TDI, pull::NONE),
+    Role::new(io::JTAG_TMS, subsystem::JTAG, function::JTAG_TMS, pull::NONE),
+    Role::new(io::JTAG_TDO, subsystem::JTAG, function::JTAG_TDO, pull::NONE),
+    Role::new(io::ISP_RX, subsystem::ISP, function::UARTHS_RX, pull::NONE),
+    Role::new(io::ISP_TX, subsystem::ISP, function::UARTHS_TX, pull::NONE),
+    Role::new(io::WIFI_TX, subsystem::WIFI, function::UART1_RX, pull::NONE),
+    Role::new(io::WIFI_RX, subsystem::WIFI, function::UART1_TX, pull::NONE),
+    Role::new(io::WIFI_EN, subsystem::WIFI, function::GPIOHS4, pull::NONE),
+    Role::unrouted(io::IO9, subsystem::FREE),
+    Role::unrouted(io::IO10, subsystem::FREE),
+    Role::unrouted(io::IO11, subsystem::FREE),
+    Role::new(io::IO12, subsystem::LEDS, function::GPIO3, pull::NONE),
+    Role::new(io::IO13, subsystem::LEDS, function::GPIO4, pull::NONE),
+    Role::new(io::IO14, subsystem::LEDS, function::GPIO5, pull::NONE),
+    Role::new(io::IO15, subsystem::KEYS, function::GPIOHS5, pull::UP),
+    Role::new(io::BOOT, subsystem::KEYS, function::GPIOHS6, pull::UP),
+    Role::new(io::IO17, subsystem::KEYS, function::GPIOHS7, pull::UP),
+    Role::new(io::IO18, subsystem::MIC, function::I2S0_SCLK, pull::NONE),
+    Role::new(io::IO19, subsystem::MIC, function::I2S0_WS, pull::NONE),
+    Role::new(io::IO20, subsystem::MIC, function::I2S0_IN_D3, pull::NONE),
+    Role::new(io::IO21, subsystem::MIC, function::I2S0_IN_D2, pull::NONE),
+    Role::new(io::RGB_LED_R, subsystem::LEDS, function::GPIO0, pull::NONE),
+    Role::new(io::RGB_LED_B, subsystem::LEDS, function::GPIO2, pull::NONE),
+    Role::new(io::RGB_LED_G, subsystem::LEDS, function::GPIO1, pull::NONE),
+    /* LED ring of the microphone board, no driver yet */
+    Role::unrouted(io::IO25, subsystem::MIC),
+    Role::new(io::IO26, subsystem::SD, function::SPI1_D1, pull::NONE),
+    Role::new(io::IO27, subsystem::SD, function::SPI1_SCLK, pull::NONE),
+    Role::new(io::IO28, subsystem::SD, function::SPI1_D0, pull::NONE),
+    Role::new(io::IO29, subsystem::SD, function::GPIOHS0, pull::DOWN),
+    Role::new(io::IO30, subsystem::I2C, function::I2C1_SCLK, pull::NONE),
+    Role::new(io::IO31, subsystem::I2C, function::I2C1_SDA, pull::NONE),
+    Role::unrouted(io::IO32, subsystem::FREE),
+    Role::new(io::IO33, subsystem::DAC, function::I2S2_WS, pull::NONE),
+    Role::new(io::IO34, subsystem::DAC, function::I2S2_OUT_D1, pull::NONE),
+    Role::new(io::IO35, subsystem::DAC, function::I2S2_SCLK, pull::NONE),
+    Role::new(io::LCD_CS, subsystem::LCD, function::SPI0_SS3, pull::NONE),
+    Role::new(io::LCD_RST, subsystem::LCD, function::GPIOHS3, pull::DOWN),
+    Role::new(io::LCD_DC, subsystem::LCD, function::GPIOHS2, pull::DOWN),
+    Role::new(io::LCD_WR, subsystem::LCD, function::SPI0_SCLK, pull::NONE),
+    Role::new(io::DVP_SDA, subsystem::DVP, function::SCCB_SDA, pull::NONE),
+    Role::new(io::DVP_SCL, subsystem::DVP, function::SCCB_SCLK, pull::NONE),
+    Role::new(io::DVP_RST, subsystem::DVP, function::CMOS_RST, pull::NONE),
+    Role::new(io::DVP_VSYNC, subsystem::DVP, function::CMOS_VSYNC, pull::NONE),
+    Role::new(io::DVP_PWDN, subsystem::DVP, function::CMOS_PWDN, pull::NONE),
+    Role::new(io::DVP_HSYNC, subsystem::DVP, function::CMOS_HREF, pull::NONE),
+    Role::new(io::DVP_XCLK, subsystem::DVP, function::CMOS_XCLK, pull::NONE),
+    Role::new(io::DVP_PCLK, subsystem::DVP, function::CMOS_PCLK, pull::NONE),
+];
+
+/** Pads, functions and pulls of `subsystem::PROBES`, by probe channel */
+pub const PROBE_PADS: &[(io, function, pull)] = &[
+    (io::IO15, function::SPI0_D0, pull::NONE),
+    (io::IO14, function::SPI0_D1, pull::NONE),
+    (io::IO13, function::SPI0_SCLK, pull::NONE),
+    (io::IO12, function::SPI0_SS3, pull::NONE),
+    /* Pulled down like the D/C pad it mirrors */
+    (io::IO11, function::GPIOHS2, pull::DOWN),
+];
+
+/** Keys and the GPIOHS pins `subsystem::KEYS` routes them to, they read low when pressed */
+pub const KEYS: &[(io, u8)] = &[(io::IO15, 5), (io::BOOT, 6), (io::IO17, 7)];
+
+/** Every routed pad of the board, fails the build if the table has a conflict */
+pub const CORGIDUDE: PinMap = map(subsystem::ALL).validate();
+
+const fn selected(subsystems: &[subsystem], subsystem: subsystem) -> bool {
+    let mut i = 0;
+    while i < subsystems.len() {
+        if subsystems[i] as usize == subsystem as usize {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/** Pin map of some subsystems */
+pub const fn map(subsystems: &[subsystem]) -> PinMap {
+    let mut map = PinMap::new();
+    let mut i = 0;
+    while i < PINS.len() {
+        let role = PINS[i];
+        if let Some(function) = role.function {
+            if selected(subsystems, role.subsystem) {
+                map = map.assign_pull(role.pad, function, role.pull);
+            }
+        }
+        i += 1;
+    }
+    if selected(subsystems, subsystem::PROBES) {
+        let mut i = 0;
+        while i < PROBE_PADS.len() {
+            let (pad, function, pull) = PROBE_PADS[i];
+            map = map.mirror(pad, function).pull(pad, pull);
+            i += 1;
+        }
+    }
+    map
+}
+
+/** Role of a pad */
+pub fn role(pad: io) -> &'static Role {
+    &PINS[pad as usize]
+}
+
+/**
+ * Route the pads of some subsystems and return the pads they don't use. Every pad the subsystems
+ * use has to be in `pads`, so nothing else holds one it could reroute. The SPI0 data functions of
+ * the LCD and the camera data pins bypass the FPIOA, with `LCD` or `DVP` the SPI0/DVP data pins
+ * are enabled for them.
+ */
+pub fn apply(pads: Pads, subsystems: &[subsystem]) -> Result<Pads, PinError> {
+    apply_on(&Sysctl::new(Mmio), &Fpioa::new(Mmio), pads, subsystems)
+}
+
+/** `apply` through the given SYSCTL and FPIOA */
+pub fn apply_on<R: Registers>(
+    sysctl: &Sysctl<R>,
+    fpioa: &Fpioa<R>,
+    mut pads: Pads,
+    subsystems: &[subsystem],
+) -> Result<Pads, PinError> {
+    let map = map(subsystems);
+    map.check()?;
+    if let Some(pin) = map.pins().find(|pin| !pads.has(pin.pad)) {
+        return Err(PinError::NotOwned(pin.pad));
+    }
+    for pin in map.pins() {
+        pads.remove(pin.pad);
+    }
+    route_on(sysctl, fpioa, subsystems)?;
+    Ok(pads)
+}
+
+/** Route every pad of the board */
+pub fn apply_all(pads: Pads) -> Result<Pads, PinError> {
+    apply(pads, subsystem::ALL)
+}
+
+/** `apply` for code that can't own the pads, like the fault handler */
+pub(crate) fn route(subsystems: &[subsystem]) -> Result<(), PinError> {
+    route_on(&Sysctl::new(Mmio), &Fpioa::new(Mmio), subsystems)
+}
+
+fn route_on<R: Registers>(
+    sysctl: &Sysctl<R>,
+    fpioa: &Fpioa<R>,
+    subsystems: &[subsystem],
+) -> Result<(), PinError> {
+    sysctl.clock_enable(clock::FPIOA);
+    map(subsystems).apply_to(fpioa)?;
+    if selected(subsystems, subsystem::LCD) || selected(subsystems, subsystem::DVP) {
+        sysctl.set_spi0_dvp_data(true);
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Platform-level interrupt controller
+//!
+//! External interrupts of the current hart go through `handle`, which claims each pending source,
+//! calls the handler registered for it and completes it. With the `interrupt-handler` feature
+//! the board installs `MachineExternal` to do that, binaries with their own turn it off and call
+//! `handle` themselves.
+use core::cell::UnsafeCell;
+
+use k210_hal::pac;
+use riscv::register::{mhartid, mie, mstatus};
+
+/** Number of interrupt sources, source 0 means "none" */
+pub const IRQ_SOURCES: usize = 66;
+
+/** All GPIO pins share one source */
+pub const IRQ_GPIO: usize = 23;
+
+/** First GPIOHS source, GPIOHS pin `n` is `IRQ_GPIOHS0 + n` */
+pub const IRQ_GPIOHS0: usize = 34;
+
+/** Highest priority, priority 0 never fires */
+pub const PRIORITY_MAX: u32 = 7;
+
+/** Called with the number of the source that fired */
+pub type irq_handler = fn(usize);
+
+struct Handlers(UnsafeCell<[Option<irq_handler>; IRQ_SOURCES]>);
+
+unsafe impl Sync for Handlers {}
+
+static HANDLERS: Handlers = Handlers(UnsafeCell::new([None; IRQ_SOURCES]));
+
+fn plic() -> &'static pac::plic::RegisterBlock {
+    unsafe { &*pac::PLIC::ptr() }
+}
+
+fn hart() -> usize {
+    mhartid::read()
+}
+
+pub fn set_priority(irq: usize, priority: u32) {
+    plic().priority[irq].write(|w| unsafe { w.bits(priority) });
+}
+
+/** Sources with a priority at or below the threshold are masked on the current hart */
+pub fn set_threshold(threshold: u32) {
+    plic().targets[hart()]
+        .threshold
+        .write(|w| unsafe { w.bits(threshold) });
+}
+
+/** Let a source interrupt the current hart */
+pub fn enable(irq: usize) {
+    riscv::interrupt::free(|_| {
+        plic().target_enables[hart()].enable[irq / 32]
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << (irq % 32))) });
+    })
+}
+
+pub fn disable(irq: usize) {
+    riscv::interrupt::free(|_| {
+        plic().target_enables[hart()].enable[irq / 32]
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << (irq % 32))) });
+    })
+}
+
+/** Call `handler` when `irq` fires, replacing the previous one */
+pub fn register(irq: usize, handler: irq_handler) {
+    riscv::interrupt::free(|_| unsafe {
+        (*HANDLERS.0.get())[irq] = Some(handler);
+    })
+}
+
+pub fn unregister(irq: usize) {
+    riscv::interrupt::free(|_| unsafe {
+        (*HANDLERS.0.get())[irq] = None;
+    })
+}
+
+/** Highest priority pending source of the current hart, if any */
+pub fn claim() -> Option<usize> {
+    match plic().targets[hart()].claim.read().bits() as usize {
+        0 => None,
+        irq => Some(irq),
+    }
+}
+
+/** Finish handling a claimed source, so it can fire again */
+pub fn complete(irq: usize) {
+    plic().targets[hart()]
+        .claim
+        .write(|w| unsafe { w.bits(irq as u32) });
+}
+
+/** Dispatch all pending sources to their handlers */
+pub fn handle() {
+    while let Some(irq) = claim() {
+        let handler = unsafe { (*HANDLERS.0.get())[irq] };
+        if let Some(handler) = handler {
+            handler(irq);
+        }
+        complete(irq);
+    }
+}
+
+/** Unmask external interrupts on the current hart and turn on interrupts */
+pub fn enable_interrupts() {
+    set_threshold(0);
+    unsafe {
+        mie::set_mext();
+        mstatus::set_mie();
+    }
+}
+
+#[cfg(all(feature = "interrupt-handler", target_arch = "riscv64"))]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub extern "C" fn MachineExternal() {
+    handle();
+}
@@ -1,5 +1,7 @@
+use core::cmp::Ordering;
+
 /** PLL configuration */
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Params {
     pub clkr: u8,
     pub clkf: u8,
@@ -107,8 +109,8 @@ const F_MIN: u32 = 1;
 const F_MAX: u32 = 64;
 const OD_MIN: u32 = 1;
 const OD_MAX: u32 = 16;
-const OUT_MIN: u32 = (VCO_MIN as u32) / OD_MAX; /* 21_250_000 */
-const OUT_MAX: u32 = (VCO_MAX as u32) / OD_MIN; /* 1_750_000_000 */
+pub const OUT_MIN: u32 = (VCO_MIN as u32) / OD_MAX; /* 21_250_000 */
+pub const OUT_MAX: u32 = (VCO_MAX as u32) / OD_MIN; /* 1_750_000_000 */
 const IN_MIN: u32 = DIV_MIN * R_MIN; /* 13_300_000 */
 const IN_MAX: u32 = OUT_MAX; /* 1_750_000_000 */
 
@@ -484,3 +486,192 @@ pub fn compute_params(freq_in: u32, freq_out: u32) -> Option<Params> {
 
     return best;
 }
+
+/** A valid PLL setting together with the frequencies it results in */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub params: Params,
+    /** VCO frequency in Hz: freq_in * f / r */
+    pub vco: u32,
+    /** Output frequency in Hz: freq_in * f / (r * od) */
+    pub freq: u32,
+    /** Relative error of the output frequency in parts per million */
+    pub error_ppm: u32,
+    /** Whether the output frequency is hit without any error */
+    pub exact: bool,
+}
+
+impl Candidate {
+    pub fn r(&self) -> u32 {
+        u32::from(self.params.clkr) + 1
+    }
+
+    pub fn f(&self) -> u32 {
+        u32::from(self.params.clkf) + 1
+    }
+
+    pub fn od(&self) -> u32 {
+        u32::from(self.params.clkod) + 1
+    }
+}
+
+/** Iterator over every in-spec (r, f, od) setting, see `candidates` */
+pub struct Candidates {
+    freq_in: u32,
+    freq_out: u32,
+    /** Position in the r/od/f space, f varies fastest */
+    index: u32,
+}
+
+const CANDIDATE_COUNT: u32 = R_MAX * OD_MAX * F_MAX;
+
+impl Iterator for Candidates {
+    type Item = Candidate;
+
+    fn next(&mut self) -> Option<Candidate> {
+        while self.index < CANDIDATE_COUNT {
+            let index = self.index;
+            self.index += 1;
+
+            let f = index % F_MAX + F_MIN;
+            let od = (index / F_MAX) % OD_MAX + OD_MIN;
+            let r = index / (F_MAX * OD_MAX) + R_MIN;
+
+            let div = self.freq_in / r;
+            if div < DIV_MIN || div > DIV_MAX {
+                continue;
+            }
+            let vco = div_round_closest(u64::from(self.freq_in) * u64::from(f), r);
+            if vco > VCO_MAX || vco < VCO_MIN {
+                continue;
+            }
+
+            /* Compare freq_in * f against freq_out * r * od to get the exact error */
+            let actual = u64::from(self.freq_in) * u64::from(f);
+            let wanted = u64::from(self.freq_out) * u64::from(r * od);
+            let diff = if actual > wanted {
+                actual - wanted
+            } else {
+                wanted - actual
+            };
+            let error_ppm = (diff * 1_000_000 + wanted / 2) / wanted;
+
+            return Some(Candidate {
+                params: Params {
+                    clkr: (r - 1) as u8,
+                    clkf: (f - 1) as u8,
+                    clkod: (od - 1) as u8,
+                    bwadj: (f - 1) as u8,
+                },
+                vco: vco as u32,
+                freq: div_round_closest(actual, r * od) as u32,
+                error_ppm: if error_ppm > u64::from(u32::max_value()) {
+                    u32::max_value()
+                } else {
+                    error_ppm as u32
+                },
+                exact: diff == 0,
+            });
+        }
+        None
+    }
+}
+
+/**
+ * Enumerate every (r, f, od) setting that keeps the divided clock and the VCO in spec, with its
+ * output frequency and error relative to `freq_out`. Nothing is yielded when either frequency
+ * is outside of what the PLL can take or produce.
+ */
+pub fn candidates(freq_in: u32, freq_out: u32) -> Candidates {
+    let in_range =
+        freq_out >= OUT_MIN && freq_out <= OUT_MAX && freq_in >= IN_MIN && freq_in <= IN_MAX;
+    Candidates {
+        freq_in,
+        freq_out,
+        index: if in_range { 0 } else { CANDIDATE_COUNT },
+    }
+}
+
+/**
+ * Which VCO frequency to favour among acceptable settings. With a bounded `max_error_ppm` this
+ * wins over the error within the bound, without a bound it only decides between equally close
+ * settings.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum vco_preference {
+    /** Don't care, only the error counts */
+    ANY,
+    /** Highest VCO frequency, for the lowest jitter */
+    MAX,
+    /** Lowest VCO frequency, for the lowest power */
+    MIN,
+}
+
+/** Constraints and preferences for `compute_params_with` */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Preference {
+    /** Only accept settings that hit the output frequency exactly */
+    pub exact: bool,
+    /** Reject settings with a larger error, `u32::max_value()` for no bound */
+    pub max_error_ppm: u32,
+    pub vco: vco_preference,
+}
+
+impl Preference {
+    /** Smallest error, like `compute_params` */
+    pub const CLOSEST: Preference = Preference {
+        exact: false,
+        max_error_ppm: u32::max_value(),
+        vco: vco_preference::ANY,
+    };
+
+    /** Exact output frequency from the fastest VCO, e.g. for I2S audio clocks */
+    pub const LOW_JITTER: Preference = Preference {
+        exact: true,
+        max_error_ppm: 0,
+        vco: vco_preference::MAX,
+    };
+
+    pub fn accepts(&self, candidate: &Candidate) -> bool {
+        (!self.exact || candidate.exact) && candidate.error_ppm <= self.max_error_ppm
+    }
+
+    /** Whether `a` should be picked over `b`, both being acceptable */
+    pub fn prefers(&self, a: &Candidate, b: &Candidate) -> bool {
+        /* An exact match beats a sub-ppm error that rounds to zero */
+        let by_error = a
+            .exact
+            .cmp(&b.exact)
+            .then_with(|| b.error_ppm.cmp(&a.error_ppm));
+        let by_vco = match self.vco {
+            vco_preference::ANY => Ordering::Equal,
+            vco_preference::MAX => a.vco.cmp(&b.vco),
+            vco_preference::MIN => b.vco.cmp(&a.vco),
+        };
+        /* Without a bound any far-off setting would do, as long as its VCO is fast or slow */
+        let order = if self.max_error_ppm == u32::max_value() {
+            by_error.then(by_vco)
+        } else {
+            by_vco.then(by_error)
+        };
+        order == Ordering::Greater
+    }
+}
+
+/**
+ * Pick the setting for `freq_out` that `preference` accepts and favours most. Unlike
+ * `compute_params` this walks all 16384 r/f/od settings.
+ */
+pub fn compute_params_with(
+    freq_in: u32,
+    freq_out: u32,
+    preference: &Preference,
+) -> Option<Candidate> {
+    let mut best: Option<Candidate> = None;
+    for candidate in candidates(freq_in, freq_out).filter(|c| preference.accepts(c)) {
+        if best.map_or(true, |best| preference.prefers(&candidate, &best)) {
+            best = Some(candidate);
+        }
+    }
+    best
+}
@@ -0,0 +1,123 @@
+//! IO power domains
+//!
+//! The 48 IO pads are powered in eight banks of six, each running at either 3.3V or 1.8V. A
+//! `PowerDomains` holds the voltage of every bank and refuses to be applied when a pad of
+//! `def::io` would end up on a bank with the wrong voltage for what is connected to it.
+use core::fmt;
+
+use crate::def::io;
+use crate::sysctl::{self, io_power_mode, power_bank};
+
+/** Number of IO pads in a power bank */
+pub const PADS_PER_BANK: usize = 6;
+
+/** Voltages the CorgiDude peripherals need on their pads */
+pub const PAD_VOLTAGES: &[(io, io_power_mode)] = &[
+    /* ESP8285 */
+    (io::WIFI_TX, io_power_mode::V33),
+    (io::WIFI_RX, io_power_mode::V33),
+    (io::WIFI_EN, io_power_mode::V33),
+    /* SD card */
+    (io::IO26, io_power_mode::V33),
+    (io::IO27, io_power_mode::V33),
+    (io::IO28, io_power_mode::V33),
+    (io::IO29, io_power_mode::V33),
+    /* OV2640 */
+    (io::DVP_SDA, io_power_mode::V18),
+    (io::DVP_SCL, io_power_mode::V18),
+    (io::DVP_RST, io_power_mode::V18),
+    (io::DVP_VSYNC, io_power_mode::V18),
+    (io::DVP_PWDN, io_power_mode::V18),
+    (io::DVP_HSYNC, io_power_mode::V18),
+    (io::DVP_XCLK, io_power_mode::V18),
+    (io::DVP_PCLK, io_power_mode::V18),
+];
+
+/** Bank that powers a pad */
+pub fn bank_of(pad: io) -> power_bank {
+    power_bank::ALL[pad as usize / PADS_PER_BANK]
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerError {
+    /** The pad needs the given voltage, but its bank is set to the other one */
+    WrongVoltage(io, power_bank, io_power_mode),
+}
+
+impl fmt::Display for PowerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PowerError::WrongVoltage(pad, bank, mode) => {
+                write!(f, "{:?} on {:?} needs {:?}", pad, bank, mode)
+            }
+        }
+    }
+}
+
+/** Voltage of every IO bank */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PowerDomains {
+    modes: [io_power_mode; 8],
+}
+
+/** CorgiDude setup: the camera banks (6 and 7) at 1.8V, everything else at 3.3V */
+pub const CORGIDUDE: PowerDomains = PowerDomains::new([
+    io_power_mode::V33,
+    io_power_mode::V33,
+    io_power_mode::V33,
+    io_power_mode::V33,
+    io_power_mode::V33,
+    io_power_mode::V33,
+    io_power_mode::V18,
+    io_power_mode::V18,
+]);
+
+impl PowerDomains {
+    /** Voltages indexed by `power_bank` */
+    pub const fn new(modes: [io_power_mode; 8]) -> PowerDomains {
+        PowerDomains { modes }
+    }
+
+    pub fn mode(&self, bank: power_bank) -> io_power_mode {
+        self.modes[bank as usize]
+    }
+
+    pub fn with(mut self, bank: power_bank, mode: io_power_mode) -> PowerDomains {
+        self.modes[bank as usize] = mode;
+        self
+    }
+
+    /** Check every pad of `pads` is on a bank with the voltage it needs */
+    pub fn check_pads(&self, pads: &[(io, io_power_mode)]) -> Result<(), PowerError> {
+        for &(pad, mode) in pads {
+            let bank = bank_of(pad);
+            if self.mode(bank) != mode {
+                return Err(PowerError::WrongVoltage(pad, bank, mode));
+            }
+        }
+        Ok(())
+    }
+
+    /** Check against the CorgiDude peripherals, see `PAD_VOLTAGES` */
+    pub fn check(&self) -> Result<(), PowerError> {
+        self.check_pads(PAD_VOLTAGES)
+    }
+
+    /** Program all banks, if the setup passes `check` */
+    pub fn apply(&self) -> Result<(), PowerError> {
+        self.check()?;
+        for &bank in power_bank::ALL {
+            sysctl::set_power_mode(bank, self.mode(bank));
+        }
+        Ok(())
+    }
+
+    /** Current voltages of the hardware */
+    pub fn read() -> PowerDomains {
+        let mut domains = CORGIDUDE;
+        for &bank in power_bank::ALL {
+            domains.modes[bank as usize] = sysctl::get_power_mode(bank);
+        }
+        domains
+    }
+}
@@ -0,0 +1,108 @@
+//! Power/performance profiles
+//!
+//! A profile is a set of clock targets for the planner plus the clocks it turns on and off.
+//! `set_profile` switches PLLs, dividers and clock gates in one go, so e.g. a camera board can
+//! drop to `LOW_POWER` between captures and come back without rebooting.
+use crate::clock_gate;
+use crate::clock_plan::{ClockPlan, Target};
+use crate::clock_tree::ClockTree;
+use crate::sysctl::{self, clock, pll, SysctlError};
+use crate::utils::cs;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum profile {
+    /** CPU at 400 MHz from PLL0 at 800 MHz, KPU at 400 MHz from PLL1 */
+    PERFORMANCE,
+    /** CPU at 200 MHz, KPU and PLL1 off */
+    BALANCED,
+    /** CPU on IN0, KPU, PLL1 and DVP off, PLL0 off unless SPI0-2, I2C or a timer still use it */
+    LOW_POWER,
+}
+
+/** What a profile sets up */
+pub struct Settings {
+    /**
+     * Frequencies to plan for. Profiles that run the CPU from PLL0 pin it, so the SPI and I2C
+     * dividers behind it stay valid across switches.
+     */
+    pub targets: &'static [Target],
+    /** Clocks to turn on, including those another profile turns off */
+    pub enable: &'static [clock],
+    /** Clocks to turn off, unless a `ClockGuard` still holds them */
+    pub disable: &'static [clock],
+}
+
+static PERFORMANCE: Settings = Settings {
+    targets: &[
+        Target::new(clock::PLL0, 800_000_000),
+        Target::new(clock::CPU, 400_000_000),
+        Target::new(clock::PLL1, 400_000_000),
+        Target::new(clock::AI, 400_000_000),
+    ],
+    enable: &[clock::PLL1, clock::AI, clock::DVP],
+    disable: &[],
+};
+
+static BALANCED: Settings = Settings {
+    targets: &[
+        Target::new(clock::PLL0, 800_000_000),
+        Target::new(clock::CPU, 200_000_000),
+    ],
+    enable: &[clock::DVP],
+    disable: &[clock::AI, clock::PLL1],
+};
+
+static LOW_POWER: Settings = Settings {
+    targets: &[Target::new(clock::CPU, sysctl::SYSCTRL_CLOCK_FREQ_IN0)],
+    enable: &[],
+    disable: &[clock::AI, clock::PLL1, clock::DVP],
+};
+
+impl profile {
+    pub const ALL: &'static [profile] =
+        &[profile::PERFORMANCE, profile::BALANCED, profile::LOW_POWER];
+
+    pub fn settings(self) -> &'static Settings {
+        match self {
+            profile::PERFORMANCE => &PERFORMANCE,
+            profile::BALANCED => &BALANCED,
+            profile::LOW_POWER => &LOW_POWER,
+        }
+    }
+
+    /** Clock plan of the profile, starting from `base` */
+    pub fn plan(self, base: &ClockTree) -> Result<ClockPlan<'static>, SysctlError> {
+        ClockPlan::solve(base, self.settings().targets)
+    }
+}
+
+/**
+ * Switch to a profile. The plan is solved before anything is touched, then clocks, dividers and
+ * gates are changed with interrupts disabled and clock listeners are notified once.
+ *
+ * PLL0 and PLL1 are powered down when no enabled clock runs from them anymore, a `ClockGuard` on
+ * `clock::PLL0`/`clock::PLL1` keeps them running.
+ */
+pub fn set_profile(profile: profile) -> Result<(), SysctlError> {
+    let settings = profile.settings();
+    let plan = profile.plan(&sysctl::clock_tree())?;
+    sysctl::clock_change(|| {
+        cs(|| {
+            plan.apply()?;
+            for &clock in settings.enable {
+                sysctl::clock_enable(clock);
+            }
+            for &clock in settings.disable {
+                if clock_gate::users(clock) == 0 {
+                    sysctl::sysctl_clock_disable(clock);
+                }
+            }
+            for &(pll, output) in &[(pll::PLL0, clock::PLL0), (pll::PLL1, clock::PLL1)] {
+                if clock_gate::users(output) == 0 && !sysctl::pll_in_use(pll) {
+                    sysctl::pll_power_down(pll);
+                }
+            }
+            Ok(())
+        })
+    })
+}
@@ -0,0 +1,187 @@
+//! PWM on the TIMER peripherals
+//!
+//! Each of the three timers has four channels. In PWM mode a channel drives its
+//! `TIMERn_TOGGLEm` FPIOA function low for `load_count` and high for `load_count2` timer clocks
+//! (from Kendryte pwm.c).
+use core::fmt;
+
+use crate::fpioa::function;
+use crate::regs::{Mmio, Registers};
+use crate::sysctl::{clock, Sysctl, SysctlError};
+
+/** Channels per timer */
+pub const PWM_CHANNELS: u8 = 4;
+
+/** Duty cycle of an output that is always high */
+pub const DUTY_MAX: u16 = u16::MAX;
+
+/* Bits of a channel control register */
+const CR_ENABLE: u32 = 0x1;
+const CR_USER_MODE: u32 = 0x2;
+const CR_INTERRUPT_MASK: u32 = 0x4;
+const CR_PWM_ENABLE: u32 = 0x8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum timer {
+    TIMER0,
+    TIMER1,
+    TIMER2,
+}
+
+impl timer {
+    pub const ALL: &'static [timer] = &[timer::TIMER0, timer::TIMER1, timer::TIMER2];
+
+    pub fn clock(self) -> clock {
+        match self {
+            timer::TIMER0 => clock::TIMER0,
+            timer::TIMER1 => clock::TIMER1,
+            timer::TIMER2 => clock::TIMER2,
+        }
+    }
+
+    /** FPIOA function carrying the output of `channel` */
+    pub fn output(self, channel: u8) -> function {
+        assert!(channel < PWM_CHANNELS, "no such PWM channel");
+        function::from_u8(function::TIMER0_TOGGLE1 as u8 + self as u8 * PWM_CHANNELS + channel)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PwmError {
+    /** Zero, or faster than the timer clock */
+    BadFrequency(u32),
+    /** The timer clock could not be worked out */
+    Clock(SysctlError),
+}
+
+impl fmt::Display for PwmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PwmError::BadFrequency(freq) => write!(f, "cannot generate {} Hz", freq),
+            PwmError::Clock(ref err) => write!(f, "timer clock: {}", err),
+        }
+    }
+}
+
+/** PWM driver on top of a register backend */
+pub struct Pwm<R> {
+    regs: R,
+}
+
+impl<R: Registers> Pwm<R> {
+    pub fn new(regs: R) -> Self {
+        Pwm { regs }
+    }
+
+    /** Turn on the clock of a timer */
+    pub fn init(&self, timer: timer) {
+        Sysctl::new(&self.regs).clock_enable(timer.clock());
+    }
+
+    /**
+     * Set up `channel` for `freq` Hz with `duty` out of `DUTY_MAX` high. Returns the frequency
+     * the timer actually makes, the closest whole divider of its clock. The channel keeps running
+     * or stays stopped, see `set_enable`.
+     */
+    pub fn set_frequency(
+        &self,
+        timer: timer,
+        channel: u8,
+        freq: u32,
+        duty: u16,
+    ) -> Result<u32, PwmError> {
+        let clock_freq = Sysctl::new(&self.regs)
+            .clock_get_freq(timer.clock())
+            .map_err(PwmError::Clock)?;
+        if freq == 0 || freq > clock_freq {
+            return Err(PwmError::BadFrequency(freq));
+        }
+        let periods = clock_freq / freq;
+        let enable = self.is_enabled(timer, channel);
+        unsafe {
+            let ptr = self.regs.timer(timer as usize);
+            (*ptr).channel[usize::from(channel)].control.write(|w| {
+                w.bits(
+                    CR_INTERRUPT_MASK
+                        | CR_PWM_ENABLE
+                        | CR_USER_MODE
+                        | if enable { CR_ENABLE } else { 0 },
+                )
+            });
+        }
+        self.set_counts(timer, channel, periods, duty);
+        Ok(clock_freq / periods)
+    }
+
+    /** Change the duty cycle of `channel`, keeping its frequency */
+    pub fn set_duty(&self, timer: timer, channel: u8, duty: u16) {
+        let periods = unsafe {
+            let ptr = self.regs.timer(timer as usize);
+            (*ptr).channel[usize::from(channel)]
+                .load_count
+                .read()
+                .bits()
+                + (*ptr).load_count2[usize::from(channel)].read().bits()
+        };
+        self.set_counts(timer, channel, periods, duty);
+    }
+
+    fn set_counts(&self, timer: timer, channel: u8, periods: u32, duty: u16) {
+        /* The timer stops on a zero count, so fully on still has one clock of low time */
+        let high = ((u64::from(periods) * u64::from(duty) / u64::from(DUTY_MAX)) as u32)
+            .min(periods.saturating_sub(1));
+        unsafe {
+            let ptr = self.regs.timer(timer as usize);
+            (*ptr).channel[usize::from(channel)]
+                .load_count
+                .write(|w| w.bits(periods - high));
+            (*ptr).load_count2[usize::from(channel)].write(|w| w.bits(high));
+        }
+    }
+
+    pub fn set_enable(&self, timer: timer, channel: u8, enable: bool) {
+        unsafe {
+            let ptr = self.regs.timer(timer as usize);
+            let channel = &(*ptr).channel[usize::from(channel)];
+            if enable {
+                channel
+                    .control
+                    .modify(|r, w| w.bits(r.bits() | CR_INTERRUPT_MASK | CR_ENABLE));
+            } else {
+                channel.control.modify(|r, w| w.bits(r.bits() & !CR_ENABLE));
+            }
+        }
+    }
+
+    pub fn is_enabled(&self, timer: timer, channel: u8) -> bool {
+        unsafe {
+            let ptr = self.regs.timer(timer as usize);
+            (*ptr).channel[usize::from(channel)].control.read().bits() & CR_ENABLE != 0
+        }
+    }
+}
+
+/** PWM of the running SoC */
+fn pwm() -> Pwm<Mmio> {
+    Pwm::new(Mmio)
+}
+
+pub fn init(timer: timer) {
+    pwm().init(timer)
+}
+
+pub fn set_frequency(timer: timer, channel: u8, freq: u32, duty: u16) -> Result<u32, PwmError> {
+    pwm().set_frequency(timer, channel, freq, duty)
+}
+
+pub fn set_duty(timer: timer, channel: u8, duty: u16) {
+    pwm().set_duty(timer, channel, duty)
+}
+
+pub fn set_enable(timer: timer, channel: u8, enable: bool) {
+    pwm().set_enable(timer, channel, enable)
+}
+
+pub fn is_enabled(timer: timer, channel: u8) -> bool {
+    pwm().is_enabled(timer, channel)
+}
@@ -0,0 +1,191 @@
+//! Register access backends
+//!
+//! The SYSCTL, FPIOA, GPIO, GPIOHS and TIMER drivers reach their registers through a `Registers`
+//! implementation: `Mmio` is the memory-mapped hardware, `Fake` keeps zero-initialized register
+//! blocks in RAM so the drivers can run on a host and their writes can be inspected afterwards.
+use core::cell::{Cell, UnsafeCell};
+use core::mem;
+
+use k210_hal::pac;
+use riscv::register::mcycle;
+
+use crate::sleep;
+
+pub trait Registers {
+    fn sysctl(&self) -> *const pac::sysctl::RegisterBlock;
+    fn fpioa(&self) -> *const pac::fpioa::RegisterBlock;
+    fn gpio(&self) -> *const pac::gpio::RegisterBlock;
+    fn gpiohs(&self) -> *const pac::gpiohs::RegisterBlock;
+    /** TIMER0, TIMER1 or TIMER2 */
+    fn timer(&self, n: usize) -> *const pac::timer0::RegisterBlock;
+
+    /** Wait between register writes that need time to settle */
+    fn usleep(&self, n: usize);
+
+    /** Free-running cycle counter, used to bound busy waits */
+    fn cycles(&self) -> usize;
+}
+
+impl<'a, R: Registers> Registers for &'a R {
+    fn sysctl(&self) -> *const pac::sysctl::RegisterBlock {
+        (**self).sysctl()
+    }
+
+    fn fpioa(&self) -> *const pac::fpioa::RegisterBlock {
+        (**self).fpioa()
+    }
+
+    fn gpio(&self) -> *const pac::gpio::RegisterBlock {
+        (**self).gpio()
+    }
+
+    fn gpiohs(&self) -> *const pac::gpiohs::RegisterBlock {
+        (**self).gpiohs()
+    }
+
+    fn timer(&self, n: usize) -> *const pac::timer0::RegisterBlock {
+        (**self).timer(n)
+    }
+
+    fn usleep(&self, n: usize) {
+        (**self).usleep(n)
+    }
+
+    fn cycles(&self) -> usize {
+        (**self).cycles()
+    }
+}
+
+/** The memory-mapped peripherals of the running SoC */
+#[derive(Copy, Clone)]
+pub struct Mmio;
+
+impl Registers for Mmio {
+    fn sysctl(&self) -> *const pac::sysctl::RegisterBlock {
+        pac::SYSCTL::ptr()
+    }
+
+    fn fpioa(&self) -> *const pac::fpioa::RegisterBlock {
+        pac::FPIOA::ptr()
+    }
+
+    fn gpio(&self) -> *const pac::gpio::RegisterBlock {
+        pac::GPIO::ptr()
+    }
+
+    fn gpiohs(&self) -> *const pac::gpiohs::RegisterBlock {
+        pac::GPIOHS::ptr()
+    }
+
+    fn timer(&self, n: usize) -> *const pac::timer0::RegisterBlock {
+        match n {
+            0 => pac::TIMER0::ptr(),
+            1 => pac::TIMER1::ptr(),
+            2 => pac::TIMER2::ptr(),
+            _ => panic!("no such timer"),
+        }
+    }
+
+    fn usleep(&self, n: usize) {
+        sleep::usleep(n)
+    }
+
+    fn cycles(&self) -> usize {
+        mcycle::read()
+    }
+}
+
+const SYSCTL_WORDS: usize = mem::size_of::<pac::sysctl::RegisterBlock>() / 4;
+const FPIOA_WORDS: usize = mem::size_of::<pac::fpioa::RegisterBlock>() / 4;
+const GPIO_WORDS: usize = mem::size_of::<pac::gpio::RegisterBlock>() / 4;
+const GPIOHS_WORDS: usize = mem::size_of::<pac::gpiohs::RegisterBlock>() / 4;
+const TIMER_WORDS: usize = mem::size_of::<pac::timer0::RegisterBlock>() / 4;
+
+/** Register block backed by RAM, aligned like the real one */
+#[repr(C, align(8))]
+struct Block<T>(UnsafeCell<T>);
+
+/** In-memory register blocks, all registers start out as zero */
+pub struct Fake {
+    sysctl: Block<[u32; SYSCTL_WORDS]>,
+    fpioa: Block<[u32; FPIOA_WORDS]>,
+    gpio: Block<[u32; GPIO_WORDS]>,
+    gpiohs: Block<[u32; GPIOHS_WORDS]>,
+    timers: [Block<[u32; TIMER_WORDS]>; 3],
+    /** Advances by one on every read, so bounded waits always finish */
+    cycles: Cell<usize>,
+}
+
+impl Fake {
+    pub const fn new() -> Fake {
+        Fake {
+            sysctl: Block(UnsafeCell::new([0; SYSCTL_WORDS])),
+            fpioa: Block(UnsafeCell::new([0; FPIOA_WORDS])),
+            gpio: Block(UnsafeCell::new([0; GPIO_WORDS])),
+            gpiohs: Block(UnsafeCell::new([0; GPIOHS_WORDS])),
+            timers: [
+                Block(UnsafeCell::new([0; TIMER_WORDS])),
+                Block(UnsafeCell::new([0; TIMER_WORDS])),
+                Block(UnsafeCell::new([0; TIMER_WORDS])),
+            ],
+            cycles: Cell::new(0),
+        }
+    }
+
+    /** Typed view of the fake SYSCTL, to preset or inspect registers */
+    pub fn sysctl_regs(&self) -> &pac::sysctl::RegisterBlock {
+        unsafe { &*self.sysctl() }
+    }
+
+    /** Typed view of the fake FPIOA */
+    pub fn fpioa_regs(&self) -> &pac::fpioa::RegisterBlock {
+        unsafe { &*self.fpioa() }
+    }
+
+    /** Typed view of the fake GPIO */
+    pub fn gpio_regs(&self) -> &pac::gpio::RegisterBlock {
+        unsafe { &*self.gpio() }
+    }
+
+    /** Typed view of the fake GPIOHS */
+    pub fn gpiohs_regs(&self) -> &pac::gpiohs::RegisterBlock {
+        unsafe { &*self.gpiohs() }
+    }
+
+    /** Typed view of a fake TIMER */
+    pub fn timer_regs(&self, n: usize) -> &pac::timer0::RegisterBlock {
+        unsafe { &*self.timer(n) }
+    }
+}
+
+impl Registers for Fake {
+    fn sysctl(&self) -> *const pac::sysctl::RegisterBlock {
+        self.sysctl.0.get() as *const _
+    }
+
+    fn fpioa(&self) -> *const pac::fpioa::RegisterBlock {
+        self.fpioa.0.get() as *const _
+    }
+
+    fn gpio(&self) -> *const pac::gpio::RegisterBlock {
+        self.gpio.0.get() as *const _
+    }
+
+    fn gpiohs(&self) -> *const pac::gpiohs::RegisterBlock {
+        self.gpiohs.0.get() as *const _
+    }
+
+    fn timer(&self, n: usize) -> *const pac::timer0::RegisterBlock {
+        self.timers[n].0.get() as *const _
+    }
+
+    fn usleep(&self, _n: usize) {
+        /* Registers in RAM settle immediately */
+    }
+
+    fn cycles(&self) -> usize {
+        let cycles = self.cycles.get();
+        self.cycles.set(cycles.wrapping_add(1));
+        cycles
+    }
+}
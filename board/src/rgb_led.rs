@@ -0,0 +1,331 @@
+//! RGB LED
+//!
+//! The three LEDs of the RGB LED (RGB_LED_R/G/B) are driven by PWM on three channels of one
+//! timer. Colors are 24-bit, scaled by a global brightness and then gamma corrected, so equal
+//! steps in a color look like equal steps in light. Animations are computed from the time since
+//! they started and never block: call `RgbLed::update` from the main loop often enough to look
+//! smooth, every 10 ms or so.
+use crate::fpioa::{Fpioa, PadConfig};
+use crate::pads::{Pad, RGB_LED_B, RGB_LED_G, RGB_LED_R};
+use crate::pwm::{timer, Pwm, PwmError};
+use crate::regs::Registers;
+
+/** PWM frequency of the LEDs, well above what the eye can see flicker */
+pub const PWM_FREQ: u32 = 2_000;
+
+/** Timer channels of the red, green and blue LEDs */
+const LED_CHANNELS: u8 = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0, 0, 0);
+    pub const WHITE: Color = Color::new(255, 255, 255);
+    pub const RED: Color = Color::new(255, 0, 0);
+    pub const GREEN: Color = Color::new(0, 255, 0);
+    pub const BLUE: Color = Color::new(0, 0, 255);
+    pub const YELLOW: Color = Color::new(255, 255, 0);
+    pub const CYAN: Color = Color::new(0, 255, 255);
+    pub const MAGENTA: Color = Color::new(255, 0, 255);
+    pub const ORANGE: Color = Color::new(255, 96, 0);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b }
+    }
+
+    /** Color from `0xRRGGBB` */
+    pub const fn from_rgb24(rgb: u32) -> Color {
+        Color::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+
+    pub const fn rgb24(self) -> u32 {
+        (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
+    }
+
+    /** The color at `level` out of 255 of its intensity */
+    pub fn scale(self, level: u8) -> Color {
+        let scale = |c: u8| ((u16::from(c) * u16::from(level) + 127) / 255) as u8;
+        Color::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+
+    /** The color `num / den` of the way from `self` to `to` */
+    pub fn lerp(self, to: Color, num: u32, den: u32) -> Color {
+        if den == 0 || num >= den {
+            return to;
+        }
+        let lerp = |from: u8, to: u8| {
+            let from = i64::from(from);
+            (from + (i64::from(to) - from) * i64::from(num) / i64::from(den)) as u8
+        };
+        Color::new(lerp(self.r, to.r), lerp(self.g, to.g), lerp(self.b, to.b))
+    }
+}
+
+/** Gamma 2.2, from 8-bit intensity to PWM duty out of `pwm::DUTY_MAX` */
+#[rustfmt::skip]
+static GAMMA: [u16; 256] = [
+    0, 0, 2, 4, 7, 11, 17, 24,
+    32, 42, 53, 65, 79, 94, 111, 129,
+    148, 169, 192, 216, 242, 270, 299, 330,
+    362, 396, 432, 469, 508, 549, 591, 635,
+    681, 729, 779, 830, 883, 938, 995, 1053,
+    1113, 1175, 1239, 1305, 1373, 1443, 1514, 1587,
+    1663, 1740, 1819, 1900, 1983, 2068, 2155, 2243,
+    2334, 2427, 2521, 2618, 2717, 2817, 2920, 3024,
+    3131, 3240, 3350, 3463, 3578, 3694, 3813, 3934,
+    4057, 4182, 4309, 4438, 4570, 4703, 4838, 4976,
+    5115, 5257, 5401, 5547, 5695, 5845, 5998, 6152,
+    6309, 6468, 6629, 6792, 6957, 7124, 7294, 7466,
+    7640, 7816, 7994, 8175, 8358, 8543, 8730, 8919,
+    9111, 9305, 9501, 9699, 9900, 10102, 10307, 10515,
+    10724, 10936, 11150, 11366, 11585, 11806, 12029, 12254,
+    12482, 12712, 12944, 13179, 13416, 13655, 13896, 14140,
+    14386, 14635, 14885, 15138, 15394, 15652, 15912, 16174,
+    16439, 16706, 16975, 17247, 17521, 17798, 18077, 18358,
+    18642, 18928, 19216, 19507, 19800, 20095, 20393, 20694,
+    20996, 21301, 21609, 21919, 22231, 22546, 22863, 23182,
+    23504, 23829, 24156, 24485, 24817, 25151, 25487, 25826,
+    26168, 26512, 26858, 27207, 27558, 27912, 28268, 28627,
+    28988, 29351, 29717, 30086, 30457, 30830, 31206, 31585,
+    31966, 32349, 32735, 33124, 33514, 33908, 34304, 34702,
+    35103, 35507, 35913, 36321, 36732, 37146, 37562, 37981,
+    38402, 38825, 39252, 39680, 40112, 40546, 40982, 41421,
+    41862, 42306, 42753, 43202, 43654, 44108, 44565, 45025,
+    45487, 45951, 46418, 46888, 47360, 47835, 48313, 48793,
+    49275, 49761, 50249, 50739, 51232, 51728, 52226, 52727,
+    53230, 53736, 54245, 54756, 55270, 55787, 56306, 56828,
+    57352, 57879, 58409, 58941, 59476, 60014, 60554, 61097,
+    61642, 62190, 62741, 63295, 63851, 64410, 64971, 65535,
+];
+
+/** PWM duty that makes `level` out of 255 look like that share of full light */
+pub fn gamma(level: u8) -> u16 {
+    GAMMA[usize::from(level)]
+}
+
+/** PWM duties of the red, green and blue LED showing `color` at `brightness` out of 255 */
+pub fn duties(color: Color, brightness: u8) -> [u16; 3] {
+    let color = color.scale(brightness);
+    [gamma(color.r), gamma(color.g), gamma(color.b)]
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Animation {
+    Solid(Color),
+    /** Go from `from` to `to` in `duration_ms`, then stay at `to` */
+    Fade {
+        from: Color,
+        to: Color,
+        duration_ms: u32,
+    },
+    /** Ramp up to `color` and back down to black every `period_ms` */
+    Breathe {
+        color: Color,
+        period_ms: u32,
+    },
+    /** `color` for `on_ms`, then black for `off_ms`, over and over */
+    Blink {
+        color: Color,
+        on_ms: u32,
+        off_ms: u32,
+    },
+    /** `count` blinks of `on_ms` on and `off_ms` off, then `pause_ms` dark, over and over */
+    Count {
+        color: Color,
+        count: u8,
+        on_ms: u32,
+        off_ms: u32,
+        pause_ms: u32,
+    },
+}
+
+impl Animation {
+    /** Color `elapsed_ms` after the animation started */
+    pub fn color_at(&self, elapsed_ms: u32) -> Color {
+        match *self {
+            Animation::Solid(color) => color,
+            Animation::Fade {
+                from,
+                to,
+                duration_ms,
+            } => from.lerp(to, elapsed_ms, duration_ms),
+            Animation::Breathe { color, period_ms } => {
+                if period_ms < 2 {
+                    return color;
+                }
+                let period_ms = u64::from(period_ms);
+                let phase = u64::from(elapsed_ms) % period_ms;
+                let rise = period_ms / 2;
+                let level = if phase < rise {
+                    phase * 255 / rise
+                } else {
+                    (period_ms - phase) * 255 / (period_ms - rise)
+                };
+                color.scale(level as u8)
+            }
+            Animation::Blink {
+                color,
+                on_ms,
+                off_ms,
+            } => {
+                let period_ms = on_ms.saturating_add(off_ms);
+                if period_ms == 0 || elapsed_ms % period_ms < on_ms {
+                    color
+                } else {
+                    Color::BLACK
+                }
+            }
+            Animation::Count {
+                color,
+                count,
+                on_ms,
+                off_ms,
+                pause_ms,
+            } => {
+                let blink_ms = on_ms.saturating_add(off_ms);
+                let blinks_ms = blink_ms.saturating_mul(u32::from(count));
+                let period_ms = blinks_ms.saturating_add(pause_ms);
+                if period_ms == 0 {
+                    return color;
+                }
+                let phase = elapsed_ms % period_ms;
+                if phase < blinks_ms && phase % blink_ms < on_ms {
+                    color
+                } else {
+                    Color::BLACK
+                }
+            }
+        }
+    }
+
+    /** Whether the color stays the same from `elapsed_ms` on */
+    pub fn is_done(&self, elapsed_ms: u32) -> bool {
+        match *self {
+            Animation::Solid(_) => true,
+            Animation::Fade { duration_ms, .. } => elapsed_ms >= duration_ms,
+            Animation::Breathe { .. } | Animation::Blink { .. } | Animation::Count { .. } => false,
+        }
+    }
+}
+
+/** The RGB LED on PWM channels 0 to 2 of a timer */
+pub struct RgbLed<R> {
+    regs: R,
+    timer: timer,
+    /** The LED pads, routed to the timer for as long as the LED exists */
+    _pads: (RGB_LED_R, RGB_LED_G, RGB_LED_B),
+    brightness: u8,
+    animation: Animation,
+    /** Time the animation started, in ms */
+    start: u32,
+    /** Duties on the channels, `None` until the first update */
+    shown: Option<[u16; 3]>,
+}
+
+impl<R: Registers> RgbLed<R> {
+    /**
+     * Start PWM on channels 0 to 2 of `timer` with the LED off and route them to the LED pads.
+     * The LEDs are active low, the pads invert the PWM output so a higher duty is more light.
+     */
+    pub fn new(
+        regs: R,
+        timer: timer,
+        red: RGB_LED_R,
+        green: RGB_LED_G,
+        blue: RGB_LED_B,
+    ) -> Result<RgbLed<R>, PwmError> {
+        let pwm = Pwm::new(&regs);
+        pwm.init(timer);
+        for channel in 0..LED_CHANNELS {
+            pwm.set_frequency(timer, channel, PWM_FREQ, 0)?;
+            pwm.set_enable(timer, channel, true);
+        }
+        let pads = (
+            route(&regs, red, timer, 0),
+            route(&regs, green, timer, 1),
+            route(&regs, blue, timer, 2),
+        );
+        Ok(RgbLed {
+            regs,
+            timer,
+            _pads: pads,
+            brightness: 255,
+            animation: Animation::Solid(Color::BLACK),
+            start: 0,
+            shown: None,
+        })
+    }
+
+    /** Show `color` from the next `update` on */
+    pub fn set_color(&mut self, color: Color) {
+        self.animation = Animation::Solid(color);
+    }
+
+    /** Scale all colors to `brightness` out of 255 */
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /** Run `animation` from `now_ms` on, replacing the current one */
+    pub fn animate(&mut self, animation: Animation, now_ms: u32) {
+        self.animation = animation;
+        self.start = now_ms;
+    }
+
+    pub fn animation(&self) -> Animation {
+        self.animation
+    }
+
+    /** Whether the current animation has come to rest by `now_ms` */
+    pub fn is_done(&self, now_ms: u32) -> bool {
+        self.animation.is_done(now_ms.wrapping_sub(self.start))
+    }
+
+    /**
+     * Bring the LED to where the animation is at `now_ms` and return that color. The timestamp
+     * may wrap around, only the time since `animate` matters.
+     */
+    pub fn update(&mut self, now_ms: u32) -> Color {
+        let color = self.animation.color_at(now_ms.wrapping_sub(self.start));
+        let duties = duties(color, self.brightness);
+        if self.shown != Some(duties) {
+            let pwm = Pwm::new(&self.regs);
+            for (channel, &duty) in duties.iter().enumerate() {
+                pwm.set_duty(self.timer, channel as u8, duty);
+            }
+            self.shown = Some(duties);
+        }
+        color
+    }
+
+    /** Turn the LED dark until the next `animate` or `set_color`, the PWM keeps running */
+    pub fn off(&mut self) {
+        let pwm = Pwm::new(&self.regs);
+        for channel in 0..LED_CHANNELS {
+            pwm.set_duty(self.timer, channel, 0);
+        }
+        self.animation = Animation::Solid(Color::BLACK);
+        self.shown = Some([0; 3]);
+    }
+}
+
+/** Route the output of `channel` to an LED pad, inverted */
+fn route<R: Registers, P: Pad>(regs: &R, pad: P, timer: timer, channel: u8) -> P {
+    let function = timer.output(channel);
+    let config = PadConfig {
+        output_invert: true,
+        ..PadConfig::default_for(function)
+    };
+    Fpioa::new(regs)
+        .route_with(pad, function, &config)
+        .release()
+}
@@ -1,7 +1,21 @@
 //! Utilities for sleeping short timespans
-use crate::sysctl;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use crate::sysctl::{self, ClockChange};
+use crate::utils::cs;
 use riscv::register::mcycle;
 
+/** Milliseconds counted up to `BASE_CYCLE`, the cycle of the last CPU clock change */
+static BASE_MS: AtomicU32 = AtomicU32::new(0);
+static BASE_CYCLE: AtomicUsize = AtomicUsize::new(0);
+
+static SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+
+/** Fall back to the crystal frequency if the clock tree cannot be resolved */
+fn cpu_freq() -> usize {
+    sysctl::clock_get_freq(sysctl::clock::CPU).unwrap_or(sysctl::SYSCTRL_CLOCK_FREQ_IN0) as usize
+}
+
 pub fn cycle_sleep(n: usize) {
     let start = mcycle::read();
     while (mcycle::read().wrapping_sub(start)) < n {
@@ -10,6 +24,40 @@ pub fn cycle_sleep(n: usize) {
 }
 
 pub fn usleep(n: usize) {
-    let freq = sysctl::clock_get_freq(sysctl::clock::CPU) as usize;
-    cycle_sleep(freq * n / 1000000);
+    cycle_sleep(cpu_freq() * n / 1000000);
+}
+
+/**
+ * Milliseconds since reset, wrapping around after 49 days. Cycles are counted at the CPU clock
+ * they ran at, so this keeps going forward when the CPU clock changes.
+ */
+pub fn now_ms() -> u32 {
+    if !SUBSCRIBED.swap(true, Ordering::AcqRel) && sysctl::clock_subscribe(on_clock_change).is_err()
+    {
+        SUBSCRIBED.store(false, Ordering::Release);
+    }
+    cs(|| ms_at(mcycle::read(), cpu_freq()))
+}
+
+/** Milliseconds at cycle `now`, with the CPU at `freq` since the last change */
+fn ms_at(now: usize, freq: usize) -> u32 {
+    let cycles = now.wrapping_sub(BASE_CYCLE.load(Ordering::Relaxed));
+    BASE_MS
+        .load(Ordering::Relaxed)
+        .wrapping_add((cycles / (freq / 1000)) as u32)
+}
+
+/* Less than a millisecond is lost on every change */
+fn on_clock_change(change: &ClockChange) {
+    if change.affects(sysctl::clock::CPU) {
+        let before = change
+            .before
+            .freq(sysctl::clock::CPU)
+            .unwrap_or(sysctl::SYSCTRL_CLOCK_FREQ_IN0) as usize;
+        cs(|| {
+            let now = mcycle::read();
+            BASE_MS.store(ms_at(now, before), Ordering::Relaxed);
+            BASE_CYCLE.store(now, Ordering::Relaxed);
+        });
+    }
 }
@@ -0,0 +1,261 @@
+//! Device status on the RGB LED
+//!
+//! Units in the field have no serial cable, so what went wrong is blinked on the RGB LED. Every
+//! `fault` has its own number of blinks, repeated after a pause:
+//!
+//! - red: the firmware stopped, `halt` blinks forever and is where the panic handler ends up
+//! - orange: the firmware carries on without the part, shown by `Status::report`
+//!
+//! `fail` and `check` record the fault before panicking, so the panic handler knows which code to
+//! blink. A hook set with `set_hook` sees every fault, e.g. to put `fault::message` on the LCD.
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::gpio::{self, direction};
+use crate::pins::{self, subsystem};
+use crate::regs::Registers;
+use crate::rgb_led::{Animation, Color, RgbLed};
+use crate::sleep;
+use crate::sysctl::{self, clock};
+use crate::utils::cs;
+
+/* Timing of the blink codes, slow enough to count */
+const BLINK_ON_MS: u32 = 250;
+const BLINK_OFF_MS: u32 = 250;
+const PAUSE_MS: u32 = 1500;
+
+/** GPIO pins of the red, green and blue LED, as routed by `pins::subsystem::LEDS` */
+const LED_GPIOS: [u8; 3] = [0, 1, 2];
+
+/** Steps of the blink loop in `halt` */
+const HALT_STEP_MS: u32 = 10;
+
+/** Color of the codes of faults the firmware stopped at */
+pub const HALT_COLOR: Color = Color::RED;
+/** Color of the codes of faults the firmware got past */
+pub const REPORT_COLOR: Color = Color::ORANGE;
+/** Everything fine, breathing slowly */
+pub const OK_ANIMATION: Animation = Animation::Breathe {
+    color: Color::GREEN,
+    period_ms: 4000,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum fault {
+    /** A panic without a recorded fault */
+    PANIC,
+    CLOCK,
+    PINS,
+    POWER,
+    DMA,
+    LCD,
+    CAMERA,
+    SD_CARD,
+}
+
+impl fault {
+    pub const ALL: &'static [fault] = &[
+        fault::PANIC,
+        fault::CLOCK,
+        fault::PINS,
+        fault::POWER,
+        fault::DMA,
+        fault::LCD,
+        fault::CAMERA,
+        fault::SD_CARD,
+    ];
+
+    /** Blinks in the code of the fault */
+    pub fn blinks(self) -> u8 {
+        self as u8 + 1
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            fault::PANIC => "panic",
+            fault::CLOCK => "clock setup failed",
+            fault::PINS => "pin routing failed",
+            fault::POWER => "IO power setup failed",
+            fault::DMA => "no DMA channel",
+            fault::LCD => "LCD not responding",
+            fault::CAMERA => "camera not found",
+            fault::SD_CARD => "SD card failed",
+        }
+    }
+
+    /** Blink code of the fault in `color` */
+    pub fn code(self, color: Color) -> Animation {
+        Animation::Count {
+            color,
+            count: self.blinks(),
+            on_ms: BLINK_ON_MS,
+            off_ms: BLINK_OFF_MS,
+            pause_ms: PAUSE_MS,
+        }
+    }
+
+    fn from_u8(n: u8) -> Option<fault> {
+        fault::ALL.get(usize::from(n)).copied()
+    }
+}
+
+impl fmt::Display for fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (code {})", self.message(), self.blinks())
+    }
+}
+
+/** Called with every fault that is reported or halted at */
+pub type fault_hook = fn(fault);
+
+struct Hook(UnsafeCell<Option<fault_hook>>);
+
+unsafe impl Sync for Hook {}
+
+static HOOK: Hook = Hook(UnsafeCell::new(None));
+
+/** Fault for the panic handler, `NO_FAULT` if none */
+static FAULT: AtomicU8 = AtomicU8::new(NO_FAULT);
+const NO_FAULT: u8 = u8::MAX;
+
+/** Set once `halt` started, so a panic in the hook does not go round in circles */
+static HALTING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_hook(hook: fault_hook) {
+    cs(|| unsafe {
+        *HOOK.0.get() = Some(hook);
+    });
+}
+
+pub fn clear_hook() {
+    cs(|| unsafe {
+        *HOOK.0.get() = None;
+    });
+}
+
+fn call_hook(fault: fault) {
+    let hook = unsafe { *HOOK.0.get() };
+    if let Some(hook) = hook {
+        hook(fault);
+    }
+}
+
+/** Make `fault` the code a following panic blinks */
+pub fn record(fault: fault) {
+    FAULT.store(fault as u8, Ordering::SeqCst);
+}
+
+pub fn recorded() -> Option<fault> {
+    fault::from_u8(FAULT.load(Ordering::SeqCst))
+}
+
+/** Stop the firmware at `fault`: record it and panic with its message */
+pub fn fail(fault: fault) -> ! {
+    record(fault);
+    panic!("{}", fault)
+}
+
+/** Unwrap `result`, or fail at `fault` with the error in the panic message */
+pub fn check<T, E: fmt::Display>(result: Result<T, E>, fault: fault) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            record(fault);
+            panic!("{}: {}", fault, err)
+        }
+    }
+}
+
+/**
+ * Blink the code of `fault` in `HALT_COLOR` forever, with interrupts off. Only the LED GPIOs are
+ * used, not the timers, so this works whatever state the firmware broke down in; colors are
+ * shown at full or no brightness per LED. Halting again, e.g. from a panic in the hook, skips
+ * the hook and keeps blinking the new code on the LEDs as they are.
+ */
+pub fn halt(fault: fault) -> ! {
+    unsafe { riscv::interrupt::disable() };
+    if HALTING.swap(true, Ordering::SeqCst) {
+        blink(fault)
+    }
+
+    /*
+     * Route the LEDs back from PWM in case they were animated, they are active low. This takes
+     * the pads from whoever owns them, on purpose: nothing else runs anymore.
+     */
+    let _ = pins::route(&[subsystem::LEDS]);
+    sysctl::clock_enable(clock::GPIO);
+    let mask = led_mask();
+    gpio::set_pins(mask, mask);
+    for &pin in LED_GPIOS.iter() {
+        gpio::set_direction(pin, direction::OUTPUT);
+    }
+
+    call_hook(fault);
+    blink(fault)
+}
+
+fn led_mask() -> u8 {
+    LED_GPIOS.iter().fold(0, |mask, &pin| mask | 1 << pin)
+}
+
+/** The blink loop of `halt`, on LEDs that are already set up */
+fn blink(fault: fault) -> ! {
+    let mask = led_mask();
+    let code = fault.code(HALT_COLOR);
+    let mut elapsed_ms: u32 = 0;
+    loop {
+        let color = code.color_at(elapsed_ms);
+        let on = [color.r, color.g, color.b];
+        let mut value = mask;
+        for (&pin, &level) in LED_GPIOS.iter().zip(on.iter()) {
+            if level >= 0x80 {
+                value &= !(1 << pin);
+            }
+        }
+        gpio::set_pins(mask, value);
+        sleep::usleep(HALT_STEP_MS as usize * 1000);
+        elapsed_ms = elapsed_ms.wrapping_add(HALT_STEP_MS);
+    }
+}
+
+/** Status shown on the RGB LED while the firmware runs */
+pub struct Status<R> {
+    led: RgbLed<R>,
+    fault: Option<fault>,
+}
+
+impl<R: Registers> Status<R> {
+    /** Take over `led`, showing `OK_ANIMATION` from `now_ms` */
+    pub fn new(mut led: RgbLed<R>, now_ms: u32) -> Status<R> {
+        led.animate(OK_ANIMATION, now_ms);
+        Status { led, fault: None }
+    }
+
+    /** Blink the code of `fault` in `REPORT_COLOR` until `clear` or the next `report` */
+    pub fn report(&mut self, fault: fault, now_ms: u32) {
+        call_hook(fault);
+        self.fault = Some(fault);
+        self.led.animate(fault.code(REPORT_COLOR), now_ms);
+    }
+
+    /** Back to `OK_ANIMATION` */
+    pub fn clear(&mut self, now_ms: u32) {
+        self.fault = None;
+        self.led.animate(OK_ANIMATION, now_ms);
+    }
+
+    /** Fault shown right now */
+    pub fn fault(&self) -> Option<fault> {
+        self.fault
+    }
+
+    /** Bring the LED up to `now_ms`, call this from the main loop */
+    pub fn update(&mut self, now_ms: u32) {
+        self.led.update(now_ms);
+    }
+
+    pub fn led(&mut self) -> &mut RgbLed<R> {
+        &mut self.led
+    }
+}
@@ -1,15 +1,24 @@
 //! SYSCTL peripheral
 use k210_hal::pac;
 
-use core::convert::TryInto;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::clock_tree::ClockTree;
 use crate::pll_compute;
-use crate::sleep::usleep;
-use crate::utils::set_bit;
+use crate::regs::{Mmio, Registers};
+use crate::utils::{column, cs, get_bit, set_bit};
 
-const SYSCTRL_CLOCK_FREQ_IN0: u32 = 26000000;
+pub(crate) const SYSCTRL_CLOCK_FREQ_IN0: u32 = 26000000;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+/** Time a reset is held asserted in microseconds */
+pub const RESET_HOLD_US: usize = 10;
+
+/** Default number of CPU cycles to wait for a PLL to lock (100ms when running from IN0) */
+pub const PLL_LOCK_BUDGET: usize = 2_600_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum pll {
     /** PLL0 can usually be selected as alternative to IN0, for example the CPU
      * clock speed. It can be used as source for PLL2. */
@@ -20,7 +29,7 @@ pub enum pll {
     PLL2,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum clock_source {
     IN0,
     PLL0,
@@ -29,7 +38,7 @@ pub enum clock_source {
     ACLK,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum clock {
     PLL0,
     PLL1,
@@ -74,7 +83,54 @@ pub enum clock {
     IN0,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+impl clock {
+    /** All variants, in declaration order */
+    pub const ALL: &'static [clock] = &[
+        clock::PLL0,
+        clock::PLL1,
+        clock::PLL2,
+        clock::CPU,
+        clock::SRAM0,
+        clock::SRAM1,
+        clock::APB0,
+        clock::APB1,
+        clock::APB2,
+        clock::ROM,
+        clock::DMA,
+        clock::AI,
+        clock::DVP,
+        clock::FFT,
+        clock::GPIO,
+        clock::SPI0,
+        clock::SPI1,
+        clock::SPI2,
+        clock::SPI3,
+        clock::I2S0,
+        clock::I2S1,
+        clock::I2S2,
+        clock::I2C0,
+        clock::I2C1,
+        clock::I2C2,
+        clock::UART1,
+        clock::UART2,
+        clock::UART3,
+        clock::AES,
+        clock::FPIOA,
+        clock::TIMER0,
+        clock::TIMER1,
+        clock::TIMER2,
+        clock::WDT0,
+        clock::WDT1,
+        clock::SHA,
+        clock::OTP,
+        clock::RTC,
+        clock::ACLK,
+        clock::HCLK,
+        clock::IN0,
+    ];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum threshold {
     ACLK,
     APB0,
@@ -105,7 +161,53 @@ pub enum threshold {
     WDT1,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+impl threshold {
+    /** All variants, in declaration order */
+    pub const ALL: &'static [threshold] = &[
+        threshold::ACLK,
+        threshold::APB0,
+        threshold::APB1,
+        threshold::APB2,
+        threshold::SRAM0,
+        threshold::SRAM1,
+        threshold::AI,
+        threshold::DVP,
+        threshold::ROM,
+        threshold::SPI0,
+        threshold::SPI1,
+        threshold::SPI2,
+        threshold::SPI3,
+        threshold::TIMER0,
+        threshold::TIMER1,
+        threshold::TIMER2,
+        threshold::I2S0,
+        threshold::I2S1,
+        threshold::I2S2,
+        threshold::I2S0_M,
+        threshold::I2S1_M,
+        threshold::I2S2_M,
+        threshold::I2C0,
+        threshold::I2C1,
+        threshold::I2C2,
+        threshold::WDT0,
+        threshold::WDT1,
+    ];
+
+    /** Largest value the divider register can hold */
+    pub fn max(self) -> u32 {
+        use threshold::*;
+        match self {
+            ACLK => 0x3,
+            APB0 | APB1 | APB2 => 0x7,
+            SRAM0 | SRAM1 | AI | DVP | ROM => 0xf,
+            SPI0 | SPI1 | SPI2 | SPI3 | TIMER0 | TIMER1 | TIMER2 | I2S0_M | I2S1_M | I2S2_M
+            | I2C0 | I2C1 | I2C2 | WDT0 | WDT1 => 0xff,
+            I2S0 | I2S1 | I2S2 => 0xffff,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum clock_select {
     PLL0_BYPASS,
     PLL1_BYPASS,
@@ -119,13 +221,38 @@ pub enum clock_select {
     SPI3_SAMPLE,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+impl clock_select {
+    /** All variants, in declaration order */
+    pub const ALL: &'static [clock_select] = &[
+        clock_select::PLL0_BYPASS,
+        clock_select::PLL1_BYPASS,
+        clock_select::PLL2_BYPASS,
+        clock_select::PLL2,
+        clock_select::ACLK,
+        clock_select::SPI3,
+        clock_select::TIMER0,
+        clock_select::TIMER1,
+        clock_select::TIMER2,
+        clock_select::SPI3_SAMPLE,
+    ];
+
+    /** Largest value the select register can hold */
+    pub fn max(self) -> u8 {
+        match self {
+            /* 0 IN0, 1 PLL0, 2 PLL1 */
+            clock_select::PLL2 => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum io_power_mode {
     V33,
     V18,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum power_bank {
     BANK0 = 0,
     BANK1,
@@ -137,7 +264,20 @@ pub enum power_bank {
     BANK7,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+impl power_bank {
+    pub const ALL: &'static [power_bank] = &[
+        power_bank::BANK0,
+        power_bank::BANK1,
+        power_bank::BANK2,
+        power_bank::BANK3,
+        power_bank::BANK4,
+        power_bank::BANK5,
+        power_bank::BANK6,
+        power_bank::BANK7,
+    ];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum reset {
     SOC,
     ROM,
@@ -170,6 +310,55 @@ pub enum reset {
     RTC,
 }
 
+impl reset {
+    pub const ALL: &'static [reset] = &[
+        reset::SOC,
+        reset::ROM,
+        reset::DMA,
+        reset::AI,
+        reset::DVP,
+        reset::FFT,
+        reset::GPIO,
+        reset::SPI0,
+        reset::SPI1,
+        reset::SPI2,
+        reset::SPI3,
+        reset::I2S0,
+        reset::I2S1,
+        reset::I2S2,
+        reset::I2C0,
+        reset::I2C1,
+        reset::I2C2,
+        reset::UART1,
+        reset::UART2,
+        reset::UART3,
+        reset::AES,
+        reset::FPIOA,
+        reset::TIMER0,
+        reset::TIMER1,
+        reset::TIMER2,
+        reset::WDT0,
+        reset::WDT1,
+        reset::SHA,
+        reset::RTC,
+    ];
+}
+
+/** Why the SoC was last reset */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum reset_cause {
+    /** Power-on */
+    HARD,
+    /** Watchdog 0 timed out */
+    WDT0,
+    /** Watchdog 1 timed out */
+    WDT1,
+    /** Software reset through `reset::SOC` */
+    SOFT,
+    /** The external reset pin */
+    PIN,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum dma_channel {
     CHANNEL0 = 0,
@@ -181,6 +370,15 @@ pub enum dma_channel {
 }
 
 impl dma_channel {
+    pub const ALL: &'static [dma_channel] = &[
+        dma_channel::CHANNEL0,
+        dma_channel::CHANNEL1,
+        dma_channel::CHANNEL2,
+        dma_channel::CHANNEL3,
+        dma_channel::CHANNEL4,
+        dma_channel::CHANNEL5,
+    ];
+
     pub fn idx(self) -> usize {
         self as usize
     }
@@ -188,713 +386,850 @@ impl dma_channel {
 
 pub type dma_select = pac::sysctl::dma_sel0::DMA_SEL0_A;
 
-fn clock_bus_en(clock: clock, en: bool) {
-    /*
-     * The timer is under APB0, to prevent apb0_clk_en1 and apb0_clk_en0
-     * on same register, we split it to peripheral and central two
-     * registers, to protect CPU close apb0 clock accidentally.
-     *
-     * The apb0_clk_en0 and apb0_clk_en1 have same function,
-     * one of them set, the APB0 clock enable.
-     */
-
-    /* The APB clock should carefully disable */
-    if en {
-        match clock {
-            /*
-             * These peripheral devices are under APB0
-             * GPIO, UART1, UART2, UART3, SPI_SLAVE, I2S0, I2S1,
-             * I2S2, I2C0, I2C1, I2C2, FPIOA, SHA256, TIMER0,
-             * TIMER1, TIMER2
-             */
-            clock::GPIO
-            | clock::SPI2
-            | clock::I2S0
-            | clock::I2S1
-            | clock::I2S2
-            | clock::I2C0
-            | clock::I2C1
-            | clock::I2C2
-            | clock::UART1
-            | clock::UART2
-            | clock::UART3
-            | clock::FPIOA
-            | clock::TIMER0
-            | clock::TIMER1
-            | clock::TIMER2
-            | clock::SHA => unsafe {
-                (*pac::SYSCTL::ptr())
-                    .clk_en_cent
-                    .modify(|_, w| w.apb0_clk_en().bit(en));
-            },
-
-            /*
-             * These peripheral devices are under APB1
-             * WDT, AES, OTP, DVP, SYSCTL
-             */
-            clock::AES | clock::WDT0 | clock::WDT1 | clock::OTP | clock::RTC => unsafe {
-                (*pac::SYSCTL::ptr())
-                    .clk_en_cent
-                    .modify(|_, w| w.apb1_clk_en().bit(en));
-            },
-
-            /*
-             * These peripheral devices are under APB2
-             * SPI0, SPI1
-             */
-            clock::SPI0 | clock::SPI1 => unsafe {
-                (*pac::SYSCTL::ptr())
-                    .clk_en_cent
-                    .modify(|_, w| w.apb2_clk_en().bit(en));
-            },
+/** Errors reported by the SYSCTL functions */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SysctlError {
+    /** The clock source cannot be used as input here */
+    UnsupportedSource(clock_source),
+    /** No PLL parameters reach the requested frequency (in Hz) */
+    PllOutOfRange(pll, u32),
+    /** The PLL did not achieve lock */
+    LockTimeout(pll),
+    /** The clock select holds, or was given, a value without meaning */
+    InvalidSelect(clock_select, u8),
+    /** The divider value does not fit in its register */
+    InvalidThreshold(threshold, u32),
+    /** The requested frequency (in Hz) makes no sense for the clock */
+    InvalidTarget(clock, u32),
+    /** All `MAX_CLOCK_LISTENERS` slots are taken */
+    TooManyListeners,
+}
 
-            _ => {}
+impl fmt::Display for SysctlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SysctlError::UnsupportedSource(source) => {
+                write!(f, "unsupported clock source {:?}", source)
+            }
+            SysctlError::PllOutOfRange(pll, freq) => {
+                write!(f, "{:?} cannot be set to {} Hz", pll, freq)
+            }
+            SysctlError::LockTimeout(pll) => write!(f, "{:?} did not lock", pll),
+            SysctlError::InvalidSelect(which, select) => {
+                write!(f, "invalid clock select {} for {:?}", select, which)
+            }
+            SysctlError::InvalidThreshold(which, value) => {
+                write!(f, "invalid threshold {} for {:?}", value, which)
+            }
+            SysctlError::InvalidTarget(clock, freq) => {
+                write!(f, "invalid target {} Hz for {:?}", freq, clock)
+            }
+            SysctlError::TooManyListeners => write!(f, "too many clock listeners"),
         }
     }
 }
 
-fn clock_device_en(clock: clock, en: bool) {
-    unsafe {
-        let ptr = pac::SYSCTL::ptr();
-        match clock {
-            clock::PLL0 => (*ptr).pll0.modify(|_, w| w.out_en().bit(en)),
-            clock::PLL1 => (*ptr).pll1.modify(|_, w| w.out_en().bit(en)),
-            clock::PLL2 => (*ptr).pll2.modify(|_, w| w.out_en().bit(en)),
-            clock::CPU => (*ptr).clk_en_cent.modify(|_, w| w.cpu_clk_en().bit(en)),
-            clock::SRAM0 => (*ptr).clk_en_cent.modify(|_, w| w.sram0_clk_en().bit(en)),
-            clock::SRAM1 => (*ptr).clk_en_cent.modify(|_, w| w.sram1_clk_en().bit(en)),
-            clock::APB0 => (*ptr).clk_en_cent.modify(|_, w| w.apb0_clk_en().bit(en)),
-            clock::APB1 => (*ptr).clk_en_cent.modify(|_, w| w.apb1_clk_en().bit(en)),
-            clock::APB2 => (*ptr).clk_en_cent.modify(|_, w| w.apb2_clk_en().bit(en)),
-            clock::ROM => (*ptr).clk_en_peri.modify(|_, w| w.rom_clk_en().bit(en)),
-            clock::DMA => (*ptr).clk_en_peri.modify(|_, w| w.dma_clk_en().bit(en)),
-            clock::AI => (*ptr).clk_en_peri.modify(|_, w| w.ai_clk_en().bit(en)),
-            clock::DVP => (*ptr).clk_en_peri.modify(|_, w| w.dvp_clk_en().bit(en)),
-            clock::FFT => (*ptr).clk_en_peri.modify(|_, w| w.fft_clk_en().bit(en)),
-            clock::SPI3 => (*ptr).clk_en_peri.modify(|_, w| w.spi3_clk_en().bit(en)),
-            clock::GPIO => (*ptr).clk_en_peri.modify(|_, w| w.gpio_clk_en().bit(en)),
-            clock::SPI2 => (*ptr).clk_en_peri.modify(|_, w| w.spi2_clk_en().bit(en)),
-            clock::I2S0 => (*ptr).clk_en_peri.modify(|_, w| w.i2s0_clk_en().bit(en)),
-            clock::I2S1 => (*ptr).clk_en_peri.modify(|_, w| w.i2s1_clk_en().bit(en)),
-            clock::I2S2 => (*ptr).clk_en_peri.modify(|_, w| w.i2s2_clk_en().bit(en)),
-            clock::I2C0 => (*ptr).clk_en_peri.modify(|_, w| w.i2c0_clk_en().bit(en)),
-            clock::I2C1 => (*ptr).clk_en_peri.modify(|_, w| w.i2c1_clk_en().bit(en)),
-            clock::I2C2 => (*ptr).clk_en_peri.modify(|_, w| w.i2c2_clk_en().bit(en)),
-            clock::UART1 => (*ptr).clk_en_peri.modify(|_, w| w.uart1_clk_en().bit(en)),
-            clock::UART2 => (*ptr).clk_en_peri.modify(|_, w| w.uart2_clk_en().bit(en)),
-            clock::UART3 => (*ptr).clk_en_peri.modify(|_, w| w.uart3_clk_en().bit(en)),
-            clock::FPIOA => (*ptr).clk_en_peri.modify(|_, w| w.fpioa_clk_en().bit(en)),
-            clock::TIMER0 => (*ptr).clk_en_peri.modify(|_, w| w.timer0_clk_en().bit(en)),
-            clock::TIMER1 => (*ptr).clk_en_peri.modify(|_, w| w.timer1_clk_en().bit(en)),
-            clock::TIMER2 => (*ptr).clk_en_peri.modify(|_, w| w.timer2_clk_en().bit(en)),
-            clock::SHA => (*ptr).clk_en_peri.modify(|_, w| w.sha_clk_en().bit(en)),
-            clock::AES => (*ptr).clk_en_peri.modify(|_, w| w.aes_clk_en().bit(en)),
-            clock::WDT0 => (*ptr).clk_en_peri.modify(|_, w| w.wdt0_clk_en().bit(en)),
-            clock::WDT1 => (*ptr).clk_en_peri.modify(|_, w| w.wdt1_clk_en().bit(en)),
-            clock::OTP => (*ptr).clk_en_peri.modify(|_, w| w.otp_clk_en().bit(en)),
-            clock::RTC => (*ptr).clk_en_peri.modify(|_, w| w.rtc_clk_en().bit(en)),
-            clock::SPI0 => (*ptr).clk_en_peri.modify(|_, w| w.spi0_clk_en().bit(en)),
-            clock::SPI1 => (*ptr).clk_en_peri.modify(|_, w| w.spi1_clk_en().bit(en)),
-            clock::ACLK | clock::HCLK | clock::IN0 => { /* no separate enables */ }
-        }
+/** Map PLL2 cksel value to clock source */
+pub(crate) fn pll2_cksel_to_source(bits: u8) -> Result<clock_source, SysctlError> {
+    match bits {
+        0 => Ok(clock_source::IN0),
+        1 => Ok(clock_source::PLL0),
+        2 => Ok(clock_source::PLL1),
+        _ => Err(SysctlError::InvalidSelect(clock_select::PLL2, bits)),
     }
 }
 
-pub fn clock_enable(clock: clock) {
-    clock_bus_en(clock, true);
-    clock_device_en(clock, true);
+/** Map clock source to PLL2 cksel value */
+pub(crate) fn pll2_source_to_cksel(source: clock_source) -> Result<u8, SysctlError> {
+    match source {
+        clock_source::IN0 => Ok(0),
+        clock_source::PLL0 => Ok(1),
+        clock_source::PLL1 => Ok(2),
+        _ => Err(SysctlError::UnsupportedSource(source)),
+    }
 }
 
-pub fn sysctl_clock_disable(clock: clock) {
-    clock_bus_en(clock, false);
-    clock_device_en(clock, false);
-}
+/** APB bus a peripheral clock is attached to, if any */
+pub fn clock_bus(clock: clock) -> Option<clock> {
+    match clock {
+        /*
+         * These peripheral devices are under APB0
+         * GPIO, UART1, UART2, UART3, SPI_SLAVE, I2S0, I2S1,
+         * I2S2, I2C0, I2C1, I2C2, FPIOA, SHA256, TIMER0,
+         * TIMER1, TIMER2
+         */
+        clock::GPIO
+        | clock::SPI2
+        | clock::I2S0
+        | clock::I2S1
+        | clock::I2S2
+        | clock::I2C0
+        | clock::I2C1
+        | clock::I2C2
+        | clock::UART1
+        | clock::UART2
+        | clock::UART3
+        | clock::FPIOA
+        | clock::TIMER0
+        | clock::TIMER1
+        | clock::TIMER2
+        | clock::SHA => Some(clock::APB0),
 
-/// Set clock divider
-pub fn clock_set_threshold(which: threshold, threshold: u32) {
-    // TODO: this should take a multiplier directly, not a peripheral specific value
-    unsafe {
-        let ptr = pac::SYSCTL::ptr();
-        match which {
-            /* 2 bit wide */
-            threshold::ACLK => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.aclk_divider_sel().bits(threshold as u8)),
-
-            /* 3 bit wide */
-            threshold::APB0 => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.apb0_clk_sel().bits(threshold as u8)),
-            threshold::APB1 => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.apb1_clk_sel().bits(threshold as u8)),
-            threshold::APB2 => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.apb2_clk_sel().bits(threshold as u8)),
-
-            /* 4 bit wide */
-            threshold::SRAM0 => (*ptr)
-                .clk_th0
-                .modify(|_, w| w.sram0_gclk().bits(threshold as u8)),
-            threshold::SRAM1 => (*ptr)
-                .clk_th0
-                .modify(|_, w| w.sram1_gclk().bits(threshold as u8)),
-            threshold::AI => (*ptr)
-                .clk_th0
-                .modify(|_, w| w.ai_gclk().bits(threshold as u8)),
-            threshold::DVP => (*ptr)
-                .clk_th0
-                .modify(|_, w| w.dvp_gclk().bits(threshold as u8)),
-            threshold::ROM => (*ptr)
-                .clk_th0
-                .modify(|_, w| w.rom_gclk().bits(threshold as u8)),
-
-            /* 8 bit wide */
-            threshold::SPI0 => (*ptr)
-                .clk_th1
-                .modify(|_, w| w.spi0_clk().bits(threshold as u8)),
-            threshold::SPI1 => (*ptr)
-                .clk_th1
-                .modify(|_, w| w.spi1_clk().bits(threshold as u8)),
-            threshold::SPI2 => (*ptr)
-                .clk_th1
-                .modify(|_, w| w.spi2_clk().bits(threshold as u8)),
-            threshold::SPI3 => (*ptr)
-                .clk_th1
-                .modify(|_, w| w.spi3_clk().bits(threshold as u8)),
-            threshold::TIMER0 => (*ptr)
-                .clk_th2
-                .modify(|_, w| w.timer0_clk().bits(threshold as u8)),
-            threshold::TIMER1 => (*ptr)
-                .clk_th2
-                .modify(|_, w| w.timer1_clk().bits(threshold as u8)),
-            threshold::TIMER2 => (*ptr)
-                .clk_th2
-                .modify(|_, w| w.timer2_clk().bits(threshold as u8)),
-            threshold::I2S0_M => (*ptr)
-                .clk_th4
-                .modify(|_, w| w.i2s0_mclk().bits(threshold as u8)),
-            threshold::I2S1_M => (*ptr)
-                .clk_th4
-                .modify(|_, w| w.i2s1_mclk().bits(threshold as u8)),
-            threshold::I2S2_M => (*ptr)
-                .clk_th5
-                .modify(|_, w| w.i2s2_mclk().bits(threshold as u8)),
-            threshold::I2C0 => (*ptr)
-                .clk_th5
-                .modify(|_, w| w.i2c0_clk().bits(threshold as u8)),
-            threshold::I2C1 => (*ptr)
-                .clk_th5
-                .modify(|_, w| w.i2c1_clk().bits(threshold as u8)),
-            threshold::I2C2 => (*ptr)
-                .clk_th5
-                .modify(|_, w| w.i2c2_clk().bits(threshold as u8)),
-            threshold::WDT0 => (*ptr)
-                .clk_th6
-                .modify(|_, w| w.wdt0_clk().bits(threshold as u8)),
-            threshold::WDT1 => (*ptr)
-                .clk_th6
-                .modify(|_, w| w.wdt1_clk().bits(threshold as u8)),
-
-            /* 16 bit wide */
-            threshold::I2S0 => (*ptr)
-                .clk_th3
-                .modify(|_, w| w.i2s0_clk().bits(threshold as u16)),
-            threshold::I2S1 => (*ptr)
-                .clk_th3
-                .modify(|_, w| w.i2s1_clk().bits(threshold as u16)),
-            threshold::I2S2 => (*ptr)
-                .clk_th4
-                .modify(|_, w| w.i2s2_clk().bits(threshold as u16)),
-        }
-    }
-}
+        /*
+         * These peripheral devices are under APB1
+         * WDT, AES, OTP, DVP, SYSCTL
+         */
+        clock::AES | clock::WDT0 | clock::WDT1 | clock::OTP | clock::RTC => Some(clock::APB1),
 
-/// Get clock divider
-pub fn clock_get_threshold(which: threshold) -> u32 {
-    unsafe {
-        // TODO: this should return a multiplier directly, not a peripheral specific value
-        let ptr = pac::SYSCTL::ptr();
-        match which {
-            /* 2 bit wide */
-            threshold::ACLK => (*ptr).clk_sel0.read().aclk_divider_sel().bits().into(),
-
-            /* 3 bit wide */
-            threshold::APB0 => (*ptr).clk_sel0.read().apb0_clk_sel().bits().into(),
-            threshold::APB1 => (*ptr).clk_sel0.read().apb1_clk_sel().bits().into(),
-            threshold::APB2 => (*ptr).clk_sel0.read().apb2_clk_sel().bits().into(),
-
-            /* 4 bit wide */
-            threshold::SRAM0 => (*ptr).clk_th0.read().sram0_gclk().bits().into(),
-            threshold::SRAM1 => (*ptr).clk_th0.read().sram1_gclk().bits().into(),
-            threshold::AI => (*ptr).clk_th0.read().ai_gclk().bits().into(),
-            threshold::DVP => (*ptr).clk_th0.read().dvp_gclk().bits().into(),
-            threshold::ROM => (*ptr).clk_th0.read().rom_gclk().bits().into(),
-
-            /* 8 bit wide */
-            threshold::SPI0 => (*ptr).clk_th1.read().spi0_clk().bits().into(),
-            threshold::SPI1 => (*ptr).clk_th1.read().spi1_clk().bits().into(),
-            threshold::SPI2 => (*ptr).clk_th1.read().spi2_clk().bits().into(),
-            threshold::SPI3 => (*ptr).clk_th1.read().spi3_clk().bits().into(),
-            threshold::TIMER0 => (*ptr).clk_th2.read().timer0_clk().bits().into(),
-            threshold::TIMER1 => (*ptr).clk_th2.read().timer1_clk().bits().into(),
-            threshold::TIMER2 => (*ptr).clk_th2.read().timer2_clk().bits().into(),
-            threshold::I2S0_M => (*ptr).clk_th4.read().i2s0_mclk().bits().into(),
-            threshold::I2S1_M => (*ptr).clk_th4.read().i2s1_mclk().bits().into(),
-            threshold::I2S2_M => (*ptr).clk_th5.read().i2s2_mclk().bits().into(),
-            threshold::I2C0 => (*ptr).clk_th5.read().i2c0_clk().bits().into(),
-            threshold::I2C1 => (*ptr).clk_th5.read().i2c1_clk().bits().into(),
-            threshold::I2C2 => (*ptr).clk_th5.read().i2c2_clk().bits().into(),
-            threshold::WDT0 => (*ptr).clk_th6.read().wdt0_clk().bits().into(),
-            threshold::WDT1 => (*ptr).clk_th6.read().wdt1_clk().bits().into(),
-
-            /* 16 bit wide */
-            threshold::I2S0 => (*ptr).clk_th3.read().i2s0_clk().bits().into(),
-            threshold::I2S1 => (*ptr).clk_th3.read().i2s1_clk().bits().into(),
-            threshold::I2S2 => (*ptr).clk_th4.read().i2s2_clk().bits().into(),
-        }
+        /*
+         * These peripheral devices are under APB2
+         * SPI0, SPI1
+         */
+        clock::SPI0 | clock::SPI1 => Some(clock::APB2),
+
+        _ => None,
     }
 }
 
-pub fn set_power_mode(power_bank: power_bank, mode: io_power_mode) {
-    unsafe {
-        (*pac::SYSCTL::ptr()).power_sel.modify(|r, w| {
-            w.bits(set_bit(
-                r.bits(),
-                power_bank as u8,
-                match mode {
-                    io_power_mode::V33 => false,
-                    io_power_mode::V18 => true,
-                },
-            ))
-        });
+fn pll_params(clkr: u8, clkf: u8, clkod: u8, bwadj: u8) -> pll_compute::Params {
+    pll_compute::Params {
+        clkr,
+        clkf,
+        clkod,
+        bwadj,
     }
 }
 
-/** Route SPI0_D0-D7 DVP_D0-D7 functions to SPI and DVP data pins (bypassing FPIOA). */
-pub fn set_spi0_dvp_data(status: bool) {
-    unsafe {
-        (*pac::SYSCTL::ptr())
-            .misc
-            .modify(|_, w| w.spi_dvp_data_enable().bit(status));
-    }
+/** SYSCTL driver on top of a register backend */
+pub struct Sysctl<R> {
+    regs: R,
+    lock_budget: usize,
 }
 
-/** Map PLL2 cksel value to clock source */
-fn pll2_cksel_to_source(bits: u8) -> clock_source {
-    match bits {
-        0 => clock_source::IN0,
-        1 => clock_source::PLL0,
-        2 => clock_source::PLL1,
-        _ => panic!("invalid value for PLL2 ckin_sel"),
+impl<R: Registers> Sysctl<R> {
+    pub fn new(regs: R) -> Self {
+        Sysctl {
+            regs,
+            lock_budget: PLL_LOCK_BUDGET,
+        }
     }
-}
 
-/** Map clock source to PLL2 cksel value */
-fn pll2_source_to_cksel(source: clock_source) -> u8 {
-    match source {
-        clock_source::IN0 => 0,
-        clock_source::PLL0 => 1,
-        clock_source::PLL1 => 2,
-        _ => panic!("unsupported clock source for PLL2"),
+    /** Number of CPU cycles `pll_set_freq` waits for lock before giving up */
+    pub fn with_lock_budget(mut self, cycles: usize) -> Self {
+        self.lock_budget = cycles;
+        self
     }
-}
 
-pub fn pll_get_freq(pll: pll) -> u32 {
-    let freq_in;
-    let nr;
-    let nf;
-    let od;
-
-    match pll {
-        pll::PLL0 => {
-            freq_in = clock_source_get_freq(clock_source::IN0);
-            unsafe {
-                let val = (*pac::SYSCTL::ptr()).pll0.read();
-                nr = val.clkr().bits() + 1;
-                nf = val.clkf().bits() + 1;
-                od = val.clkod().bits() + 1;
+    fn clock_bus_en(&self, clock: clock, en: bool) {
+        /*
+         * The timer is under APB0, to prevent apb0_clk_en1 and apb0_clk_en0
+         * on same register, we split it to peripheral and central two
+         * registers, to protect CPU close apb0 clock accidentally.
+         *
+         * The apb0_clk_en0 and apb0_clk_en1 have same function,
+         * one of them set, the APB0 clock enable.
+         */
+
+        /* The APB clock should carefully disable */
+        if en {
+            if let Some(bus) = clock_bus(clock) {
+                self.clock_device_en(bus, en);
             }
         }
-        pll::PLL1 => {
-            freq_in = clock_source_get_freq(clock_source::IN0);
-            unsafe {
-                let val = (*pac::SYSCTL::ptr()).pll1.read();
-                nr = val.clkr().bits() + 1;
-                nf = val.clkf().bits() + 1;
-                od = val.clkod().bits() + 1;
+    }
+
+    fn clock_device_en(&self, clock: clock, en: bool) {
+        unsafe {
+            let ptr = self.regs.sysctl();
+            match clock {
+                clock::PLL0 => (*ptr).pll0.modify(|_, w| w.out_en().bit(en)),
+                clock::PLL1 => (*ptr).pll1.modify(|_, w| w.out_en().bit(en)),
+                clock::PLL2 => (*ptr).pll2.modify(|_, w| w.out_en().bit(en)),
+                clock::CPU => (*ptr).clk_en_cent.modify(|_, w| w.cpu_clk_en().bit(en)),
+                clock::SRAM0 => (*ptr).clk_en_cent.modify(|_, w| w.sram0_clk_en().bit(en)),
+                clock::SRAM1 => (*ptr).clk_en_cent.modify(|_, w| w.sram1_clk_en().bit(en)),
+                clock::APB0 => (*ptr).clk_en_cent.modify(|_, w| w.apb0_clk_en().bit(en)),
+                clock::APB1 => (*ptr).clk_en_cent.modify(|_, w| w.apb1_clk_en().bit(en)),
+                clock::APB2 => (*ptr).clk_en_cent.modify(|_, w| w.apb2_clk_en().bit(en)),
+                clock::ROM => (*ptr).clk_en_peri.modify(|_, w| w.rom_clk_en().bit(en)),
+                clock::DMA => (*ptr).clk_en_peri.modify(|_, w| w.dma_clk_en().bit(en)),
+                clock::AI => (*ptr).clk_en_peri.modify(|_, w| w.ai_clk_en().bit(en)),
+                clock::DVP => (*ptr).clk_en_peri.modify(|_, w| w.dvp_clk_en().bit(en)),
+                clock::FFT => (*ptr).clk_en_peri.modify(|_, w| w.fft_clk_en().bit(en)),
+                clock::SPI3 => (*ptr).clk_en_peri.modify(|_, w| w.spi3_clk_en().bit(en)),
+                clock::GPIO => (*ptr).clk_en_peri.modify(|_, w| w.gpio_clk_en().bit(en)),
+                clock::SPI2 => (*ptr).clk_en_peri.modify(|_, w| w.spi2_clk_en().bit(en)),
+                clock::I2S0 => (*ptr).clk_en_peri.modify(|_, w| w.i2s0_clk_en().bit(en)),
+                clock::I2S1 => (*ptr).clk_en_peri.modify(|_, w| w.i2s1_clk_en().bit(en)),
+                clock::I2S2 => (*ptr).clk_en_peri.modify(|_, w| w.i2s2_clk_en().bit(en)),
+                clock::I2C0 => (*ptr).clk_en_peri.modify(|_, w| w.i2c0_clk_en().bit(en)),
+                clock::I2C1 => (*ptr).clk_en_peri.modify(|_, w| w.i2c1_clk_en().bit(en)),
+                clock::I2C2 => (*ptr).clk_en_peri.modify(|_, w| w.i2c2_clk_en().bit(en)),
+                clock::UART1 => (*ptr).clk_en_peri.modify(|_, w| w.uart1_clk_en().bit(en)),
+                clock::UART2 => (*ptr).clk_en_peri.modify(|_, w| w.uart2_clk_en().bit(en)),
+                clock::UART3 => (*ptr).clk_en_peri.modify(|_, w| w.uart3_clk_en().bit(en)),
+                clock::FPIOA => (*ptr).clk_en_peri.modify(|_, w| w.fpioa_clk_en().bit(en)),
+                clock::TIMER0 => (*ptr).clk_en_peri.modify(|_, w| w.timer0_clk_en().bit(en)),
+                clock::TIMER1 => (*ptr).clk_en_peri.modify(|_, w| w.timer1_clk_en().bit(en)),
+                clock::TIMER2 => (*ptr).clk_en_peri.modify(|_, w| w.timer2_clk_en().bit(en)),
+                clock::SHA => (*ptr).clk_en_peri.modify(|_, w| w.sha_clk_en().bit(en)),
+                clock::AES => (*ptr).clk_en_peri.modify(|_, w| w.aes_clk_en().bit(en)),
+                clock::WDT0 => (*ptr).clk_en_peri.modify(|_, w| w.wdt0_clk_en().bit(en)),
+                clock::WDT1 => (*ptr).clk_en_peri.modify(|_, w| w.wdt1_clk_en().bit(en)),
+                clock::OTP => (*ptr).clk_en_peri.modify(|_, w| w.otp_clk_en().bit(en)),
+                clock::RTC => (*ptr).clk_en_peri.modify(|_, w| w.rtc_clk_en().bit(en)),
+                clock::SPI0 => (*ptr).clk_en_peri.modify(|_, w| w.spi0_clk_en().bit(en)),
+                clock::SPI1 => (*ptr).clk_en_peri.modify(|_, w| w.spi1_clk_en().bit(en)),
+                clock::ACLK | clock::HCLK | clock::IN0 => { /* no separate enables */ }
             }
         }
-        pll::PLL2 => {
-            /* Get input freq accrording to select register. */
-            freq_in = clock_source_get_freq(pll2_cksel_to_source(clock_get_clock_select(
-                clock_select::PLL2,
-            )));
-            unsafe {
-                let val = (*pac::SYSCTL::ptr()).pll2.read();
-                nr = val.clkr().bits() + 1;
-                nf = val.clkf().bits() + 1;
-                od = val.clkod().bits() + 1;
+    }
+
+    /** Whether a clock is enabled, clocks without an enable of their own always run */
+    pub fn clock_is_enabled(&self, clock: clock) -> bool {
+        unsafe {
+            let ptr = self.regs.sysctl();
+            match clock {
+                clock::PLL0 => (*ptr).pll0.read().out_en().bit(),
+                clock::PLL1 => (*ptr).pll1.read().out_en().bit(),
+                clock::PLL2 => (*ptr).pll2.read().out_en().bit(),
+                clock::CPU => (*ptr).clk_en_cent.read().cpu_clk_en().bit(),
+                clock::SRAM0 => (*ptr).clk_en_cent.read().sram0_clk_en().bit(),
+                clock::SRAM1 => (*ptr).clk_en_cent.read().sram1_clk_en().bit(),
+                clock::APB0 => (*ptr).clk_en_cent.read().apb0_clk_en().bit(),
+                clock::APB1 => (*ptr).clk_en_cent.read().apb1_clk_en().bit(),
+                clock::APB2 => (*ptr).clk_en_cent.read().apb2_clk_en().bit(),
+                clock::ROM => (*ptr).clk_en_peri.read().rom_clk_en().bit(),
+                clock::DMA => (*ptr).clk_en_peri.read().dma_clk_en().bit(),
+                clock::AI => (*ptr).clk_en_peri.read().ai_clk_en().bit(),
+                clock::DVP => (*ptr).clk_en_peri.read().dvp_clk_en().bit(),
+                clock::FFT => (*ptr).clk_en_peri.read().fft_clk_en().bit(),
+                clock::SPI3 => (*ptr).clk_en_peri.read().spi3_clk_en().bit(),
+                clock::GPIO => (*ptr).clk_en_peri.read().gpio_clk_en().bit(),
+                clock::SPI2 => (*ptr).clk_en_peri.read().spi2_clk_en().bit(),
+                clock::I2S0 => (*ptr).clk_en_peri.read().i2s0_clk_en().bit(),
+                clock::I2S1 => (*ptr).clk_en_peri.read().i2s1_clk_en().bit(),
+                clock::I2S2 => (*ptr).clk_en_peri.read().i2s2_clk_en().bit(),
+                clock::I2C0 => (*ptr).clk_en_peri.read().i2c0_clk_en().bit(),
+                clock::I2C1 => (*ptr).clk_en_peri.read().i2c1_clk_en().bit(),
+                clock::I2C2 => (*ptr).clk_en_peri.read().i2c2_clk_en().bit(),
+                clock::UART1 => (*ptr).clk_en_peri.read().uart1_clk_en().bit(),
+                clock::UART2 => (*ptr).clk_en_peri.read().uart2_clk_en().bit(),
+                clock::UART3 => (*ptr).clk_en_peri.read().uart3_clk_en().bit(),
+                clock::FPIOA => (*ptr).clk_en_peri.read().fpioa_clk_en().bit(),
+                clock::TIMER0 => (*ptr).clk_en_peri.read().timer0_clk_en().bit(),
+                clock::TIMER1 => (*ptr).clk_en_peri.read().timer1_clk_en().bit(),
+                clock::TIMER2 => (*ptr).clk_en_peri.read().timer2_clk_en().bit(),
+                clock::SHA => (*ptr).clk_en_peri.read().sha_clk_en().bit(),
+                clock::AES => (*ptr).clk_en_peri.read().aes_clk_en().bit(),
+                clock::WDT0 => (*ptr).clk_en_peri.read().wdt0_clk_en().bit(),
+                clock::WDT1 => (*ptr).clk_en_peri.read().wdt1_clk_en().bit(),
+                clock::OTP => (*ptr).clk_en_peri.read().otp_clk_en().bit(),
+                clock::RTC => (*ptr).clk_en_peri.read().rtc_clk_en().bit(),
+                clock::SPI0 => (*ptr).clk_en_peri.read().spi0_clk_en().bit(),
+                clock::SPI1 => (*ptr).clk_en_peri.read().spi1_clk_en().bit(),
+                clock::ACLK | clock::HCLK | clock::IN0 => true,
             }
         }
     }
 
-    /*
-     * Get final PLL output freq
-     * FOUT = FIN / NR * NF / OD
-     * (rewritten as integer expression)
-     */
-    ((u64::from(freq_in) * u64::from(nf)) / (u64::from(nr) * u64::from(od)))
-        .try_into()
-        .unwrap()
-}
+    pub fn clock_enable(&self, clock: clock) {
+        self.clock_bus_en(clock, true);
+        self.clock_device_en(clock, true);
+    }
 
-pub fn clock_source_get_freq(source: clock_source) -> u32 {
-    match source {
-        clock_source::IN0 => SYSCTRL_CLOCK_FREQ_IN0,
-        clock_source::PLL0 => pll_get_freq(pll::PLL0),
-        clock_source::PLL1 => pll_get_freq(pll::PLL1),
-        clock_source::PLL2 => pll_get_freq(pll::PLL2),
-        clock_source::ACLK => clock_get_freq(clock::ACLK),
+    pub fn sysctl_clock_disable(&self, clock: clock) {
+        self.clock_bus_en(clock, false);
+        self.clock_device_en(clock, false);
     }
-}
 
-pub fn clock_set_clock_select(which: clock_select, select: u8) {
-    unsafe {
-        let ptr = pac::SYSCTL::ptr();
-        // Seems that PLL2 is the only one that takes a non-boolean clock select
-        // TODO:  take a clock_source directly when we know the meanings of these bits
-        match which {
-            clock_select::PLL0_BYPASS => (*ptr).pll0.modify(|_, w| w.bypass().bit(select != 0)),
-            clock_select::PLL1_BYPASS => (*ptr).pll1.modify(|_, w| w.bypass().bit(select != 0)),
-            clock_select::PLL2_BYPASS => (*ptr).pll2.modify(|_, w| w.bypass().bit(select != 0)),
-            clock_select::PLL2 => (*ptr).pll2.modify(|_, w| w.ckin_sel().bits(select)),
-            clock_select::ACLK => (*ptr).clk_sel0.modify(|_, w| w.aclk_sel().bit(select != 0)),
-            clock_select::SPI3 => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.spi3_clk_sel().bit(select != 0)),
-            clock_select::TIMER0 => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.timer0_clk_sel().bit(select != 0)),
-            clock_select::TIMER1 => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.timer1_clk_sel().bit(select != 0)),
-            clock_select::TIMER2 => (*ptr)
-                .clk_sel0
-                .modify(|_, w| w.timer2_clk_sel().bit(select != 0)),
-            clock_select::SPI3_SAMPLE => (*ptr)
-                .clk_sel1
-                .modify(|_, w| w.spi3_sample_clk_sel().bit(select != 0)),
+    /// Set clock divider
+    pub fn clock_set_threshold(&self, which: threshold, threshold: u32) -> Result<(), SysctlError> {
+        // TODO: this should take a multiplier directly, not a peripheral specific value
+        if threshold > which.max() {
+            return Err(SysctlError::InvalidThreshold(which, threshold));
         }
-    }
-}
+        unsafe {
+            let ptr = self.regs.sysctl();
+            match which {
+                /* 2 bit wide */
+                threshold::ACLK => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.aclk_divider_sel().bits(threshold as u8)),
 
-pub fn clock_get_clock_select(which: clock_select) -> u8 {
-    unsafe {
-        let ptr = pac::SYSCTL::ptr();
-        // Seems that PLL2 is the only one that has a non-boolean clock select
-        // TODO: return a clock_source directly when we know the meanings of these bits
-        //   meaning seems to be usually:
-        //     0  IN0
-        //     1  PLL0
-        //     (2  PLL1)
-        //   it's likely different for _BYPASS, which, I suspect, wires the PLL output to the
-        //   input (IN0 for PLL0 and PLL1, selectable for PLL2)
-        match which {
-            clock_select::PLL0_BYPASS => (*ptr).pll0.read().bypass().bit().into(),
-            clock_select::PLL1_BYPASS => (*ptr).pll1.read().bypass().bit().into(),
-            clock_select::PLL2_BYPASS => (*ptr).pll2.read().bypass().bit().into(),
-            clock_select::PLL2 => (*ptr).pll2.read().ckin_sel().bits().into(),
-            clock_select::ACLK => (*ptr).clk_sel0.read().aclk_sel().bit().into(),
-            clock_select::SPI3 => (*ptr).clk_sel0.read().spi3_clk_sel().bit().into(),
-            clock_select::TIMER0 => (*ptr).clk_sel0.read().timer0_clk_sel().bit().into(),
-            clock_select::TIMER1 => (*ptr).clk_sel0.read().timer1_clk_sel().bit().into(),
-            clock_select::TIMER2 => (*ptr).clk_sel0.read().timer2_clk_sel().bit().into(),
-            clock_select::SPI3_SAMPLE => (*ptr).clk_sel1.read().spi3_sample_clk_sel().bit().into(),
+                /* 3 bit wide */
+                threshold::APB0 => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.apb0_clk_sel().bits(threshold as u8)),
+                threshold::APB1 => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.apb1_clk_sel().bits(threshold as u8)),
+                threshold::APB2 => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.apb2_clk_sel().bits(threshold as u8)),
+
+                /* 4 bit wide */
+                threshold::SRAM0 => (*ptr)
+                    .clk_th0
+                    .modify(|_, w| w.sram0_gclk().bits(threshold as u8)),
+                threshold::SRAM1 => (*ptr)
+                    .clk_th0
+                    .modify(|_, w| w.sram1_gclk().bits(threshold as u8)),
+                threshold::AI => (*ptr)
+                    .clk_th0
+                    .modify(|_, w| w.ai_gclk().bits(threshold as u8)),
+                threshold::DVP => (*ptr)
+                    .clk_th0
+                    .modify(|_, w| w.dvp_gclk().bits(threshold as u8)),
+                threshold::ROM => (*ptr)
+                    .clk_th0
+                    .modify(|_, w| w.rom_gclk().bits(threshold as u8)),
+
+                /* 8 bit wide */
+                threshold::SPI0 => (*ptr)
+                    .clk_th1
+                    .modify(|_, w| w.spi0_clk().bits(threshold as u8)),
+                threshold::SPI1 => (*ptr)
+                    .clk_th1
+                    .modify(|_, w| w.spi1_clk().bits(threshold as u8)),
+                threshold::SPI2 => (*ptr)
+                    .clk_th1
+                    .modify(|_, w| w.spi2_clk().bits(threshold as u8)),
+                threshold::SPI3 => (*ptr)
+                    .clk_th1
+                    .modify(|_, w| w.spi3_clk().bits(threshold as u8)),
+                threshold::TIMER0 => (*ptr)
+                    .clk_th2
+                    .modify(|_, w| w.timer0_clk().bits(threshold as u8)),
+                threshold::TIMER1 => (*ptr)
+                    .clk_th2
+                    .modify(|_, w| w.timer1_clk().bits(threshold as u8)),
+                threshold::TIMER2 => (*ptr)
+                    .clk_th2
+                    .modify(|_, w| w.timer2_clk().bits(threshold as u8)),
+                threshold::I2S0_M => (*ptr)
+                    .clk_th4
+                    .modify(|_, w| w.i2s0_mclk().bits(threshold as u8)),
+                threshold::I2S1_M => (*ptr)
+                    .clk_th4
+                    .modify(|_, w| w.i2s1_mclk().bits(threshold as u8)),
+                threshold::I2S2_M => (*ptr)
+                    .clk_th5
+                    .modify(|_, w| w.i2s2_mclk().bits(threshold as u8)),
+                threshold::I2C0 => (*ptr)
+                    .clk_th5
+                    .modify(|_, w| w.i2c0_clk().bits(threshold as u8)),
+                threshold::I2C1 => (*ptr)
+                    .clk_th5
+                    .modify(|_, w| w.i2c1_clk().bits(threshold as u8)),
+                threshold::I2C2 => (*ptr)
+                    .clk_th5
+                    .modify(|_, w| w.i2c2_clk().bits(threshold as u8)),
+                threshold::WDT0 => (*ptr)
+                    .clk_th6
+                    .modify(|_, w| w.wdt0_clk().bits(threshold as u8)),
+                threshold::WDT1 => (*ptr)
+                    .clk_th6
+                    .modify(|_, w| w.wdt1_clk().bits(threshold as u8)),
+
+                /* 16 bit wide */
+                threshold::I2S0 => (*ptr)
+                    .clk_th3
+                    .modify(|_, w| w.i2s0_clk().bits(threshold as u16)),
+                threshold::I2S1 => (*ptr)
+                    .clk_th3
+                    .modify(|_, w| w.i2s1_clk().bits(threshold as u16)),
+                threshold::I2S2 => (*ptr)
+                    .clk_th4
+                    .modify(|_, w| w.i2s2_clk().bits(threshold as u16)),
+            }
         }
+        Ok(())
     }
-}
 
-pub fn clock_get_freq(clock: clock) -> u32 {
-    // TODO: all of these are source / threshold, where source can depend on clock_select: generalize this
-    //       to some kind of clock tree
-    // TODO: clock_source_get_freq(ACLK) calls back into here, don't do this
-    match clock {
-        clock::IN0 => clock_source_get_freq(clock_source::IN0),
-        clock::PLL0 => clock_source_get_freq(clock_source::PLL0),
-        clock::PLL1 => clock_source_get_freq(clock_source::PLL1),
-        clock::PLL2 => clock_source_get_freq(clock_source::PLL2),
-        clock::CPU | clock::DMA | clock::FFT | clock::ACLK | clock::HCLK => {
-            match clock_get_clock_select(clock_select::ACLK) {
-                0 => clock_source_get_freq(clock_source::IN0),
-                1 => {
-                    clock_source_get_freq(clock_source::PLL0)
-                        / (2 << clock_get_threshold(threshold::ACLK))
-                }
-                _ => panic!("invalid cpu clock select"),
+    /// Get clock divider
+    pub fn clock_get_threshold(&self, which: threshold) -> u32 {
+        unsafe {
+            // TODO: this should return a multiplier directly, not a peripheral specific value
+            let ptr = self.regs.sysctl();
+            match which {
+                /* 2 bit wide */
+                threshold::ACLK => (*ptr).clk_sel0.read().aclk_divider_sel().bits().into(),
+
+                /* 3 bit wide */
+                threshold::APB0 => (*ptr).clk_sel0.read().apb0_clk_sel().bits().into(),
+                threshold::APB1 => (*ptr).clk_sel0.read().apb1_clk_sel().bits().into(),
+                threshold::APB2 => (*ptr).clk_sel0.read().apb2_clk_sel().bits().into(),
+
+                /* 4 bit wide */
+                threshold::SRAM0 => (*ptr).clk_th0.read().sram0_gclk().bits().into(),
+                threshold::SRAM1 => (*ptr).clk_th0.read().sram1_gclk().bits().into(),
+                threshold::AI => (*ptr).clk_th0.read().ai_gclk().bits().into(),
+                threshold::DVP => (*ptr).clk_th0.read().dvp_gclk().bits().into(),
+                threshold::ROM => (*ptr).clk_th0.read().rom_gclk().bits().into(),
+
+                /* 8 bit wide */
+                threshold::SPI0 => (*ptr).clk_th1.read().spi0_clk().bits().into(),
+                threshold::SPI1 => (*ptr).clk_th1.read().spi1_clk().bits().into(),
+                threshold::SPI2 => (*ptr).clk_th1.read().spi2_clk().bits().into(),
+                threshold::SPI3 => (*ptr).clk_th1.read().spi3_clk().bits().into(),
+                threshold::TIMER0 => (*ptr).clk_th2.read().timer0_clk().bits().into(),
+                threshold::TIMER1 => (*ptr).clk_th2.read().timer1_clk().bits().into(),
+                threshold::TIMER2 => (*ptr).clk_th2.read().timer2_clk().bits().into(),
+                threshold::I2S0_M => (*ptr).clk_th4.read().i2s0_mclk().bits().into(),
+                threshold::I2S1_M => (*ptr).clk_th4.read().i2s1_mclk().bits().into(),
+                threshold::I2S2_M => (*ptr).clk_th5.read().i2s2_mclk().bits().into(),
+                threshold::I2C0 => (*ptr).clk_th5.read().i2c0_clk().bits().into(),
+                threshold::I2C1 => (*ptr).clk_th5.read().i2c1_clk().bits().into(),
+                threshold::I2C2 => (*ptr).clk_th5.read().i2c2_clk().bits().into(),
+                threshold::WDT0 => (*ptr).clk_th6.read().wdt0_clk().bits().into(),
+                threshold::WDT1 => (*ptr).clk_th6.read().wdt1_clk().bits().into(),
+
+                /* 16 bit wide */
+                threshold::I2S0 => (*ptr).clk_th3.read().i2s0_clk().bits().into(),
+                threshold::I2S1 => (*ptr).clk_th3.read().i2s1_clk().bits().into(),
+                threshold::I2S2 => (*ptr).clk_th4.read().i2s2_clk().bits().into(),
             }
         }
-        clock::SRAM0 => {
-            clock_source_get_freq(clock_source::ACLK) / (clock_get_threshold(threshold::SRAM0) + 1)
-        }
-        clock::SRAM1 => {
-            clock_source_get_freq(clock_source::ACLK) / (clock_get_threshold(threshold::SRAM1) + 1)
-        }
-        clock::ROM => {
-            clock_source_get_freq(clock_source::ACLK) / (clock_get_threshold(threshold::ROM) + 1)
-        }
-        clock::DVP => {
-            clock_source_get_freq(clock_source::ACLK) / (clock_get_threshold(threshold::DVP) + 1)
-        }
-        clock::APB0
-        | clock::GPIO
-        | clock::UART1
-        | clock::UART2
-        | clock::UART3
-        | clock::FPIOA
-        | clock::SHA => {
-            clock_source_get_freq(clock_source::ACLK) / (clock_get_threshold(threshold::APB0) + 1)
-        }
-        clock::APB1 | clock::AES | clock::OTP => {
-            clock_source_get_freq(clock_source::ACLK) / (clock_get_threshold(threshold::APB1) + 1)
-        }
-        clock::APB2 => {
-            clock_source_get_freq(clock_source::ACLK) / (clock_get_threshold(threshold::APB2) + 1)
-        }
-        clock::AI => {
-            clock_source_get_freq(clock_source::PLL1) / (clock_get_threshold(threshold::AI) + 1)
-        }
-        clock::I2S0 => {
-            clock_source_get_freq(clock_source::PLL2)
-                / ((clock_get_threshold(threshold::I2S0) + 1) * 2)
+    }
+
+    pub fn set_power_mode(&self, power_bank: power_bank, mode: io_power_mode) {
+        unsafe {
+            (*self.regs.sysctl()).power_sel.modify(|r, w| {
+                w.bits(set_bit(
+                    r.bits(),
+                    power_bank as u8,
+                    match mode {
+                        io_power_mode::V33 => false,
+                        io_power_mode::V18 => true,
+                    },
+                ))
+            });
         }
-        clock::I2S1 => {
-            clock_source_get_freq(clock_source::PLL2)
-                / ((clock_get_threshold(threshold::I2S1) + 1) * 2)
+    }
+
+    pub fn get_power_mode(&self, power_bank: power_bank) -> io_power_mode {
+        let bits = unsafe { (*self.regs.sysctl()).power_sel.read().bits() };
+        if get_bit(bits, power_bank as u8) {
+            io_power_mode::V18
+        } else {
+            io_power_mode::V33
         }
-        clock::I2S2 => {
-            clock_source_get_freq(clock_source::PLL2)
-                / ((clock_get_threshold(threshold::I2S2) + 1) * 2)
+    }
+
+    /** Route SPI0_D0-D7 DVP_D0-D7 functions to SPI and DVP data pins (bypassing FPIOA). */
+    pub fn set_spi0_dvp_data(&self, status: bool) {
+        unsafe {
+            (*self.regs.sysctl())
+                .misc
+                .modify(|_, w| w.spi_dvp_data_enable().bit(status));
         }
-        clock::WDT0 => {
-            clock_source_get_freq(clock_source::IN0)
-                / ((clock_get_threshold(threshold::WDT0) + 1) * 2)
+    }
+
+    /** Snapshot the PLL, clock select and threshold registers into a `ClockTree` */
+    pub fn clock_tree(&self) -> ClockTree {
+        let mut tree = ClockTree::new(SYSCTRL_CLOCK_FREQ_IN0);
+        unsafe {
+            let ptr = self.regs.sysctl();
+            let val = (*ptr).pll0.read();
+            tree.set_pll(
+                pll::PLL0,
+                pll_params(
+                    val.clkr().bits(),
+                    val.clkf().bits(),
+                    val.clkod().bits(),
+                    val.bwadj().bits(),
+                ),
+            );
+            let val = (*ptr).pll1.read();
+            tree.set_pll(
+                pll::PLL1,
+                pll_params(
+                    val.clkr().bits(),
+                    val.clkf().bits(),
+                    val.clkod().bits(),
+                    val.bwadj().bits(),
+                ),
+            );
+            let val = (*ptr).pll2.read();
+            tree.set_pll(
+                pll::PLL2,
+                pll_params(
+                    val.clkr().bits(),
+                    val.clkf().bits(),
+                    val.clkod().bits(),
+                    val.bwadj().bits(),
+                ),
+            );
         }
-        clock::WDT1 => {
-            clock_source_get_freq(clock_source::IN0)
-                / ((clock_get_threshold(threshold::WDT1) + 1) * 2)
+        for &which in clock_select::ALL {
+            tree.set_clock_select(which, self.clock_get_clock_select(which));
         }
-        clock::SPI0 => {
-            clock_source_get_freq(clock_source::PLL0)
-                / ((clock_get_threshold(threshold::SPI0) + 1) * 2)
+        for &which in threshold::ALL {
+            tree.set_threshold(which, self.clock_get_threshold(which));
         }
-        clock::SPI1 => {
-            clock_source_get_freq(clock_source::PLL0)
-                / ((clock_get_threshold(threshold::SPI1) + 1) * 2)
+        tree
+    }
+
+    pub fn pll_get_freq(&self, pll: pll) -> Result<u32, SysctlError> {
+        self.clock_tree().pll_freq(pll)
+    }
+
+    pub fn clock_source_get_freq(&self, source: clock_source) -> Result<u32, SysctlError> {
+        self.clock_tree().source_freq(source)
+    }
+
+    pub fn clock_set_clock_select(
+        &self,
+        which: clock_select,
+        select: u8,
+    ) -> Result<(), SysctlError> {
+        if select > which.max() {
+            return Err(SysctlError::InvalidSelect(which, select));
         }
-        clock::SPI2 => {
-            clock_source_get_freq(clock_source::PLL0)
-                / ((clock_get_threshold(threshold::SPI2) + 1) * 2)
+        unsafe {
+            let ptr = self.regs.sysctl();
+            // Seems that PLL2 is the only one that takes a non-boolean clock select
+            // TODO:  take a clock_source directly when we know the meanings of these bits
+            match which {
+                clock_select::PLL0_BYPASS => (*ptr).pll0.modify(|_, w| w.bypass().bit(select != 0)),
+                clock_select::PLL1_BYPASS => (*ptr).pll1.modify(|_, w| w.bypass().bit(select != 0)),
+                clock_select::PLL2_BYPASS => (*ptr).pll2.modify(|_, w| w.bypass().bit(select != 0)),
+                clock_select::PLL2 => (*ptr).pll2.modify(|_, w| w.ckin_sel().bits(select)),
+                clock_select::ACLK => (*ptr).clk_sel0.modify(|_, w| w.aclk_sel().bit(select != 0)),
+                clock_select::SPI3 => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.spi3_clk_sel().bit(select != 0)),
+                clock_select::TIMER0 => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.timer0_clk_sel().bit(select != 0)),
+                clock_select::TIMER1 => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.timer1_clk_sel().bit(select != 0)),
+                clock_select::TIMER2 => (*ptr)
+                    .clk_sel0
+                    .modify(|_, w| w.timer2_clk_sel().bit(select != 0)),
+                clock_select::SPI3_SAMPLE => (*ptr)
+                    .clk_sel1
+                    .modify(|_, w| w.spi3_sample_clk_sel().bit(select != 0)),
+            }
         }
-        clock::I2C0 => {
-            clock_source_get_freq(clock_source::PLL0)
-                / ((clock_get_threshold(threshold::I2C0) + 1) * 2)
+        Ok(())
+    }
+
+    pub fn clock_get_clock_select(&self, which: clock_select) -> u8 {
+        unsafe {
+            let ptr = self.regs.sysctl();
+            // Seems that PLL2 is the only one that has a non-boolean clock select
+            // TODO: return a clock_source directly when we know the meanings of these bits
+            //   meaning seems to be usually:
+            //     0  IN0
+            //     1  PLL0
+            //     (2  PLL1)
+            //   it's likely different for _BYPASS, which, I suspect, wires the PLL output to the
+            //   input (IN0 for PLL0 and PLL1, selectable for PLL2)
+            match which {
+                clock_select::PLL0_BYPASS => (*ptr).pll0.read().bypass().bit().into(),
+                clock_select::PLL1_BYPASS => (*ptr).pll1.read().bypass().bit().into(),
+                clock_select::PLL2_BYPASS => (*ptr).pll2.read().bypass().bit().into(),
+                clock_select::PLL2 => (*ptr).pll2.read().ckin_sel().bits().into(),
+                clock_select::ACLK => (*ptr).clk_sel0.read().aclk_sel().bit().into(),
+                clock_select::SPI3 => (*ptr).clk_sel0.read().spi3_clk_sel().bit().into(),
+                clock_select::TIMER0 => (*ptr).clk_sel0.read().timer0_clk_sel().bit().into(),
+                clock_select::TIMER1 => (*ptr).clk_sel0.read().timer1_clk_sel().bit().into(),
+                clock_select::TIMER2 => (*ptr).clk_sel0.read().timer2_clk_sel().bit().into(),
+                clock_select::SPI3_SAMPLE => {
+                    (*ptr).clk_sel1.read().spi3_sample_clk_sel().bit().into()
+                }
+            }
         }
-        clock::I2C1 => {
-            clock_source_get_freq(clock_source::PLL0)
-                / ((clock_get_threshold(threshold::I2C1) + 1) * 2)
+    }
+
+    pub fn clock_get_freq(&self, clock: clock) -> Result<u32, SysctlError> {
+        self.clock_tree().freq(clock)
+    }
+
+    fn reset_ctl(&self, reset: reset, rst_value: bool) {
+        unsafe {
+            let ptr = self.regs.sysctl();
+            match reset {
+                reset::SOC => (*ptr)
+                    .soft_reset
+                    .modify(|_, w| w.soft_reset().bit(rst_value)),
+                reset::ROM => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.rom_reset().bit(rst_value)),
+                reset::DMA => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.dma_reset().bit(rst_value)),
+                reset::AI => (*ptr).peri_reset.modify(|_, w| w.ai_reset().bit(rst_value)),
+                reset::DVP => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.dvp_reset().bit(rst_value)),
+                reset::FFT => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.fft_reset().bit(rst_value)),
+                reset::GPIO => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.gpio_reset().bit(rst_value)),
+                reset::SPI0 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.spi0_reset().bit(rst_value)),
+                reset::SPI1 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.spi1_reset().bit(rst_value)),
+                reset::SPI2 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.spi2_reset().bit(rst_value)),
+                reset::SPI3 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.spi3_reset().bit(rst_value)),
+                reset::I2S0 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.i2s0_reset().bit(rst_value)),
+                reset::I2S1 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.i2s1_reset().bit(rst_value)),
+                reset::I2S2 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.i2s2_reset().bit(rst_value)),
+                reset::I2C0 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.i2c0_reset().bit(rst_value)),
+                reset::I2C1 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.i2c1_reset().bit(rst_value)),
+                reset::I2C2 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.i2c2_reset().bit(rst_value)),
+                reset::UART1 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.uart1_reset().bit(rst_value)),
+                reset::UART2 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.uart2_reset().bit(rst_value)),
+                reset::UART3 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.uart3_reset().bit(rst_value)),
+                reset::AES => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.aes_reset().bit(rst_value)),
+                reset::FPIOA => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.fpioa_reset().bit(rst_value)),
+                reset::TIMER0 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.timer0_reset().bit(rst_value)),
+                reset::TIMER1 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.timer1_reset().bit(rst_value)),
+                reset::TIMER2 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.timer2_reset().bit(rst_value)),
+                reset::WDT0 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.wdt0_reset().bit(rst_value)),
+                reset::WDT1 => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.wdt1_reset().bit(rst_value)),
+                reset::SHA => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.sha_reset().bit(rst_value)),
+                reset::RTC => (*ptr)
+                    .peri_reset
+                    .modify(|_, w| w.rtc_reset().bit(rst_value)),
+            }
         }
-        clock::I2C2 => {
-            clock_source_get_freq(clock_source::PLL0)
-                / ((clock_get_threshold(threshold::I2C2) + 1) * 2)
+    }
+
+    /** Whether a reset is currently asserted */
+    pub fn reset_is_asserted(&self, reset: reset) -> bool {
+        unsafe {
+            let ptr = self.regs.sysctl();
+            match reset {
+                reset::SOC => (*ptr).soft_reset.read().soft_reset().bit(),
+                reset::ROM => (*ptr).peri_reset.read().rom_reset().bit(),
+                reset::DMA => (*ptr).peri_reset.read().dma_reset().bit(),
+                reset::AI => (*ptr).peri_reset.read().ai_reset().bit(),
+                reset::DVP => (*ptr).peri_reset.read().dvp_reset().bit(),
+                reset::FFT => (*ptr).peri_reset.read().fft_reset().bit(),
+                reset::GPIO => (*ptr).peri_reset.read().gpio_reset().bit(),
+                reset::SPI0 => (*ptr).peri_reset.read().spi0_reset().bit(),
+                reset::SPI1 => (*ptr).peri_reset.read().spi1_reset().bit(),
+                reset::SPI2 => (*ptr).peri_reset.read().spi2_reset().bit(),
+                reset::SPI3 => (*ptr).peri_reset.read().spi3_reset().bit(),
+                reset::I2S0 => (*ptr).peri_reset.read().i2s0_reset().bit(),
+                reset::I2S1 => (*ptr).peri_reset.read().i2s1_reset().bit(),
+                reset::I2S2 => (*ptr).peri_reset.read().i2s2_reset().bit(),
+                reset::I2C0 => (*ptr).peri_reset.read().i2c0_reset().bit(),
+                reset::I2C1 => (*ptr).peri_reset.read().i2c1_reset().bit(),
+                reset::I2C2 => (*ptr).peri_reset.read().i2c2_reset().bit(),
+                reset::UART1 => (*ptr).peri_reset.read().uart1_reset().bit(),
+                reset::UART2 => (*ptr).peri_reset.read().uart2_reset().bit(),
+                reset::UART3 => (*ptr).peri_reset.read().uart3_reset().bit(),
+                reset::AES => (*ptr).peri_reset.read().aes_reset().bit(),
+                reset::FPIOA => (*ptr).peri_reset.read().fpioa_reset().bit(),
+                reset::TIMER0 => (*ptr).peri_reset.read().timer0_reset().bit(),
+                reset::TIMER1 => (*ptr).peri_reset.read().timer1_reset().bit(),
+                reset::TIMER2 => (*ptr).peri_reset.read().timer2_reset().bit(),
+                reset::WDT0 => (*ptr).peri_reset.read().wdt0_reset().bit(),
+                reset::WDT1 => (*ptr).peri_reset.read().wdt1_reset().bit(),
+                reset::SHA => (*ptr).peri_reset.read().sha_reset().bit(),
+                reset::RTC => (*ptr).peri_reset.read().rtc_reset().bit(),
+            }
         }
-        clock::SPI3 => {
-            let source = match clock_get_clock_select(clock_select::SPI3) {
-                0 => clock_source_get_freq(clock_source::IN0),
-                1 => clock_source_get_freq(clock_source::PLL0),
-                _ => panic!("unimplemented clock source"),
-            };
-            source / ((clock_get_threshold(threshold::SPI3) + 1) * 2)
+    }
+
+    pub fn reset(&self, reset: reset) {
+        self.reset_group(&[reset]);
+    }
+
+    /**
+     * Reset several peripherals together: all resets are asserted in the given order, held for
+     * `RESET_HOLD_US` and released in the same order, so list a peripheral after the ones it
+     * depends on (e.g. DMA before SPI0).
+     */
+    pub fn reset_group(&self, group: &[reset]) {
+        for &reset in group {
+            self.reset_ctl(reset, true);
         }
-        clock::TIMER0 => {
-            let source = match clock_get_clock_select(clock_select::TIMER0) {
-                0 => clock_source_get_freq(clock_source::IN0),
-                1 => clock_source_get_freq(clock_source::PLL0),
-                _ => panic!("unimplemented clock source"),
-            };
-            source / ((clock_get_threshold(threshold::TIMER0) + 1) * 2)
+        self.regs.usleep(RESET_HOLD_US);
+        for &reset in group {
+            self.reset_ctl(reset, false);
         }
-        clock::TIMER1 => {
-            let source = match clock_get_clock_select(clock_select::TIMER1) {
-                0 => clock_source_get_freq(clock_source::IN0),
-                1 => clock_source_get_freq(clock_source::PLL0),
-                _ => panic!("unimplemented clock source"),
-            };
-            source / ((clock_get_threshold(threshold::TIMER1) + 1) * 2)
+    }
+
+    /** Cause of the last SoC reset, as recorded since it was last cleared */
+    pub fn reset_cause(&self) -> reset_cause {
+        let status = unsafe { (*self.regs.sysctl()).reset_status.read() };
+        if status.wdt0_reset_sts().bit() {
+            reset_cause::WDT0
+        } else if status.wdt1_reset_sts().bit() {
+            reset_cause::WDT1
+        } else if status.soft_reset_sts().bit() {
+            reset_cause::SOFT
+        } else if status.pin_reset_sts().bit() {
+            reset_cause::PIN
+        } else {
+            reset_cause::HARD
         }
-        clock::TIMER2 => {
-            let source = match clock_get_clock_select(clock_select::TIMER2) {
-                0 => clock_source_get_freq(clock_source::IN0),
-                1 => clock_source_get_freq(clock_source::PLL0),
-                _ => panic!("unimplemented clock source"),
-            };
-            source / ((clock_get_threshold(threshold::TIMER2) + 1) * 2)
+    }
+
+    /** Clear the recorded reset cause, so the next reset is reported correctly */
+    pub fn clear_reset_cause(&self) {
+        unsafe {
+            (*self.regs.sysctl())
+                .reset_status
+                .modify(|_, w| w.reset_sts_clr().set_bit());
         }
-        clock::RTC => clock_source_get_freq(clock_source::IN0),
     }
-}
 
-fn reset_ctl(reset: reset, rst_value: bool) {
-    unsafe {
-        let ptr = pac::SYSCTL::ptr();
-        match reset {
-            reset::SOC => (*ptr)
-                .soft_reset
-                .modify(|_, w| w.soft_reset().bit(rst_value)),
-            reset::ROM => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.rom_reset().bit(rst_value)),
-            reset::DMA => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.dma_reset().bit(rst_value)),
-            reset::AI => (*ptr).peri_reset.modify(|_, w| w.ai_reset().bit(rst_value)),
-            reset::DVP => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.dvp_reset().bit(rst_value)),
-            reset::FFT => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.fft_reset().bit(rst_value)),
-            reset::GPIO => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.gpio_reset().bit(rst_value)),
-            reset::SPI0 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.spi0_reset().bit(rst_value)),
-            reset::SPI1 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.spi1_reset().bit(rst_value)),
-            reset::SPI2 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.spi2_reset().bit(rst_value)),
-            reset::SPI3 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.spi3_reset().bit(rst_value)),
-            reset::I2S0 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.i2s0_reset().bit(rst_value)),
-            reset::I2S1 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.i2s1_reset().bit(rst_value)),
-            reset::I2S2 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.i2s2_reset().bit(rst_value)),
-            reset::I2C0 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.i2c0_reset().bit(rst_value)),
-            reset::I2C1 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.i2c1_reset().bit(rst_value)),
-            reset::I2C2 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.i2c2_reset().bit(rst_value)),
-            reset::UART1 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.uart1_reset().bit(rst_value)),
-            reset::UART2 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.uart2_reset().bit(rst_value)),
-            reset::UART3 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.uart3_reset().bit(rst_value)),
-            reset::AES => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.aes_reset().bit(rst_value)),
-            reset::FPIOA => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.fpioa_reset().bit(rst_value)),
-            reset::TIMER0 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.timer0_reset().bit(rst_value)),
-            reset::TIMER1 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.timer1_reset().bit(rst_value)),
-            reset::TIMER2 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.timer2_reset().bit(rst_value)),
-            reset::WDT0 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.wdt0_reset().bit(rst_value)),
-            reset::WDT1 => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.wdt1_reset().bit(rst_value)),
-            reset::SHA => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.sha_reset().bit(rst_value)),
-            reset::RTC => (*ptr)
-                .peri_reset
-                .modify(|_, w| w.rtc_reset().bit(rst_value)),
+    /** Select DMA handshake for a channel */
+    pub fn dma_select(&self, channel: dma_channel, select: dma_select) {
+        unsafe {
+            use dma_channel::*;
+            let ptr = self.regs.sysctl();
+            match channel {
+                CHANNEL0 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel0().variant(select)),
+                CHANNEL1 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel1().variant(select)),
+                CHANNEL2 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel2().variant(select)),
+                CHANNEL3 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel3().variant(select)),
+                CHANNEL4 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel4().variant(select)),
+                CHANNEL5 => (*ptr).dma_sel1.modify(|_, w| w.dma_sel5().variant(select)),
+            }
         }
     }
-}
 
-pub fn reset(reset: reset) {
-    reset_ctl(reset, true);
-    usleep(10);
-    reset_ctl(reset, false);
-}
+    /** Return whether the selected PLL has achieved lock */
+    pub fn pll_is_lock(&self, pll: pll) -> bool {
+        let ptr = self.regs.sysctl();
+        let pll_lock = unsafe { (*ptr).pll_lock.read() };
+        match pll {
+            pll::PLL0 => pll_lock.pll_lock0().bits() == 3,
+            pll::PLL1 => (pll_lock.pll_lock1().bits() & 1) == 1,
+            pll::PLL2 => (pll_lock.pll_lock2().bits() & 1) == 1,
+        }
+    }
 
-/** Select DMA handshake for a channel */
-pub fn dma_select(channel: dma_channel, select: dma_select) {
-    unsafe {
-        use dma_channel::*;
-        let ptr = pac::SYSCTL::ptr();
-        match channel {
-            CHANNEL0 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel0().variant(select)),
-            CHANNEL1 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel1().variant(select)),
-            CHANNEL2 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel2().variant(select)),
-            CHANNEL3 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel3().variant(select)),
-            CHANNEL4 => (*ptr).dma_sel0.modify(|_, w| w.dma_sel4().variant(select)),
-            CHANNEL5 => (*ptr).dma_sel1.modify(|_, w| w.dma_sel5().variant(select)),
+    /** Whether the PLL is powered on */
+    pub fn pll_is_powered(&self, pll: pll) -> bool {
+        let ptr = self.regs.sysctl();
+        unsafe {
+            match pll {
+                pll::PLL0 => (*ptr).pll0.read().pwrd().bit(),
+                pll::PLL1 => (*ptr).pll1.read().pwrd().bit(),
+                pll::PLL2 => (*ptr).pll2.read().pwrd().bit(),
+            }
         }
     }
-}
 
-/** Return whether the selected PLL has achieved lock */
-fn pll_is_lock(pll: pll) -> bool {
-    let ptr = pac::SYSCTL::ptr();
-    let pll_lock = unsafe { (*ptr).pll_lock.read() };
-    match pll {
-        pll::PLL0 => pll_lock.pll_lock0().bits() == 3,
-        pll::PLL1 => (pll_lock.pll_lock1().bits() & 1) == 1,
-        pll::PLL2 => (pll_lock.pll_lock2().bits() & 1) == 1,
+    /** Whether an enabled clock other than the PLL output itself runs from `pll` */
+    pub fn pll_in_use(&self, pll: pll) -> bool {
+        let tree = self.clock_tree();
+        let output = match pll {
+            pll::PLL0 => clock::PLL0,
+            pll::PLL1 => clock::PLL1,
+            pll::PLL2 => clock::PLL2,
+        };
+        clock::ALL.iter().any(|&clock| {
+            clock != output && tree.uses_pll(clock, pll) && self.clock_is_enabled(clock)
+        })
     }
-}
 
-/** Clear PLL slip, this is done repeatedly until lock is achieved */
-fn pll_clear_slip(pll: pll) -> bool {
-    let ptr = pac::SYSCTL::ptr();
-    unsafe {
-        (*ptr).pll_lock.modify(|_, w| match pll {
-            pll::PLL0 => w.pll_slip_clear0().set_bit(),
-            pll::PLL1 => w.pll_slip_clear1().set_bit(),
-            pll::PLL2 => w.pll_slip_clear2().set_bit(),
-        });
+    /**
+     * Disable the output of a PLL, hold it in reset and power it down. Nothing may run from it,
+     * see `pll_in_use`; `pll_set_params` powers it up again.
+     */
+    pub fn pll_power_down(&self, pll: pll) {
+        use pll::*;
+        let ptr = self.regs.sysctl();
+        unsafe {
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.out_en().clear_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.out_en().clear_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.out_en().clear_bit()),
+            };
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.reset().set_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.reset().set_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.reset().set_bit()),
+            };
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.pwrd().clear_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.pwrd().clear_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.pwrd().clear_bit()),
+            };
+        }
     }
-    pll_is_lock(pll)
-}
 
-fn pll_source_set_freq(pll: pll, source: clock_source, freq: u32) -> Result<u32, ()> {
-    use pll::*;
-    /* PLL0 and 1 can only source from IN0 */
-    if (pll == PLL0 || pll == PLL1) && source != clock_source::IN0 {
-        return Err(());
+    /** Clear PLL slip, this is done repeatedly until lock is achieved */
+    fn pll_clear_slip(&self, pll: pll) -> bool {
+        let ptr = self.regs.sysctl();
+        unsafe {
+            (*ptr).pll_lock.modify(|_, w| match pll {
+                pll::PLL0 => w.pll_slip_clear0().set_bit(),
+                pll::PLL1 => w.pll_slip_clear1().set_bit(),
+                pll::PLL2 => w.pll_slip_clear2().set_bit(),
+            });
+        }
+        self.pll_is_lock(pll)
     }
-    let freq_in = clock_source_get_freq(source);
-    if freq_in == 0 {
-        return Err(());
+
+    /** Clear slips until the PLL locks, false if the lock budget runs out first */
+    fn pll_wait_lock(&self, pll: pll) -> bool {
+        let start = self.regs.cycles();
+        while !self.pll_is_lock(pll) {
+            if self.regs.cycles().wrapping_sub(start) >= self.lock_budget {
+                return false;
+            }
+            self.pll_clear_slip(pll);
+        }
+        true
     }
-    if let Some(found) = pll_compute::compute_params(freq_in, freq) {
-        let ptr = pac::SYSCTL::ptr();
+
+    fn pll_write_params(&self, pll: pll, cksel: u8, found: &pll_compute::Params) {
+        use pll::*;
+        let ptr = self.regs.sysctl();
         unsafe {
             match pll {
                 PLL0 => {
@@ -924,7 +1259,7 @@ fn pll_source_set_freq(pll: pll, source: clock_source, freq: u32) -> Result<u32,
                 PLL2 => {
                     (*ptr).pll2.modify(|_, w| {
                         w.ckin_sel()
-                            .bits(pll2_source_to_cksel(source))
+                            .bits(cksel)
                             .clkr()
                             .bits(found.clkr)
                             .clkf()
@@ -937,108 +1272,443 @@ fn pll_source_set_freq(pll: pll, source: clock_source, freq: u32) -> Result<u32,
                 }
             }
         }
-        Ok(pll_get_freq(pll))
-    } else {
-        Err(())
-    }
-}
-
-/**
- * @brief       Init PLL freqency
- * @param[in]   pll            The PLL id
- * @param[in]   pll_freq       The desired frequency in Hz
- */
-pub fn pll_set_freq(pll: pll, freq: u32) -> Result<u32, ()> {
-    assert!(freq != 0);
-    let ptr = pac::SYSCTL::ptr();
-    use pll::*;
-
-    /* 1. Change CPU CLK to XTAL */
-    if pll == PLL0 {
-        clock_set_clock_select(clock_select::ACLK, 0 /* clock_source::IN0 */);
     }
 
-    /* 2. Disable PLL output */
-    unsafe {
-        match pll {
-            PLL0 => (*ptr).pll0.modify(|_, w| w.out_en().clear_bit()),
-            PLL1 => (*ptr).pll1.modify(|_, w| w.out_en().clear_bit()),
-            PLL2 => (*ptr).pll2.modify(|_, w| w.out_en().clear_bit()),
+    /**
+     * @brief       Init PLL freqency
+     * @param[in]   pll            The PLL id
+     * @param[in]   pll_freq       The desired frequency in Hz
+     */
+    pub fn pll_set_freq(&self, pll: pll, freq: u32) -> Result<u32, SysctlError> {
+        /* PLL2 keeps its current input, PLL0 and PLL1 always run from IN0 */
+        let source = if pll == pll::PLL2 {
+            pll2_cksel_to_source(self.clock_get_clock_select(clock_select::PLL2))?
+        } else {
+            clock_source::IN0
         };
+        let freq_in = self.clock_source_get_freq(source)?;
+        match pll_compute::compute_params(freq_in, freq) {
+            Some(found) => self.pll_set_params(pll, source, &found),
+            None => Err(SysctlError::PllOutOfRange(pll, freq)),
+        }
     }
 
-    /* 3. Turn off PLL */
-    unsafe {
-        match pll {
-            PLL0 => (*ptr).pll0.modify(|_, w| w.pwrd().clear_bit()),
-            PLL1 => (*ptr).pll1.modify(|_, w| w.pwrd().clear_bit()),
-            PLL2 => (*ptr).pll2.modify(|_, w| w.pwrd().clear_bit()),
-        };
-    }
+    /**
+     * @brief       Program precomputed PLL parameters
+     * @param[in]   pll            The PLL id
+     * @param[in]   source         The PLL input, must be IN0 for PLL0 and PLL1
+     * @param[in]   params         The r/f/od/bwadj register values
+     */
+    pub fn pll_set_params(
+        &self,
+        pll: pll,
+        source: clock_source,
+        params: &pll_compute::Params,
+    ) -> Result<u32, SysctlError> {
+        let ptr = self.regs.sysctl();
+        use pll::*;
 
-    /* 4. Set PLL to new value */
-    let result = if pll == PLL2 {
-        pll_source_set_freq(
-            pll,
-            pll2_cksel_to_source(clock_get_clock_select(clock_select::PLL2)),
-            freq,
-        )
-    } else {
-        pll_source_set_freq(pll, clock_source::IN0, freq)
-    };
-
-    /* 5. Power on PLL */
-    unsafe {
-        match pll {
-            PLL0 => (*ptr).pll0.modify(|_, w| w.pwrd().set_bit()),
-            PLL1 => (*ptr).pll1.modify(|_, w| w.pwrd().set_bit()),
-            PLL2 => (*ptr).pll2.modify(|_, w| w.pwrd().set_bit()),
-        };
+        /* PLL0 and 1 can only source from IN0 */
+        if (pll == PLL0 || pll == PLL1) && source != clock_source::IN0 {
+            return Err(SysctlError::UnsupportedSource(source));
+        }
+        let cksel = pll2_source_to_cksel(source)?;
+
+        /* 1. Change CPU CLK to XTAL */
+        if pll == PLL0 {
+            self.clock_set_clock_select(clock_select::ACLK, 0 /* clock_source::IN0 */)?;
+        }
+
+        /* 2. Disable PLL output */
+        unsafe {
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.out_en().clear_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.out_en().clear_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.out_en().clear_bit()),
+            };
+        }
+
+        /* 3. Turn off PLL */
+        unsafe {
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.pwrd().clear_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.pwrd().clear_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.pwrd().clear_bit()),
+            };
+        }
+
+        /* 4. Set PLL to new value */
+        self.pll_write_params(pll, cksel, params);
+
+        /* 5. Power on PLL */
+        unsafe {
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.pwrd().set_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.pwrd().set_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.pwrd().set_bit()),
+            };
+        }
+
+        /* wait >100ns */
+        self.regs.usleep(1);
+
+        /* 6. Reset PLL then Release Reset*/
+        unsafe {
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.reset().clear_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.reset().clear_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.reset().clear_bit()),
+            };
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.reset().set_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.reset().set_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.reset().set_bit()),
+            };
+        }
+        /* wait >100ns */
+        self.regs.usleep(1);
+        unsafe {
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.reset().clear_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.reset().clear_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.reset().clear_bit()),
+            };
+        }
+
+        /* 7. Get lock status, wait PLL stable */
+        if !self.pll_wait_lock(pll) {
+            /*
+             * Leave the output disabled. For PLL0 this also leaves the CPU on
+             * IN0 (step 1), so it keeps running from the crystal.
+             */
+            return Err(SysctlError::LockTimeout(pll));
+        }
+
+        /* 8. Enable PLL output */
+        unsafe {
+            match pll {
+                PLL0 => (*ptr).pll0.modify(|_, w| w.out_en().set_bit()),
+                PLL1 => (*ptr).pll1.modify(|_, w| w.out_en().set_bit()),
+                PLL2 => (*ptr).pll2.modify(|_, w| w.out_en().set_bit()),
+            };
+        }
+
+        /* 9. Change CPU CLK to PLL */
+        if pll == PLL0 {
+            self.clock_set_clock_select(clock_select::ACLK, 1 /*clock_source::PLL0*/)?;
+        }
+        self.pll_get_freq(pll)
     }
 
-    /* wait >100ns */
-    usleep(1);
+    /**
+     * Write a human-readable table of the PLLs, every clock, divider and clock select, and the
+     * IO bank voltages.
+     */
+    pub fn report<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        let on = |state: bool| if state { "on" } else { "off" };
 
-    /* 6. Reset PLL then Release Reset*/
-    unsafe {
-        match pll {
-            PLL0 => (*ptr).pll0.modify(|_, w| w.reset().clear_bit()),
-            PLL1 => (*ptr).pll1.modify(|_, w| w.reset().clear_bit()),
-            PLL2 => (*ptr).pll2.modify(|_, w| w.reset().clear_bit()),
-        };
-        match pll {
-            PLL0 => (*ptr).pll0.modify(|_, w| w.reset().set_bit()),
-            PLL1 => (*ptr).pll1.modify(|_, w| w.reset().set_bit()),
-            PLL2 => (*ptr).pll2.modify(|_, w| w.reset().set_bit()),
-        };
+        writeln!(w, "PLL    freq (Hz)     power  lock  output")?;
+        for &pll in &[pll::PLL0, pll::PLL1, pll::PLL2] {
+            let clock = match pll {
+                pll::PLL0 => clock::PLL0,
+                pll::PLL1 => clock::PLL1,
+                pll::PLL2 => clock::PLL2,
+            };
+            column(w, pll, 7)?;
+            freq_column(w, self.pll_get_freq(pll))?;
+            writeln!(
+                w,
+                "{:<6} {:<5} {}",
+                on(self.pll_is_powered(pll)),
+                if self.pll_is_lock(pll) { "yes" } else { "no" },
+                on(self.clock_is_enabled(clock))
+            )?;
+        }
+
+        writeln!(w, "\nclock  freq (Hz)     enabled")?;
+        for &clock in clock::ALL {
+            column(w, clock, 7)?;
+            freq_column(w, self.clock_get_freq(clock))?;
+            writeln!(w, "{}", on(self.clock_is_enabled(clock)))?;
+        }
+
+        writeln!(w, "\nthreshold  value  max")?;
+        for &which in threshold::ALL {
+            column(w, which, 11)?;
+            writeln!(w, "{:<6} {}", self.clock_get_threshold(which), which.max())?;
+        }
+
+        writeln!(w, "\nclock select  value")?;
+        for &which in clock_select::ALL {
+            column(w, which, 14)?;
+            writeln!(w, "{}", self.clock_get_clock_select(which))?;
+        }
+
+        writeln!(w, "\nbank   voltage")?;
+        for &bank in power_bank::ALL {
+            column(w, bank, 7)?;
+            match self.get_power_mode(bank) {
+                io_power_mode::V33 => writeln!(w, "3.3V")?,
+                io_power_mode::V18 => writeln!(w, "1.8V")?,
+            }
+        }
+        Ok(())
     }
-    /* wait >100ns */
-    usleep(1);
-    unsafe {
-        match pll {
-            PLL0 => (*ptr).pll0.modify(|_, w| w.reset().clear_bit()),
-            PLL1 => (*ptr).pll1.modify(|_, w| w.reset().clear_bit()),
-            PLL2 => (*ptr).pll2.modify(|_, w| w.reset().clear_bit()),
-        };
+}
+
+/** Write a frequency column, or why it could not be worked out */
+fn freq_column<W: fmt::Write>(w: &mut W, freq: Result<u32, SysctlError>) -> fmt::Result {
+    match freq {
+        Ok(freq) => write!(w, "{:<13} ", freq),
+        Err(err) => write!(w, "({}) ", err),
     }
+}
+
+/** Lock budget of the free functions, see `set_pll_lock_budget` */
+static LOCK_BUDGET: AtomicUsize = AtomicUsize::new(PLL_LOCK_BUDGET);
+
+/** SYSCTL of the running SoC */
+fn sysctl() -> Sysctl<Mmio> {
+    Sysctl::new(Mmio).with_lock_budget(pll_lock_budget())
+}
+
+/** Number of clock listeners that can be subscribed at the same time */
+pub const MAX_CLOCK_LISTENERS: usize = 8;
 
-    /* 7. Get lock status, wait PLL stable */
-    while !pll_is_lock(pll) {
-        pll_clear_slip(pll);
+/** Clock configuration before and after a change */
+pub struct ClockChange {
+    pub before: ClockTree,
+    pub after: ClockTree,
+}
+
+impl ClockChange {
+    /** Whether the frequency of `clock` is different now */
+    pub fn affects(&self, clock: clock) -> bool {
+        self.before.freq(clock).ok() != self.after.freq(clock).ok()
     }
+}
 
-    /* 8. Enable PLL output */
-    unsafe {
-        match pll {
-            PLL0 => (*ptr).pll0.modify(|_, w| w.out_en().set_bit()),
-            PLL1 => (*ptr).pll1.modify(|_, w| w.out_en().set_bit()),
-            PLL2 => (*ptr).pll2.modify(|_, w| w.out_en().set_bit()),
+/**
+ * Called after the clocks changed, so drivers can re-derive their dividers (baud rates, SPI
+ * clock, ...). Listeners run with interrupts enabled and must not change clocks themselves.
+ */
+pub type clock_listener = fn(&ClockChange);
+
+/** Handle of a subscribed clock listener */
+#[derive(Debug, PartialEq, Eq)]
+pub struct ClockSubscription(usize);
+
+/** Listener slots, only accessed with interrupts disabled */
+struct Listeners(UnsafeCell<[Option<clock_listener>; MAX_CLOCK_LISTENERS]>);
+
+unsafe impl Sync for Listeners {}
+
+static LISTENERS: Listeners = Listeners(UnsafeCell::new([None; MAX_CLOCK_LISTENERS]));
+
+/** Number of `clock_change` calls in progress */
+static CHANGES: AtomicUsize = AtomicUsize::new(0);
+
+/** Call `listener` after every change of a PLL, divider or clock select */
+pub fn clock_subscribe(listener: clock_listener) -> Result<ClockSubscription, SysctlError> {
+    cs(|| {
+        let listeners = unsafe { &mut *LISTENERS.0.get() };
+        let slot = listeners
+            .iter()
+            .position(Option::is_none)
+            .ok_or(SysctlError::TooManyListeners)?;
+        listeners[slot] = Some(listener);
+        Ok(ClockSubscription(slot))
+    })
+}
+
+pub fn clock_unsubscribe(subscription: ClockSubscription) {
+    cs(|| unsafe {
+        (*LISTENERS.0.get())[subscription.0] = None;
+    })
+}
+
+impl<R: Registers> Sysctl<R> {
+    /**
+     * Run `change`, then notify the clock listeners if the clock configuration is different
+     * afterwards, even if `change` failed halfway. Nested changes notify once they are all done.
+     */
+    pub fn clock_change<T, F>(&self, change: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let outermost = CHANGES.fetch_add(1, Ordering::Relaxed) == 0;
+        let before = if outermost {
+            Some(self.clock_tree())
+        } else {
+            None
         };
-    }
+        let result = change();
+        CHANGES.fetch_sub(1, Ordering::Relaxed);
 
-    /* 9. Change CPU CLK to PLL */
-    if pll == PLL0 {
-        clock_set_clock_select(clock_select::ACLK, 1 /*clock_source::PLL0*/);
+        let before = match before {
+            Some(before) => before,
+            None => return result,
+        };
+        let after = self.clock_tree();
+        if before != after {
+            let listeners = cs(|| unsafe { *LISTENERS.0.get() });
+            let change = ClockChange { before, after };
+            for listener in listeners.iter().flatten() {
+                listener(&change);
+            }
+        }
+        result
     }
-    result
+}
+
+/** `Sysctl::clock_change` on the running SoC */
+pub fn clock_change<T, F>(change: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    sysctl().clock_change(change)
+}
+
+pub fn clock_enable(clock: clock) {
+    sysctl().clock_enable(clock)
+}
+
+pub fn clock_is_enabled(clock: clock) -> bool {
+    sysctl().clock_is_enabled(clock)
+}
+
+pub fn sysctl_clock_disable(clock: clock) {
+    sysctl().sysctl_clock_disable(clock)
+}
+
+pub fn clock_set_threshold(which: threshold, threshold: u32) -> Result<(), SysctlError> {
+    clock_change(|| sysctl().clock_set_threshold(which, threshold))
+}
+
+pub fn clock_get_threshold(which: threshold) -> u32 {
+    sysctl().clock_get_threshold(which)
+}
+
+pub fn set_power_mode(power_bank: power_bank, mode: io_power_mode) {
+    sysctl().set_power_mode(power_bank, mode)
+}
+
+pub fn get_power_mode(power_bank: power_bank) -> io_power_mode {
+    sysctl().get_power_mode(power_bank)
+}
+
+pub fn set_spi0_dvp_data(status: bool) {
+    sysctl().set_spi0_dvp_data(status)
+}
+
+pub fn clock_tree() -> ClockTree {
+    sysctl().clock_tree()
+}
+
+pub fn pll_get_freq(pll: pll) -> Result<u32, SysctlError> {
+    sysctl().pll_get_freq(pll)
+}
+
+pub fn clock_source_get_freq(source: clock_source) -> Result<u32, SysctlError> {
+    sysctl().clock_source_get_freq(source)
+}
+
+pub fn clock_set_clock_select(which: clock_select, select: u8) -> Result<(), SysctlError> {
+    clock_change(|| sysctl().clock_set_clock_select(which, select))
+}
+
+pub fn clock_get_clock_select(which: clock_select) -> u8 {
+    sysctl().clock_get_clock_select(which)
+}
+
+pub fn clock_get_freq(clock: clock) -> Result<u32, SysctlError> {
+    sysctl().clock_get_freq(clock)
+}
+
+pub fn reset(reset: reset) {
+    sysctl().reset(reset)
+}
+
+pub fn reset_is_asserted(reset: reset) -> bool {
+    sysctl().reset_is_asserted(reset)
+}
+
+pub fn reset_group(group: &[reset]) {
+    sysctl().reset_group(group)
+}
+
+/** Last reset cause recorded by `last_reset_cause`, plus one so zero means not read yet */
+static RESET_CAUSE: AtomicUsize = AtomicUsize::new(0);
+
+/**
+ * Cause of the last SoC reset. The first call reads and clears the hardware status, later calls
+ * return the same cause until the next reset.
+ */
+pub fn last_reset_cause() -> reset_cause {
+    last_reset_cause_on(&sysctl())
+}
+
+/** `last_reset_cause`, reading the status through `sysctl` */
+pub fn last_reset_cause_on<R: Registers>(sysctl: &Sysctl<R>) -> reset_cause {
+    let causes = [
+        reset_cause::HARD,
+        reset_cause::WDT0,
+        reset_cause::WDT1,
+        reset_cause::SOFT,
+        reset_cause::PIN,
+    ];
+    cs(|| match RESET_CAUSE.load(Ordering::Relaxed) {
+        0 => {
+            let cause = sysctl.reset_cause();
+            sysctl.clear_reset_cause();
+            RESET_CAUSE.store(cause as usize + 1, Ordering::Relaxed);
+            cause
+        }
+        stored => causes[stored - 1],
+    })
+}
+
+pub fn dma_select(channel: dma_channel, select: dma_select) {
+    sysctl().dma_select(channel, select)
+}
+
+/**
+ * Number of CPU cycles `pll_set_freq`, `pll_set_params` and `ClockPlan::apply` wait for a PLL to
+ * lock, `PLL_LOCK_BUDGET` until changed
+ */
+pub fn set_pll_lock_budget(cycles: usize) {
+    LOCK_BUDGET.store(cycles, Ordering::Relaxed);
+}
+
+pub fn pll_lock_budget() -> usize {
+    LOCK_BUDGET.load(Ordering::Relaxed)
+}
+
+pub fn pll_set_freq(pll: pll, freq: u32) -> Result<u32, SysctlError> {
+    clock_change(|| sysctl().pll_set_freq(pll, freq))
+}
+
+pub fn pll_set_params(
+    pll: pll,
+    source: clock_source,
+    params: &pll_compute::Params,
+) -> Result<u32, SysctlError> {
+    clock_change(|| sysctl().pll_set_params(pll, source, params))
+}
+
+pub fn pll_is_powered(pll: pll) -> bool {
+    sysctl().pll_is_powered(pll)
+}
+
+pub fn pll_in_use(pll: pll) -> bool {
+    sysctl().pll_in_use(pll)
+}
+
+pub fn pll_power_down(pll: pll) {
+    sysctl().pll_power_down(pll)
+}
+
+/** Write the clock and power state of the SoC to `w`, e.g. a serial port */
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    sysctl().report(w)
 }
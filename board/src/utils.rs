@@ -1,4 +1,5 @@
 //! Misc utilities for the board
+use core::fmt::{self, Write};
 
 pub fn set_bit(inval: u32, bit: u8, state: bool) -> u32 {
     if state {
@@ -8,6 +9,43 @@ pub fn set_bit(inval: u32, bit: u8, state: bool) -> u32 {
     }
 }
 
+/**
+ * Run `f` with interrupts disabled. The host tests have no interrupts to disable, and the `riscv`
+ * crate does not implement disabling them off target, so there `f` is just called.
+ */
+#[cfg(target_arch = "riscv64")]
+pub(crate) fn cs<T, F: FnOnce() -> T>(f: F) -> T {
+    riscv::interrupt::free(|_| f())
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+pub(crate) fn cs<T, F: FnOnce() -> T>(f: F) -> T {
+    f()
+}
+
 pub fn get_bit(inval: u32, bit: u8) -> bool {
     (inval & (1 << u32::from(bit))) != 0
 }
+
+/** Write the `Debug` form of `value`, padded to `width` characters */
+pub fn column<W: fmt::Write, T: fmt::Debug>(w: &mut W, value: T, width: usize) -> fmt::Result {
+    /* Derived Debug ignores the width of the format spec, so count by hand */
+    struct Counter<'a, W> {
+        inner: &'a mut W,
+        count: usize,
+    }
+
+    impl<'a, W: fmt::Write> fmt::Write for Counter<'a, W> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.count += s.len();
+            self.inner.write_str(s)
+        }
+    }
+
+    let mut counter = Counter { inner: w, count: 0 };
+    write!(counter, "{:?}", value)?;
+    for _ in counter.count..width {
+        counter.inner.write_char(' ')?;
+    }
+    Ok(())
+}
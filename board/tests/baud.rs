@@ -0,0 +1,17 @@
+//! Dividers re-derived after clock changes
+use board::baud::{spi_divider, uarths_divider};
+
+#[test]
+fn uarths() {
+    assert_eq!(uarths_divider(403_000_000, 115_200), 3497);
+    assert_eq!(uarths_divider(26_000_000, 115_200), 224);
+    /* Faster than the clock saturates instead of underflowing */
+    assert_eq!(uarths_divider(100_000, 115_200), 0);
+}
+
+#[test]
+fn spi() {
+    assert_eq!(spi_divider(403_000_000, 12_000_000), 33);
+    assert_eq!(spi_divider(13_000_000, 12_000_000), 2);
+    assert_eq!(spi_divider(806_000_000, 1_000), 65534);
+}
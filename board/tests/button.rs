@@ -0,0 +1,140 @@
+//! Key events from synthetic samples
+use board::button::{action, key, Buttons, Event, Timing};
+
+/** Sample every ms from `from` to `to`, `down` tells the level at a time */
+fn run<F: Fn(u32) -> bool>(buttons: &mut Buttons, key: key, from: u32, to: u32, down: F) {
+    let mut now = from;
+    while now != to {
+        buttons.sample(key, down(now), now);
+        now = now.wrapping_add(1);
+    }
+}
+
+fn events(buttons: &mut Buttons) -> Vec<(key, action, u32)> {
+    let mut events = Vec::new();
+    while let Some(Event { key, action, time }) = buttons.next_event() {
+        events.push((key, action, time));
+    }
+    events
+}
+
+#[test]
+fn bounces_are_ignored() {
+    let mut buttons = Buttons::new(Timing::DEFAULT);
+    /* Contact bounce for 10 ms, the last one at 9 ms runs into holding until 100 ms */
+    run(&mut buttons, key::CENTER, 0, 200, |t| {
+        (t < 10 && t % 3 == 0) || (10..100).contains(&t)
+    });
+    assert_eq!(
+        events(&mut buttons),
+        [
+            (key::CENTER, action::PRESS, 29),
+            (key::CENTER, action::RELEASE, 120),
+        ]
+    );
+    assert!(!buttons.is_down(key::CENTER));
+}
+
+#[test]
+fn short_glitch_is_not_a_press() {
+    let mut buttons = Buttons::new(Timing::DEFAULT);
+    run(&mut buttons, key::DIR1, 0, 100, |t| (40..55).contains(&t));
+    assert_eq!(events(&mut buttons), []);
+}
+
+#[test]
+fn long_press_and_repeat() {
+    let mut buttons = Buttons::new(Timing::DEFAULT);
+    run(&mut buttons, key::DIR2, 0, 1300, |t| t < 1200);
+    let events = events(&mut buttons);
+    let press = 20;
+    assert_eq!(events[0], (key::DIR2, action::PRESS, press));
+    let repeats: Vec<u32> = events
+        .iter()
+        .filter(|e| e.1 == action::REPEAT)
+        .map(|e| e.2)
+        .collect();
+    assert_eq!(
+        repeats,
+        (0..7).map(|n| press + 500 + n * 100).collect::<Vec<_>>()
+    );
+    assert!(events.contains(&(key::DIR2, action::LONG_PRESS, press + 1000)));
+    assert_eq!(events.last(), Some(&(key::DIR2, action::RELEASE, 1220)));
+}
+
+#[test]
+fn double_click() {
+    let mut buttons = Buttons::new(Timing::DEFAULT);
+    let clicks = |t: u32| (0..80).contains(&t) || (200..280).contains(&t);
+    run(&mut buttons, key::CENTER, 0, 400, clicks);
+    assert_eq!(
+        events(&mut buttons),
+        [
+            (key::CENTER, action::PRESS, 20),
+            (key::CENTER, action::RELEASE, 100),
+            (key::CENTER, action::PRESS, 220),
+            (key::CENTER, action::RELEASE, 300),
+            (key::CENTER, action::DOUBLE_CLICK, 300),
+        ]
+    );
+
+    /* Too slow, and a long press does not start a double click */
+    let mut buttons = Buttons::new(Timing::DEFAULT);
+    run(&mut buttons, key::CENTER, 0, 2000, |t| {
+        (0..80).contains(&t) || (500..580).contains(&t) || (700..1800).contains(&t)
+    });
+    run(&mut buttons, key::CENTER, 2000, 2100, |t| t < 2050);
+    assert!(events(&mut buttons)
+        .iter()
+        .all(|e| e.1 != action::DOUBLE_CLICK));
+}
+
+#[test]
+fn keys_are_independent() {
+    let mut buttons = Buttons::new(Timing::DEFAULT);
+    let mut now = 0;
+    while now < 100 {
+        buttons.sample_all(now, |key| {
+            key == key::DIR1 || (key == key::DIR2 && now >= 50)
+        });
+        now += 1;
+    }
+    assert_eq!(
+        events(&mut buttons),
+        [
+            (key::DIR1, action::PRESS, 20),
+            (key::DIR2, action::PRESS, 70),
+        ]
+    );
+    assert!(buttons.is_down(key::DIR1));
+    assert!(!buttons.is_down(key::CENTER));
+}
+
+#[test]
+fn time_may_wrap() {
+    let mut buttons = Buttons::new(Timing::DEFAULT);
+    let start = u32::MAX - 600;
+    run(&mut buttons, key::DIR1, start, 700, |t| {
+        t >= start || t < 600
+    });
+    let events = events(&mut buttons);
+    assert_eq!(events[0], (key::DIR1, action::PRESS, start + 20));
+    assert!(events.contains(&(key::DIR1, action::LONG_PRESS, start.wrapping_add(1020))));
+    assert_eq!(events.last(), Some(&(key::DIR1, action::RELEASE, 620)));
+}
+
+#[test]
+fn full_queue_drops_newest() {
+    let timing = Timing {
+        repeat_delay_ms: 0,
+        repeat_ms: 1,
+        ..Timing::DEFAULT
+    };
+    let mut buttons = Buttons::new(timing);
+    run(&mut buttons, key::DIR1, 0, 100, |_| true);
+    assert!(buttons.dropped() > 0);
+    let events = events(&mut buttons);
+    assert_eq!(events.len(), board::button::EVENT_QUEUE_LEN);
+    assert_eq!(events[0], (key::DIR1, action::PRESS, 20));
+    assert_eq!(buttons.next_event(), None);
+}
@@ -0,0 +1,140 @@
+//! Clock plans solved on the host
+use board::clock_plan::{error_ppm, ClockPlan, Entry, Target};
+use board::clock_tree::ClockTree;
+use board::lcd;
+use board::pll_compute;
+use board::regs::{Fake, Registers};
+use board::sysctl::{clock, clock_select, pll, threshold, Sysctl, SysctlError};
+
+const IN0: u32 = 26_000_000;
+
+/* Byte offset of the lock register, which the hardware sets */
+const PLL_LOCK: usize = 0x18;
+
+/** LCD and camera on PLL0 at 800 MHz, with the CPU at half of it */
+const CAMERA_BOARD: &[Target] = &[
+    Target::new(clock::PLL0, 800_000_000),
+    Target::new(clock::CPU, 400_000_000),
+    Target::new(clock::SPI0, lcd::SPI_CLK),
+    Target::new(clock::DVP, 24_000_000),
+];
+
+fn entry(clock: clock, target: u32, achieved: u32) -> Entry {
+    Entry {
+        clock,
+        target,
+        achieved,
+        error_ppm: error_ppm(target, achieved),
+    }
+}
+
+#[test]
+fn camera_board() {
+    let plan = ClockPlan::solve(&ClockTree::new(IN0), CAMERA_BOARD).unwrap();
+    let tree = plan.tree();
+    /* 26 MHz * 31 is as close to 800 MHz as PLL0 gets */
+    assert_eq!(tree.pll_freq(pll::PLL0), Ok(806_000_000));
+    assert_eq!(tree.get_clock_select(clock_select::ACLK), 1);
+    assert_eq!(tree.get_threshold(threshold::ACLK), 0);
+    /* 806 MHz / 44 */
+    assert_eq!(tree.get_threshold(threshold::SPI0), 21);
+    /* 403 MHz / 16, the largest DVP divider */
+    assert_eq!(tree.get_threshold(threshold::DVP), 15);
+
+    let entries: Vec<Entry> = plan.entries().collect();
+    assert_eq!(
+        entries,
+        vec![
+            entry(clock::PLL0, 800_000_000, 806_000_000),
+            entry(clock::CPU, 400_000_000, 403_000_000),
+            entry(clock::SPI0, 18_000_000, 18_318_181),
+            entry(clock::DVP, 24_000_000, 25_187_500),
+        ]
+    );
+    assert_eq!(entries[0].error_ppm, 7500);
+    assert_eq!(entries[2].error_ppm, 17677);
+    assert_eq!(plan.max_error_ppm(), 49479);
+}
+
+#[test]
+fn untargeted_clocks_are_kept() {
+    let mut base = ClockTree::new(IN0);
+    base.set_threshold(threshold::APB0, 3);
+    let pll1 = pll_compute::compute_params(IN0, 300_000_000).unwrap();
+    base.set_pll(pll::PLL1, pll1);
+    let plan = ClockPlan::solve(&base, &CAMERA_BOARD[..1]).unwrap();
+    assert_eq!(plan.tree().get_threshold(threshold::APB0), 3);
+    assert_eq!(plan.tree().get_pll(pll::PLL1), &pll1);
+    /* Without a target behind ACLK its mux stays on IN0 */
+    assert_eq!(plan.tree().get_clock_select(clock_select::ACLK), 0);
+    assert_eq!(plan.tree().freq(clock::CPU), Ok(IN0));
+}
+
+#[test]
+fn targets_share_a_pll() {
+    /* Both hang off PLL0, which has to suit both dividers */
+    let targets = [
+        Target::new(clock::SPI0, lcd::SPI_CLK),
+        Target::new(clock::I2C0, 25_000_000),
+    ];
+    let plan = ClockPlan::solve(&ClockTree::new(IN0), &targets).unwrap();
+    let pll0 = plan.tree().pll_freq(pll::PLL0).unwrap();
+    assert_eq!(pll0, 650_000_000);
+    for entry in plan.entries() {
+        let which = match entry.clock {
+            clock::SPI0 => threshold::SPI0,
+            _ => threshold::I2C0,
+        };
+        let value = plan.tree().get_threshold(which);
+        assert_eq!(entry.achieved, pll0 / ((value + 1) * 2));
+    }
+    assert_eq!(
+        plan.entries()
+            .map(|entry| entry.achieved)
+            .collect::<Vec<_>>(),
+        vec![18_055_555, 25_000_000]
+    );
+    assert_eq!(plan.max_error_ppm(), 3086);
+}
+
+#[test]
+fn unreachable_targets() {
+    let base = ClockTree::new(IN0);
+    let targets = [
+        Target::new(clock::SPI0, lcd::SPI_CLK),
+        Target::new(clock::DVP, 0),
+    ];
+    assert_eq!(
+        ClockPlan::solve(&base, &targets).err(),
+        Some(SysctlError::InvalidTarget(clock::DVP, 0))
+    );
+    let targets = [Target::new(clock::PLL1, 3_000_000_000)];
+    assert_eq!(
+        ClockPlan::solve(&base, &targets).err(),
+        Some(SysctlError::PllOutOfRange(pll::PLL1, 3_000_000_000))
+    );
+}
+
+#[test]
+fn error_in_ppm() {
+    assert_eq!(error_ppm(1_000_000, 1_000_000), 0);
+    assert_eq!(error_ppm(1_000_000, 1_000_500), 500);
+    assert_eq!(error_ppm(1_000_000, 999_500), 500);
+    assert_eq!(error_ppm(0, 1), u32::max_value());
+}
+
+#[test]
+fn apply_reads_back() {
+    let fake = Fake::new();
+    /* All three PLLs report lock */
+    unsafe { *(fake.sysctl() as *mut u32).add(PLL_LOCK / 4) = 0x0001_0303 };
+    let sysctl = Sysctl::new(&fake);
+
+    let plan = ClockPlan::solve(&sysctl.clock_tree(), CAMERA_BOARD).unwrap();
+    plan.apply_to(&sysctl).unwrap();
+    assert_eq!(sysctl.clock_tree(), *plan.tree());
+    for entry in plan.entries() {
+        assert_eq!(sysctl.clock_get_freq(entry.clock), Ok(entry.achieved));
+    }
+    assert!(sysctl.pll_is_powered(pll::PLL0));
+}
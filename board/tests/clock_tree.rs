@@ -0,0 +1,130 @@
+//! Clock frequencies derived by `ClockTree`, without any hardware
+use board::clock_tree::{divider, parent, route, ClockTree};
+use board::pll_compute::Params;
+use board::regs::Fake;
+use board::sysctl::{clock, clock_select, clock_source, pll, threshold, Sysctl, SysctlError};
+
+const IN0: u32 = 26_000_000;
+
+/** What `pll_compute` picks for 800 MHz: 26 MHz * 31 */
+const PLL0_800M: Params = Params {
+    clkr: 0,
+    clkf: 30,
+    clkod: 0,
+    bwadj: 30,
+};
+
+/** PLL0 at 806 MHz feeding ACLK, the buses and the SPI/DVP dividers */
+fn pll0_tree() -> ClockTree {
+    let mut tree = ClockTree::new(IN0);
+    tree.set_pll(pll::PLL0, PLL0_800M);
+    tree.set_clock_select(clock_select::ACLK, 1);
+    tree.set_threshold(threshold::ACLK, 0);
+    tree.set_threshold(threshold::APB0, 1);
+    tree.set_threshold(threshold::APB1, 0);
+    tree.set_threshold(threshold::APB2, 2);
+    tree.set_threshold(threshold::SPI0, 21);
+    tree.set_threshold(threshold::DVP, 15);
+    tree
+}
+
+#[test]
+fn reset_default() {
+    /* All registers at zero: PLLs multiply by one, everything runs from IN0 */
+    let tree = ClockTree::new(IN0);
+    assert_eq!(Sysctl::new(&Fake::new()).clock_tree(), tree);
+    for &pll in &[pll::PLL0, pll::PLL1, pll::PLL2] {
+        assert_eq!(tree.pll_freq(pll), Ok(IN0));
+    }
+    assert_eq!(tree.source_freq(clock_source::ACLK), Ok(IN0));
+    for &clock in &[
+        clock::CPU,
+        clock::APB0,
+        clock::APB1,
+        clock::APB2,
+        clock::DVP,
+    ] {
+        assert_eq!(tree.freq(clock), Ok(IN0), "{:?}", clock);
+    }
+    /* Dividers that double still halve at threshold zero */
+    assert_eq!(tree.freq(clock::SPI0), Ok(IN0 / 2));
+    assert_eq!(tree.freq(clock::TIMER0), Ok(IN0 / 2));
+    assert_eq!(tree.freq(clock::WDT0), Ok(IN0 / 2));
+}
+
+#[test]
+fn pll0_800m() {
+    let tree = pll0_tree();
+    assert_eq!(tree.pll_freq(pll::PLL0), Ok(806_000_000));
+    assert_eq!(tree.freq(clock::PLL0), Ok(806_000_000));
+
+    /* ACLK from PLL0 is divided by 2 << threshold */
+    assert_eq!(tree.freq(clock::ACLK), Ok(403_000_000));
+    assert_eq!(tree.freq(clock::CPU), Ok(403_000_000));
+    let mut slow = tree;
+    slow.set_threshold(threshold::ACLK, 2);
+    assert_eq!(slow.freq(clock::CPU), Ok(100_750_000));
+
+    /* The APB dividers add one */
+    assert_eq!(
+        route(clock::APB0),
+        (
+            parent::SOURCE(clock_source::ACLK),
+            divider::ADD_ONE(threshold::APB0)
+        )
+    );
+    assert_eq!(tree.freq(clock::APB0), Ok(201_500_000));
+    assert_eq!(tree.freq(clock::GPIO), Ok(201_500_000));
+    assert_eq!(tree.freq(clock::APB1), Ok(403_000_000));
+    assert_eq!(tree.freq(clock::APB2), Ok(134_333_333));
+    assert_eq!(tree.freq(clock::DVP), Ok(25_187_500));
+
+    /* SPI0 comes from PLL0 directly and doubles */
+    assert_eq!(
+        route(clock::SPI0),
+        (
+            parent::SOURCE(clock_source::PLL0),
+            divider::DOUBLE(threshold::SPI0)
+        )
+    );
+    assert_eq!(tree.freq(clock::SPI0), Ok(18_318_181));
+
+    /* Back on IN0, only the clocks behind ACLK slow down */
+    let mut in0 = tree;
+    in0.set_clock_select(clock_select::ACLK, 0);
+    assert_eq!(in0.freq(clock::CPU), Ok(IN0));
+    assert_eq!(in0.freq(clock::APB0), Ok(IN0 / 2));
+    assert_eq!(in0.freq(clock::SPI0), Ok(18_318_181));
+}
+
+#[test]
+fn divider_factors() {
+    assert_eq!(divider::NONE.factor(7), 1);
+    assert_eq!(divider::ADD_ONE(threshold::APB0).factor(0), 1);
+    assert_eq!(divider::ADD_ONE(threshold::APB0).factor(7), 8);
+    assert_eq!(divider::DOUBLE(threshold::SPI0).factor(0), 2);
+    assert_eq!(divider::DOUBLE(threshold::SPI0).factor(7), 16);
+    assert_eq!(divider::NONE.threshold(), None);
+    assert_eq!(
+        divider::DOUBLE(threshold::SPI0).threshold(),
+        Some(threshold::SPI0)
+    );
+}
+
+#[test]
+fn pll2_input() {
+    let mut tree = pll0_tree();
+    tree.set_clock_select(clock_select::PLL2, 1);
+    assert_eq!(tree.pll_input_freq(pll::PLL2), Ok(806_000_000));
+    tree.set_clock_select(clock_select::PLL2, 3);
+    assert_eq!(
+        tree.pll_input_freq(pll::PLL2),
+        Err(SysctlError::InvalidSelect(clock_select::PLL2, 3))
+    );
+    assert_eq!(
+        tree.freq(clock::I2S0),
+        Err(SysctlError::InvalidSelect(clock_select::PLL2, 3))
+    );
+    /* Clocks that don't depend on PLL2 still resolve */
+    assert_eq!(tree.freq(clock::SPI0), Ok(18_318_181));
+}
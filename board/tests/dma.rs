@@ -0,0 +1,59 @@
+//! DMA channel allocation against the fake registers
+use board::dma::{claim_channel_on, claim_on, is_claimed, DmaError};
+use board::regs::Fake;
+use board::sysctl::{dma_channel, dma_select, Sysctl};
+
+#[test]
+fn claim_drop_reclaim() {
+    let fake = Fake::new();
+    let sysctl = Sysctl::new(&fake);
+    let regs = fake.sysctl_regs();
+
+    let lcd = claim_on(&sysctl, dma_select::SSI0_TX_REQ).unwrap();
+    assert_eq!(lcd.channel(), dma_channel::CHANNEL0);
+    assert!(is_claimed(dma_channel::CHANNEL0));
+    assert_eq!(
+        regs.dma_sel0.read().dma_sel0().bits(),
+        dma_select::SSI0_TX_REQ as u8
+    );
+    assert_eq!(
+        claim_channel_on(&sysctl, dma_channel::CHANNEL0, dma_select::SSI1_TX_REQ).err(),
+        Some(DmaError::Claimed(dma_channel::CHANNEL0))
+    );
+
+    /* The next free channel */
+    let sd = claim_on(&sysctl, dma_select::SSI1_RX_REQ).unwrap();
+    assert_eq!(sd.channel(), dma_channel::CHANNEL1);
+    assert_eq!(
+        regs.dma_sel0.read().dma_sel1().bits(),
+        dma_select::SSI1_RX_REQ as u8
+    );
+
+    /* Dropping hands the channel back */
+    drop(lcd);
+    assert!(!is_claimed(dma_channel::CHANNEL0));
+    assert!(is_claimed(dma_channel::CHANNEL1));
+    let uart = claim_on(&sysctl, dma_select::UART1_TX_REQ).unwrap();
+    assert_eq!(uart.channel(), dma_channel::CHANNEL0);
+    assert_eq!(uart.select(), dma_select::UART1_TX_REQ);
+    assert_eq!(
+        regs.dma_sel0.read().dma_sel0().bits(),
+        dma_select::UART1_TX_REQ as u8
+    );
+
+    let last = claim_channel_on(&sysctl, dma_channel::CHANNEL5, dma_select::I2C0_RX_REQ).unwrap();
+    assert_eq!(last.channel(), dma_channel::CHANNEL5);
+    assert_eq!(
+        regs.dma_sel1.read().dma_sel5().bits(),
+        dma_select::I2C0_RX_REQ as u8
+    );
+    let rest: Vec<_> = (0..3)
+        .map(|_| claim_on(&sysctl, dma_select::SSI2_TX_REQ).unwrap())
+        .collect();
+    assert_eq!(
+        claim_on(&sysctl, dma_select::SSI2_TX_REQ).err(),
+        Some(DmaError::NoFreeChannel)
+    );
+    drop(rest);
+    assert!(!is_claimed(dma_channel::CHANNEL2));
+}
@@ -0,0 +1,174 @@
+//! FPIOA pad configuration against the fake register backend
+use board::def::io;
+use board::fpioa::{drive, function, pull, slew, Fpioa, PadConfig};
+use board::pads::Pads;
+use board::pins::{self, subsystem};
+use board::regs::Fake;
+use board::sysctl::Sysctl;
+
+fn io_bits(fake: &Fake, pad: io) -> u32 {
+    fake.fpioa_regs().io[pad as usize].read().bits()
+}
+
+/** Every pad, only ever routed on fake registers here */
+fn pads() -> Pads {
+    unsafe { Pads::steal() }
+}
+
+#[test]
+fn defaults_round_trip() {
+    let fake = Fake::new();
+    let fpioa = Fpioa::new(&fake);
+    let pads = pads();
+    let (mut io9, mut io10) = (pads.io9.unwrap(), pads.io10.unwrap());
+    for function in pins::PINS.iter().filter_map(|role| role.function) {
+        io9 = Fpioa::new(&fake).route(io9, function).release();
+        let bits = io_bits(&fake, io::IO9);
+        assert_eq!(fpioa.pad_config(io::IO9), PadConfig::default_for(function));
+        let config = PadConfig::default_for(function);
+        io10 = Fpioa::new(&fake)
+            .route_with(io10, function, &config)
+            .release();
+        assert_eq!(io_bits(&fake, io::IO10), bits, "{:?}", function);
+    }
+}
+
+#[test]
+fn pad_config_keeps_function() {
+    let fake = Fake::new();
+    let fpioa = Fpioa::new(&fake);
+    let default = PadConfig::default_for(function::SPI0_SCLK);
+    let mut wr = Fpioa::new(&fake).route(pads().lcd_wr.unwrap(), function::SPI0_SCLK);
+    wr.modify_pad_config(|config| PadConfig {
+        slew: slew::SLOW,
+        schmitt: true,
+        ..config
+    });
+    wr.modify_pad_config(|config| PadConfig {
+        drive: drive::DS7,
+        ..config
+    });
+    wr.set_pull(pull::DOWN);
+    assert_eq!(
+        io_bits(&fake, io::LCD_WR) & 0xff,
+        function::SPI0_SCLK as u32
+    );
+    assert_eq!(
+        fpioa.pad_config(io::LCD_WR),
+        PadConfig {
+            drive: drive::DS7,
+            pull: pull::DOWN,
+            slew: slew::SLOW,
+            schmitt: true,
+            ..default
+        }
+    );
+}
+
+#[test]
+fn every_field_has_its_own_bit() {
+    let base = PadConfig::from_bits(0);
+    let changes = [
+        PadConfig {
+            drive: drive::DS15,
+            ..base
+        },
+        PadConfig {
+            output_enable: true,
+            ..base
+        },
+        PadConfig {
+            output_enable_invert: true,
+            ..base
+        },
+        PadConfig {
+            output_select_oe: true,
+            ..base
+        },
+        PadConfig {
+            output_invert: true,
+            ..base
+        },
+        PadConfig {
+            pull: pull::UP,
+            ..base
+        },
+        PadConfig {
+            pull: pull::DOWN,
+            ..base
+        },
+        PadConfig {
+            slew: slew::SLOW,
+            ..base
+        },
+        PadConfig {
+            input_enable: true,
+            ..base
+        },
+        PadConfig {
+            input_enable_invert: true,
+            ..base
+        },
+        PadConfig {
+            input_invert: true,
+            ..base
+        },
+        PadConfig {
+            schmitt: true,
+            ..base
+        },
+        PadConfig {
+            tie: Some(false),
+            ..base
+        },
+        PadConfig {
+            tie: Some(true),
+            ..base
+        },
+    ];
+    let mut seen = 0;
+    for config in &changes {
+        let bits = config.bits();
+        assert_ne!(bits, 0, "{:?}", config);
+        assert_eq!(bits & 0xff, 0);
+        assert_eq!(PadConfig::from_bits(bits), *config);
+        seen |= bits;
+    }
+    assert_eq!(seen, 0x03fb_ff00);
+}
+
+#[test]
+fn readback_decodes_function_and_config() {
+    let fake = Fake::new();
+    let fpioa = Fpioa::new(&fake);
+    pins::apply_on(&Sysctl::new(&fake), &fpioa, pads(), subsystem::ALL).unwrap();
+    for role in pins::PINS {
+        let state = fpioa.pad_state(role.pad);
+        match role.function {
+            Some(function) => {
+                assert_eq!(state.function, function);
+                assert_eq!(state.config.pull, role.pull);
+            }
+            None => assert_eq!(state.function, function::JTAG_TCLK),
+        }
+    }
+    assert_eq!(fpioa.function_of(io::LCD_DC), function::GPIOHS2);
+    assert_eq!(function::from_u8(255), function::DEBUG31);
+}
+
+#[test]
+fn report_has_a_row_per_pad() {
+    let fake = Fake::new();
+    let fpioa = Fpioa::new(&fake);
+    Fpioa::new(&fake)
+        .route(pads().dvp_pclk.unwrap(), function::CMOS_PCLK)
+        .set_pull(pull::UP);
+    let mut report = String::new();
+    fpioa.report(&mut report).unwrap();
+    let rows: Vec<&str> = report.lines().collect();
+    assert_eq!(rows.len(), 1 + 48);
+    assert!(rows[0].starts_with("pad  name"));
+    assert!(rows[1].starts_with("0    JTAG_TCK   JTAG_TCLK      NONE  DS0    no "));
+    assert!(rows[48]
+        .starts_with("47   DVP_PCLK   CMOS_PCLK      UP    DS0    no   yes  -    yes  FAST  0"));
+}
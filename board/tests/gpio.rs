@@ -0,0 +1,88 @@
+//! GPIO driver against the fake register backend
+use board::gpio::{direction, edge, Gpio};
+use board::regs::{Fake, Registers};
+use embedded_hal::digital::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
+
+/* Byte offsets of the registers the PAC only lets us read or only write */
+const INTERRUPT_CLEAR: usize = 0x4c;
+const DATA_INPUT: usize = 0x50;
+
+/** Raw word of the fake GPIO block */
+fn word(fake: &Fake, offset: usize) -> *mut u32 {
+    unsafe { (fake.gpio() as *mut u32).add(offset / 4) }
+}
+
+#[test]
+fn direction_and_output() {
+    let fake = Fake::new();
+    let gpio = Gpio::new(&fake);
+    gpio.set_direction(0, direction::OUTPUT);
+    gpio.set_direction(2, direction::OUTPUT);
+    gpio.set_direction(0, direction::INPUT);
+    assert_eq!(fake.gpio_regs().direction.read().bits(), 0b100);
+
+    gpio.set_pin(1, true);
+    gpio.set_pin(7, true);
+    gpio.toggle_pin(1);
+    gpio.toggle_pin(3);
+    assert_eq!(fake.gpio_regs().data_output.read().bits(), 0b1000_1000);
+
+    gpio.set_pins(0b0000_1111, 0b1111_0101);
+    assert_eq!(fake.gpio_regs().data_output.read().bits(), 0b1000_0101);
+}
+
+#[test]
+fn input() {
+    let fake = Fake::new();
+    let gpio = Gpio::new(&fake);
+    unsafe { *word(&fake, DATA_INPUT) = 0b0100_0001 };
+    assert_eq!(gpio.get_pins(), 0b0100_0001);
+    assert!(gpio.get_pin(0));
+    assert!(!gpio.get_pin(1));
+    assert!(gpio.get_pin(6));
+}
+
+#[test]
+fn irq_modes() {
+    let fake = Fake::new();
+    let gpio = Gpio::new(&fake);
+    let regs = fake.gpio_regs();
+    let mode = |pin: u8| {
+        let bit = |bits: u32| bits >> pin & 1;
+        (
+            bit(regs.interrupt_enable.read().bits()),
+            bit(regs.interrupt_level.read().bits()),
+            bit(regs.interrupt_polarity.read().bits()),
+            bit(regs.interrupt_bothedge.read().bits()),
+        )
+    };
+    gpio.set_irq_mode(4, edge::RISING);
+    assert_eq!(mode(4), (1, 1, 1, 0));
+    gpio.set_irq_mode(5, edge::BOTH);
+    assert_eq!(mode(5), (1, 1, 0, 1));
+    gpio.set_irq_mode(4, edge::LOW);
+    assert_eq!(mode(4), (1, 0, 0, 0));
+    assert_eq!(mode(5), (1, 1, 0, 1));
+    gpio.set_irq_mode(5, edge::NONE);
+    assert_eq!(mode(5).0, 0);
+    assert_eq!(unsafe { *word(&fake, INTERRUPT_CLEAR) }, 1 << 5);
+}
+
+#[test]
+fn digital_traits() {
+    let fake = Fake::new();
+    let mut led = Gpio::new(&fake).output_pin(3, false);
+    assert_eq!(fake.gpio_regs().direction.read().bits(), 1 << 3);
+    assert_eq!(led.try_is_set_high(), Ok(false));
+    led.try_set_high().unwrap();
+    assert_eq!(fake.gpio_regs().data_output.read().bits(), 1 << 3);
+    assert_eq!(led.try_is_set_high(), Ok(true));
+    led.try_toggle().unwrap();
+    assert_eq!(led.try_is_set_low(), Ok(true));
+
+    let key = Gpio::new(&fake).input_pin(6);
+    unsafe { *word(&fake, DATA_INPUT) = 1 << 6 };
+    assert_eq!(key.try_is_high(), Ok(true));
+    unsafe { *word(&fake, DATA_INPUT) = 0 };
+    assert_eq!(key.try_is_low(), Ok(true));
+}
@@ -0,0 +1,86 @@
+//! GPIOHS driver against the fake register backend
+use board::gpiohs::{edge, Gpiohs};
+use board::regs::Fake;
+use embedded_hal::digital::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
+
+#[test]
+fn irq_modes() {
+    let fake = Fake::new();
+    let gpiohs = Gpiohs::new(&fake);
+    let regs = fake.gpiohs_regs();
+    let mode = |pin: u8| {
+        let bit = |bits: u32| bits >> pin & 1;
+        (
+            bit(regs.rise_ie.read().bits()),
+            bit(regs.fall_ie.read().bits()),
+            bit(regs.high_ie.read().bits()),
+            bit(regs.low_ie.read().bits()),
+        )
+    };
+    gpiohs.set_irq_mode(5, edge::BOTH);
+    for &(edge, expected) in &[
+        (edge::RISING, (1, 0, 0, 0)),
+        (edge::FALLING, (0, 1, 0, 0)),
+        (edge::BOTH, (1, 1, 0, 0)),
+        (edge::HIGH, (0, 0, 1, 0)),
+        (edge::LOW, (0, 0, 0, 1)),
+        (edge::NONE, (0, 0, 0, 0)),
+    ] {
+        gpiohs.set_irq_mode(6, edge);
+        assert_eq!(mode(6), expected, "{:?}", edge);
+        /* Pending interrupts of the pin are dropped with the old mode */
+        assert_eq!(regs.rise_ip.read().bits(), 1 << 6);
+        assert_eq!(regs.low_ip.read().bits(), 1 << 6);
+    }
+    /* Other pins keep their mode */
+    assert_eq!(mode(5), (1, 1, 0, 0));
+}
+
+#[test]
+fn pending_bits_clear_by_writing_one() {
+    let fake = Fake::new();
+    let gpiohs = Gpiohs::new(&fake);
+    let regs = fake.gpiohs_regs();
+    let pending = || {
+        [
+            regs.rise_ip.read().bits(),
+            regs.fall_ip.read().bits(),
+            regs.high_ip.read().bits(),
+            regs.low_ip.read().bits(),
+        ]
+    };
+    regs.rise_ip.write(|w| unsafe { w.bits(0b1010_0000) });
+    regs.fall_ip.write(|w| unsafe { w.bits(0b1000_0000) });
+    regs.high_ip.write(|w| unsafe { w.bits(0b0010_0000) });
+    regs.low_ip.write(|w| unsafe { w.bits(0) });
+
+    /*
+     * Only the bit of the pin is written, a read-modify-write would write back and so clear the
+     * pending bits of the other pins too
+     */
+    gpiohs.clear_irq(7);
+    assert_eq!(pending(), [1 << 7; 4]);
+    gpiohs.clear_irq(0);
+    assert_eq!(pending(), [1; 4]);
+}
+
+#[test]
+fn digital_traits() {
+    let fake = Fake::new();
+    let regs = fake.gpiohs_regs();
+    let mut cs = Gpiohs::new(&fake).output_pin(0, true);
+    assert_eq!(regs.output_en.read().bits(), 1);
+    assert_eq!(cs.try_is_set_high(), Ok(true));
+    cs.try_toggle().unwrap();
+    assert_eq!(regs.output_val.read().bits(), 0);
+    cs.try_set_high().unwrap();
+    assert_eq!(regs.output_val.read().bits(), 1);
+    assert_eq!(cs.try_is_set_low(), Ok(false));
+
+    let key = Gpiohs::new(&fake).input_pin(5);
+    assert_eq!(regs.input_en.read().bits(), 1 << 5);
+    regs.input_val.write(|w| unsafe { w.bits(1 << 5) });
+    assert_eq!(key.try_is_high(), Ok(true));
+    regs.input_val.write(|w| unsafe { w.bits(0) });
+    assert_eq!(key.try_is_low(), Ok(true));
+}
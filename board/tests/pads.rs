@@ -0,0 +1,49 @@
+//! Ownership of the pads
+use board::def::io;
+use board::fpioa::{function, Fpioa};
+use board::pads::{Pad, Pads, IO29};
+use board::pin_map::PinError;
+use board::pins::{self, subsystem};
+use board::regs::Fake;
+use board::sysctl::Sysctl;
+
+#[test]
+fn taken_once() {
+    {
+        let pads = Pads::take();
+        assert!(pads.is_some());
+        assert!(Pads::take().is_none());
+        assert!(Pads::take().is_none());
+    }
+    /* Going out of scope doesn't hand them out again */
+    assert!(Pads::take().is_none());
+    assert_eq!(<IO29 as Pad>::IO, io::IO29);
+}
+
+#[test]
+fn apply_hands_back_the_rest() {
+    let fake = Fake::new();
+    let (sysctl, fpioa) = (Sysctl::new(&fake), Fpioa::new(&fake));
+    /* Only the fake registers are routed, no one else uses the pads */
+    let mut pads = unsafe { Pads::steal() };
+    assert!(pads.has(io::IO29));
+    let cs = pads.io29.take().unwrap();
+
+    /* The SD card needs its chip select back */
+    assert_eq!(
+        pins::apply_on(&sysctl, &fpioa, pads, &[subsystem::SD]).err(),
+        Some(PinError::NotOwned(io::IO29))
+    );
+    assert_eq!(fpioa.function_of(io::IO26), function::JTAG_TCLK);
+
+    let pads = unsafe { Pads::steal() };
+    let rest = pins::apply_on(&sysctl, &fpioa, pads, &[subsystem::LCD]).unwrap();
+    for &pad in io::ALL {
+        let lcd = pins::role(pad).subsystem == subsystem::LCD;
+        assert_eq!(rest.has(pad), !lcd, "{:?}", pad);
+    }
+    assert_eq!(fpioa.function_of(io::LCD_WR), function::SPI0_SCLK);
+    let cs = Fpioa::new(&fake).route(cs, function::GPIOHS0);
+    assert_eq!(cs.io(), io::IO29);
+    assert_eq!(fpioa.function_of(io::IO29), function::GPIOHS0);
+}
@@ -0,0 +1,207 @@
+//! Conflict detection of `PinMap`
+use board::def::io;
+use board::fpioa::{function, pull, Fpioa};
+use board::pads::Pads;
+use board::pin_map::{Pin, PinError, PinMap};
+use board::pins;
+use board::regs::Fake;
+use board::sysctl::Sysctl;
+
+/** Checked at compile time */
+const SDCARD: PinMap = PinMap::new()
+    .assign(io::IO26, function::SPI1_D1)
+    .assign(io::IO27, function::SPI1_SCLK)
+    .assign(io::IO28, function::SPI1_D0)
+    .assign_pull(io::IO29, function::GPIOHS0, pull::UP)
+    .validate();
+
+#[test]
+fn valid_map_keeps_assignments() {
+    assert_eq!(SDCARD.check(), Ok(()));
+    assert_eq!(
+        SDCARD.get(io::IO29),
+        Some(Pin {
+            pad: io::IO29,
+            function: function::GPIOHS0,
+            pull: pull::UP,
+        })
+    );
+    assert_eq!(SDCARD.get(io::IO30), None);
+    assert_eq!(SDCARD.pad_of(function::SPI1_SCLK), Some(io::IO27));
+    assert_eq!(SDCARD.pad_of(function::SPI0_SCLK), None);
+    let pads: Vec<io> = SDCARD.pins().map(|pin| pin.pad).collect();
+    assert_eq!(pads, [io::IO26, io::IO27, io::IO28, io::IO29]);
+}
+
+#[test]
+fn two_functions_on_one_pad() {
+    let map = SDCARD.assign(io::IO27, function::SPI0_SCLK);
+    assert_eq!(
+        map.check(),
+        Err(PinError::PadInUse(
+            io::IO27,
+            function::SPI1_SCLK,
+            function::SPI0_SCLK
+        ))
+    );
+}
+
+#[test]
+fn one_function_on_two_pads() {
+    let map = SDCARD.assign(io::IO30, function::GPIOHS0);
+    assert_eq!(
+        map.check(),
+        Err(PinError::FunctionInUse(
+            function::GPIOHS0,
+            io::IO29,
+            io::IO30
+        ))
+    );
+}
+
+#[test]
+fn pull_conflicts() {
+    assert_eq!(
+        SDCARD.pull(io::IO29, pull::DOWN).check(),
+        Err(PinError::PullConflict(io::IO29, pull::UP, pull::DOWN))
+    );
+    assert_eq!(
+        SDCARD
+            .assign_pull(io::IO29, function::GPIOHS0, pull::NONE)
+            .check(),
+        Err(PinError::PullConflict(io::IO29, pull::UP, pull::NONE))
+    );
+    assert_eq!(
+        SDCARD.pull(io::IO30, pull::UP).check(),
+        Err(PinError::Unassigned(io::IO30))
+    );
+    assert_eq!(SDCARD.pull(io::IO29, pull::UP).check(), Ok(()));
+    assert_eq!(
+        SDCARD
+            .pull(io::IO26, pull::UP)
+            .get(io::IO26)
+            .map(|pin| pin.pull),
+        Some(pull::UP)
+    );
+}
+
+#[test]
+fn first_error_is_kept() {
+    let map = SDCARD
+        .assign(io::IO30, function::GPIOHS0)
+        .assign(io::IO27, function::SPI0_SCLK);
+    assert_eq!(
+        map.check(),
+        Err(PinError::FunctionInUse(
+            function::GPIOHS0,
+            io::IO29,
+            io::IO30
+        ))
+    );
+}
+
+#[test]
+fn merge() {
+    let cs = PinMap::new().assign_pull(io::IO29, function::GPIOHS0, pull::UP);
+    assert_eq!(PinMap::new().merge(&SDCARD).merge(&cs), SDCARD);
+    let other = PinMap::new().assign(io::IO29, function::GPIOHS1);
+    assert_eq!(
+        SDCARD.merge(&other).check(),
+        Err(PinError::PadInUse(
+            io::IO29,
+            function::GPIOHS0,
+            function::GPIOHS1
+        ))
+    );
+    let broken = other.assign(io::IO29, function::GPIOHS2);
+    assert_eq!(PinMap::new().merge(&broken).check(), broken.check());
+}
+
+#[test]
+fn corgidude_table_is_by_pad() {
+    assert_eq!(pins::PINS.len(), 48);
+    for (pad, role) in pins::PINS.iter().enumerate() {
+        assert_eq!(role.pad as usize, pad);
+    }
+    assert_eq!(pins::CORGIDUDE.check(), Ok(()));
+    assert_eq!(pins::subsystem::SD.map(), pins::map(&[pins::subsystem::SD]));
+    assert_eq!(
+        pins::subsystem::SD.map().merge(&SDCARD).check(),
+        Err(PinError::PullConflict(io::IO29, pull::DOWN, pull::UP))
+    );
+    assert_eq!(pins::subsystem::FREE.map(), PinMap::new());
+}
+
+#[test]
+fn keys_match_the_table() {
+    for &(pad, pin) in pins::KEYS {
+        let role = pins::role(pad);
+        assert_eq!(role.subsystem, pins::subsystem::KEYS);
+        assert_eq!(role.function, Some(function::gpiohs(pin)));
+    }
+}
+
+#[test]
+fn probes_mirror_the_lcd() {
+    use pins::subsystem;
+    assert!(!subsystem::ALL.contains(&subsystem::PROBES));
+
+    let map = pins::map(&[subsystem::LCD, subsystem::PROBES]);
+    assert_eq!(map.check(), Ok(()));
+    for &(pad, function, pull) in pins::PROBE_PADS {
+        assert_eq!(
+            map.get(pad),
+            Some(Pin {
+                pad,
+                function,
+                pull
+            })
+        );
+        assert!(map.is_mirror(pad));
+    }
+    /* The LCD keeps its pads */
+    assert_eq!(map.pad_of(function::SPI0_SCLK), Some(io::LCD_WR));
+    assert_eq!(map.pad_of(function::GPIOHS2), Some(io::LCD_DC));
+    assert!(!map.is_mirror(io::LCD_WR));
+    assert_eq!(PinMap::new().merge(&map), map);
+
+    /* Mirrors don't make room for a second regular assignment */
+    assert_eq!(
+        map.assign(io::IO30, function::SPI0_SCLK).check(),
+        Err(PinError::FunctionInUse(
+            function::SPI0_SCLK,
+            io::LCD_WR,
+            io::IO30
+        ))
+    );
+    /* The probes sit on the LED and key pads */
+    assert_eq!(
+        pins::map(&[subsystem::LEDS, subsystem::PROBES]).check(),
+        Err(PinError::PadInUse(
+            io::IO14,
+            function::GPIO5,
+            function::SPI0_D1
+        ))
+    );
+}
+
+#[test]
+fn lcd_and_camera_get_the_spi0_data_pins() {
+    use pins::subsystem;
+    for &(subsystem, enabled) in &[
+        (subsystem::LCD, true),
+        (subsystem::DVP, true),
+        (subsystem::SD, false),
+    ] {
+        let fake = Fake::new();
+        /* Only the fake registers are routed, no one else uses the pads */
+        let pads = unsafe { Pads::steal() };
+        pins::apply_on(&Sysctl::new(&fake), &Fpioa::new(&fake), pads, &[subsystem]).unwrap();
+        assert_eq!(
+            fake.sysctl_regs().misc.read().spi_dvp_data_enable().bit(),
+            enabled,
+            "{:?}",
+            subsystem
+        );
+    }
+}
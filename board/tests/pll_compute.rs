@@ -0,0 +1,310 @@
+//! Check `pll_compute` against a brute-force search over every r/f/od setting
+//!
+//! `compute_params` walks a pruned table of r/od factorizations. That is exhaustive for
+//! oscillator inputs (up to 27 MHz), but can miss the best setting for the much faster inputs
+//! PLL2 gets from PLL0 or PLL1. There only the settings are checked against the limits, and
+//! `compute_params_with` has to match the search.
+use board::pll_compute::{self, vco_preference, Candidate, Params, Preference};
+
+const VCO_MIN: u64 = 340_000_000;
+const VCO_MAX: u64 = 1_750_000_000;
+const DIV_MIN: u64 = 13_300_000;
+const DIV_MAX: u64 = 1_750_000_000;
+
+/** Errors closer than this are equal, `compute_params` drops the lower 16 fraction bits */
+const RESOLUTION: f64 = 1.0 / 65536.0;
+
+const OSCILLATORS: &[u32] = &[
+    13_300_000, 16_000_000, 20_000_000, 25_000_000, 26_000_000, 27_000_000,
+];
+
+const PLL_INPUTS: &[u32] = &[
+    33_333_333,
+    48_000_000,
+    100_000_000,
+    393_216_000,
+    806_000_000,
+    1_750_000_000,
+];
+
+/** Frequencies that tend to show up in practice */
+const OUTPUTS: &[u32] = &[
+    22_579_200,
+    24_576_000,
+    45_158_400,
+    49_152_000,
+    98_304_000,
+    300_000_000,
+    390_000_000,
+    400_000_000,
+    416_000_000,
+    806_000_000,
+    1_000_000_000,
+];
+
+#[derive(Copy, Clone, Debug)]
+struct Setting {
+    r: u64,
+    f: u64,
+    od: u64,
+}
+
+impl Setting {
+    fn from(params: &Params) -> Setting {
+        Setting {
+            r: u64::from(params.clkr) + 1,
+            f: u64::from(params.clkf) + 1,
+            od: u64::from(params.clkod) + 1,
+        }
+    }
+
+    fn in_spec(&self, freq_in: u32) -> bool {
+        let freq_in = u64::from(freq_in);
+        let div = freq_in / self.r;
+        let vco = freq_in * self.f / self.r;
+        div >= DIV_MIN && div <= DIV_MAX && vco >= VCO_MIN && vco <= VCO_MAX
+    }
+
+    /** Relative error of the output frequency */
+    fn error(&self, freq_in: u32, freq_out: u32) -> f64 {
+        let actual = f64::from(freq_in) * self.f as f64 / (self.r * self.od) as f64;
+        (actual - f64::from(freq_out)).abs() / f64::from(freq_out)
+    }
+}
+
+/** Every in-spec setting */
+fn brute_force(freq_in: u32) -> Vec<Setting> {
+    let mut settings = Vec::new();
+    for r in 1..=16 {
+        for f in 1..=64 {
+            for od in 1..=16 {
+                let setting = Setting { r, f, od };
+                if setting.in_spec(freq_in) {
+                    settings.push(setting);
+                }
+            }
+        }
+    }
+    settings
+}
+
+/** Smallest error of any in-spec setting */
+fn best_error(settings: &[Setting], freq_in: u32, freq_out: u32) -> Option<f64> {
+    settings
+        .iter()
+        .map(|setting| setting.error(freq_in, freq_out))
+        .fold(None, |best: Option<f64>, error| {
+            Some(best.map_or(error, |best| best.min(error)))
+        })
+}
+
+/** Call `f` for a sweep over the whole output range and the common frequencies */
+fn sweep<F: FnMut(u32)>(mut f: F) {
+    let mut freq_out = pll_compute::OUT_MIN;
+    while freq_out <= pll_compute::OUT_MAX {
+        f(freq_out);
+        freq_out += 3_333_331;
+    }
+    f(pll_compute::OUT_MAX);
+    for &freq_out in OUTPUTS {
+        f(freq_out);
+    }
+}
+
+#[test]
+fn compute_params_is_in_spec() {
+    for &freq_in in OSCILLATORS.iter().chain(PLL_INPUTS) {
+        sweep(|freq_out| {
+            if let Some(params) = pll_compute::compute_params(freq_in, freq_out) {
+                assert!(
+                    Setting::from(&params).in_spec(freq_in),
+                    "{} Hz -> {} Hz: {:?} is out of spec",
+                    freq_in,
+                    freq_out,
+                    params
+                );
+                assert_eq!(params.bwadj, params.clkf);
+            }
+        });
+    }
+}
+
+#[test]
+fn compute_params_matches_brute_force() {
+    for &freq_in in OSCILLATORS {
+        let settings = brute_force(freq_in);
+        sweep(|freq_out| {
+            /* compute_params refuses to multiply by more than 64 */
+            if u64::from(freq_out) > u64::from(freq_in) * 64 {
+                return;
+            }
+            let best = best_error(&settings, freq_in, freq_out)
+                .expect("an oscillator can always reach the output range");
+            let params = match pll_compute::compute_params(freq_in, freq_out) {
+                Some(params) => params,
+                None => {
+                    /* Near OUT_MIN it may give up when only coarse settings exist */
+                    assert!(
+                        best > 0.01,
+                        "{} Hz -> {} Hz: no parameters, best error is {}",
+                        freq_in,
+                        freq_out,
+                        best
+                    );
+                    return;
+                }
+            };
+            let error = Setting::from(&params).error(freq_in, freq_out);
+            assert!(
+                error <= best + RESOLUTION,
+                "{} Hz -> {} Hz: {:?} has error {}, best is {}",
+                freq_in,
+                freq_out,
+                params,
+                error,
+                best
+            );
+        });
+    }
+}
+
+#[test]
+fn compute_params_rejects_unreachable_frequencies() {
+    assert_eq!(
+        pll_compute::compute_params(26_000_000, pll_compute::OUT_MIN - 1),
+        None
+    );
+    assert_eq!(
+        pll_compute::compute_params(26_000_000, pll_compute::OUT_MAX + 1),
+        None
+    );
+    assert_eq!(pll_compute::compute_params(13_299_999, 400_000_000), None);
+    assert_eq!(
+        pll_compute::compute_params(1_750_000_001, 400_000_000),
+        None
+    );
+}
+
+#[test]
+fn candidates_match_brute_force() {
+    for &freq_in in OSCILLATORS.iter().chain(PLL_INPUTS) {
+        let settings = brute_force(freq_in);
+        for &freq_out in OUTPUTS {
+            let candidates: Vec<Candidate> = pll_compute::candidates(freq_in, freq_out).collect();
+            assert_eq!(candidates.len(), settings.len(), "{} Hz", freq_in);
+            for candidate in &candidates {
+                let setting = Setting::from(&candidate.params);
+                assert!(setting.in_spec(freq_in), "{:?}", candidate);
+                assert_eq!(u64::from(candidate.vco), {
+                    let vco = u64::from(freq_in) * setting.f;
+                    (vco + setting.r / 2) / setting.r
+                });
+                assert_eq!(
+                    candidate.exact,
+                    u64::from(freq_in) * setting.f == u64::from(freq_out) * setting.r * setting.od
+                );
+                let ppm = setting.error(freq_in, freq_out) * 1e6;
+                assert!(
+                    (f64::from(candidate.error_ppm) - ppm).abs() <= 0.5,
+                    "{:?}",
+                    candidate
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn compute_params_with_closest_matches_brute_force() {
+    for &freq_in in OSCILLATORS.iter().chain(PLL_INPUTS) {
+        let settings = brute_force(freq_in);
+        for &freq_out in OUTPUTS {
+            let best = best_error(&settings, freq_in, freq_out);
+            let found = pll_compute::compute_params_with(freq_in, freq_out, &Preference::CLOSEST)
+                .map(|candidate| Setting::from(&candidate.params).error(freq_in, freq_out));
+            match (best, found) {
+                (Some(best), Some(found)) => assert!(
+                    found <= best + 1e-12,
+                    "{} Hz -> {} Hz: error {}, best is {}",
+                    freq_in,
+                    freq_out,
+                    found,
+                    best
+                ),
+                (None, None) => {}
+                _ => panic!(
+                    "{} Hz -> {} Hz: {:?} vs {:?}",
+                    freq_in, freq_out, found, best
+                ),
+            }
+        }
+    }
+}
+
+#[test]
+fn compute_params_with_low_jitter_picks_fastest_exact_vco() {
+    for &freq_in in OSCILLATORS.iter().chain(PLL_INPUTS) {
+        let settings = brute_force(freq_in);
+        for &freq_out in OUTPUTS {
+            let fastest = settings
+                .iter()
+                .filter(|s| u64::from(freq_in) * s.f == u64::from(freq_out) * s.r * s.od)
+                .map(|s| (u64::from(freq_in) * s.f + s.r / 2) / s.r)
+                .max();
+            let found =
+                pll_compute::compute_params_with(freq_in, freq_out, &Preference::LOW_JITTER);
+            assert_eq!(found.map(|c| u64::from(c.vco)), fastest);
+            if let Some(candidate) = found {
+                assert!(candidate.exact);
+                assert_eq!(candidate.freq, freq_out);
+            }
+        }
+    }
+}
+
+#[test]
+fn compute_params_with_unbounded_vco_preference_keeps_the_closest() {
+    for &vco in &[vco_preference::MAX, vco_preference::MIN] {
+        let preference = Preference {
+            vco,
+            ..Preference::CLOSEST
+        };
+        for &freq_in in OSCILLATORS.iter().chain(PLL_INPUTS) {
+            for &freq_out in OUTPUTS {
+                let closest =
+                    pll_compute::compute_params_with(freq_in, freq_out, &Preference::CLOSEST);
+                let found = pll_compute::compute_params_with(freq_in, freq_out, &preference);
+                assert_eq!(
+                    found.map(|c| (c.exact, c.error_ppm)),
+                    closest.map(|c| (c.exact, c.error_ppm)),
+                    "{} Hz -> {} Hz, {:?}",
+                    freq_in,
+                    freq_out,
+                    vco
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn compute_params_with_bounded_vco_preference_stays_within_the_bound() {
+    let preference = Preference {
+        exact: false,
+        max_error_ppm: 1000,
+        vco: vco_preference::MIN,
+    };
+    for &freq_in in OSCILLATORS.iter().chain(PLL_INPUTS) {
+        for &freq_out in OUTPUTS {
+            let slowest = pll_compute::candidates(freq_in, freq_out)
+                .filter(|c| c.error_ppm <= 1000)
+                .map(|c| c.vco)
+                .min();
+            let found = pll_compute::compute_params_with(freq_in, freq_out, &preference);
+            assert_eq!(found.map(|c| c.vco), slowest);
+            if let Some(candidate) = found {
+                assert!(candidate.error_ppm <= 1000);
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! IO bank voltages checked against the pads that need them
+use board::def::io;
+use board::power::{bank_of, PowerDomains, PowerError, CORGIDUDE};
+use board::sysctl::{io_power_mode, power_bank};
+
+#[test]
+fn banks_of_six() {
+    assert_eq!(bank_of(io::JTAG_TCK), power_bank::BANK0);
+    assert_eq!(bank_of(io::WIFI_TX), power_bank::BANK1);
+    assert_eq!(bank_of(io::DVP_SDA), power_bank::BANK6);
+    assert_eq!(bank_of(io::DVP_RST), power_bank::BANK7);
+    assert_eq!(bank_of(io::DVP_PCLK), power_bank::BANK7);
+}
+
+#[test]
+fn corgidude_passes() {
+    assert_eq!(CORGIDUDE.check(), Ok(()));
+    assert_eq!(CORGIDUDE.mode(power_bank::BANK6), io_power_mode::V18);
+    assert_eq!(CORGIDUDE.mode(power_bank::BANK5), io_power_mode::V33);
+}
+
+#[test]
+fn dvp_on_a_3v3_bank() {
+    let domains = CORGIDUDE.with(power_bank::BANK7, io_power_mode::V33);
+    let err = domains.check().unwrap_err();
+    assert_eq!(
+        err,
+        PowerError::WrongVoltage(io::DVP_RST, power_bank::BANK7, io_power_mode::V18)
+    );
+    assert_eq!(format!("{}", err), "DVP_RST on BANK7 needs V18");
+
+    /* The first pad in `PAD_VOLTAGES` order is reported */
+    let all_3v3 = PowerDomains::new([io_power_mode::V33; 8]);
+    assert_eq!(
+        all_3v3.check(),
+        Err(PowerError::WrongVoltage(
+            io::DVP_SDA,
+            power_bank::BANK6,
+            io_power_mode::V18
+        ))
+    );
+}
+
+#[test]
+fn wifi_on_a_1v8_bank() {
+    let domains = CORGIDUDE.with(power_bank::BANK1, io_power_mode::V18);
+    assert_eq!(
+        domains.check(),
+        Err(PowerError::WrongVoltage(
+            io::WIFI_TX,
+            power_bank::BANK1,
+            io_power_mode::V33
+        ))
+    );
+    /* Only the listed pads are checked */
+    let pads = [(io::IO26, io_power_mode::V33)];
+    assert_eq!(domains.check_pads(&pads), Ok(()));
+}
@@ -0,0 +1,132 @@
+//! Clock plans of the power/performance profiles
+use board::clock_tree::ClockTree;
+use board::profile::profile;
+use board::sysctl::{clock, clock_select, pll, threshold};
+
+const IN0: u32 = 26_000_000;
+
+fn achieved(profile: profile, base: &ClockTree) -> Vec<(clock, u32)> {
+    profile
+        .plan(base)
+        .unwrap()
+        .entries()
+        .map(|entry| (entry.clock, entry.achieved))
+        .collect()
+}
+
+#[test]
+fn performance() {
+    let base = ClockTree::new(IN0);
+    let plan = profile::PERFORMANCE.plan(&base).unwrap();
+    assert_eq!(
+        achieved(profile::PERFORMANCE, &base),
+        vec![
+            (clock::PLL0, 806_000_000),
+            (clock::CPU, 403_000_000),
+            (clock::PLL1, 398_666_666),
+            (clock::AI, 398_666_666),
+        ]
+    );
+    assert_eq!(plan.tree().get_clock_select(clock_select::ACLK), 1);
+    assert_eq!(plan.tree().get_threshold(threshold::AI), 0);
+    assert_eq!(plan.max_error_ppm(), 7500);
+    assert!(plan.tree().uses_pll(clock::AI, pll::PLL1));
+}
+
+#[test]
+fn balanced() {
+    let base = ClockTree::new(IN0);
+    let plan = profile::BALANCED.plan(&base).unwrap();
+    assert_eq!(
+        achieved(profile::BALANCED, &base),
+        vec![(clock::PLL0, 806_000_000), (clock::CPU, 201_500_000)]
+    );
+    /* 806 MHz / (2 << 1) */
+    assert_eq!(plan.tree().get_clock_select(clock_select::ACLK), 1);
+    assert_eq!(plan.tree().get_threshold(threshold::ACLK), 1);
+    /* PLL1 is left alone */
+    assert_eq!(plan.tree().get_pll(pll::PLL1), base.get_pll(pll::PLL1));
+}
+
+#[test]
+fn low_power() {
+    let performance = *profile::PERFORMANCE
+        .plan(&ClockTree::new(IN0))
+        .unwrap()
+        .tree();
+    let plan = profile::LOW_POWER.plan(&performance).unwrap();
+    assert_eq!(
+        achieved(profile::LOW_POWER, &performance),
+        vec![(clock::CPU, IN0)]
+    );
+    let tree = plan.tree();
+    assert_eq!(tree.get_clock_select(clock_select::ACLK), 0);
+    /* PLL0 is not pinned, but keeps its setting for the SPI and I2C dividers behind it */
+    assert_eq!(tree.get_pll(pll::PLL0), performance.get_pll(pll::PLL0));
+    assert_eq!(tree.freq(clock::SPI0), performance.freq(clock::SPI0));
+    assert!(!tree.uses_pll(clock::CPU, pll::PLL0));
+    assert!(tree.uses_pll(clock::SPI0, pll::PLL0));
+    assert!(!tree.uses_pll(clock::DVP, pll::PLL0));
+}
+
+/** Gates left on after switching through `profiles`, starting with everything on */
+fn gates_after(profiles: &[profile]) -> Vec<clock> {
+    let mut on = vec![clock::PLL1, clock::AI, clock::DVP];
+    for profile in profiles {
+        let settings = profile.settings();
+        for clock in settings.enable {
+            if !on.contains(clock) {
+                on.push(*clock);
+            }
+        }
+        on.retain(|clock| !settings.disable.contains(clock));
+    }
+    on
+}
+
+#[test]
+fn round_trip() {
+    assert_eq!(gates_after(&[profile::LOW_POWER]), vec![]);
+    for &back in &[profile::PERFORMANCE, profile::BALANCED] {
+        assert!(gates_after(&[profile::LOW_POWER, back]).contains(&clock::DVP));
+    }
+    assert_eq!(
+        gates_after(&[profile::LOW_POWER, profile::PERFORMANCE]),
+        gates_after(&[profile::PERFORMANCE])
+    );
+}
+
+/* Whatever one profile turns off, every other profile turns back on or off too */
+#[test]
+fn gates_restored() {
+    for from in profile::ALL {
+        for to in profile::ALL {
+            for clock in from.settings().disable {
+                let settings = to.settings();
+                assert!(
+                    settings.enable.contains(clock) || settings.disable.contains(clock),
+                    "{:?} leaves {:?} as {:?} left it",
+                    to,
+                    clock,
+                    from
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn pll_users() {
+    let mut tree = ClockTree::new(IN0);
+    assert!(!tree.uses_pll(clock::TIMER0, pll::PLL0));
+    tree.set_clock_select(clock_select::TIMER0, 1);
+    assert!(tree.uses_pll(clock::TIMER0, pll::PLL0));
+
+    /* I2S runs from PLL2, which may run from PLL1 */
+    assert!(tree.uses_pll(clock::I2S0, pll::PLL2));
+    assert!(!tree.uses_pll(clock::I2S0, pll::PLL1));
+    tree.set_clock_select(clock_select::PLL2, 2);
+    assert!(tree.uses_pll(clock::I2S0, pll::PLL1));
+    assert!(!tree.uses_pll(clock::I2S0, pll::PLL0));
+    assert!(!tree.uses_pll(clock::WDT0, pll::PLL0));
+}
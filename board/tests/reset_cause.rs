@@ -0,0 +1,67 @@
+//! Reset cause against the fake registers
+//!
+//! `last_reset_cause` caches the first cause it reads for the whole program, so this file has a
+//! test binary of its own.
+use board::regs::Fake;
+use board::sysctl::{self, reset_cause, Sysctl};
+
+/** Record resets in the status register as the hardware would, with the clear bit low */
+fn set_status(fake: &Fake, wdt0: bool, wdt1: bool, soft: bool, pin: bool) {
+    fake.sysctl_regs().reset_status.write(|w| {
+        w.wdt0_reset_sts()
+            .bit(wdt0)
+            .wdt1_reset_sts()
+            .bit(wdt1)
+            .soft_reset_sts()
+            .bit(soft)
+            .pin_reset_sts()
+            .bit(pin)
+    });
+}
+
+#[test]
+fn priority() {
+    let fake = Fake::new();
+    let sysctl = Sysctl::new(&fake);
+    assert_eq!(sysctl.reset_cause(), reset_cause::HARD);
+
+    /* Each cause wins over all that come after it */
+    let order = [
+        reset_cause::WDT0,
+        reset_cause::WDT1,
+        reset_cause::SOFT,
+        reset_cause::PIN,
+        reset_cause::HARD,
+    ];
+    for (i, &expected) in order.iter().enumerate() {
+        let set = |cause| order[i..].contains(&cause);
+        set_status(
+            &fake,
+            set(reset_cause::WDT0),
+            set(reset_cause::WDT1),
+            set(reset_cause::SOFT),
+            set(reset_cause::PIN),
+        );
+        assert_eq!(sysctl.reset_cause(), expected);
+    }
+}
+
+#[test]
+fn cached() {
+    let fake = Fake::new();
+    let sysctl = Sysctl::new(&fake);
+    let regs = fake.sysctl_regs();
+
+    set_status(&fake, false, true, false, true);
+    assert_eq!(sysctl::last_reset_cause_on(&sysctl), reset_cause::WDT1);
+    assert!(regs.reset_status.read().reset_sts_clr().bit());
+
+    /* Later resets recorded since are not reported, nor is the status cleared again */
+    set_status(&fake, true, false, false, false);
+    assert_eq!(sysctl::last_reset_cause_on(&sysctl), reset_cause::WDT1);
+    assert!(!regs.reset_status.read().reset_sts_clr().bit());
+    assert_eq!(
+        sysctl::last_reset_cause_on(&Sysctl::new(&Fake::new())),
+        reset_cause::WDT1
+    );
+}
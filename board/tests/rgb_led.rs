@@ -0,0 +1,149 @@
+//! RGB LED colors, gamma and animations, and the PWM duty registers behind them
+use board::def::io;
+use board::fpioa::{function, Fpioa};
+use board::pads::Pads;
+use board::pwm::{timer, Pwm, DUTY_MAX};
+use board::regs::{Fake, Registers};
+use board::rgb_led::{duties, gamma, Animation, Color, RgbLed};
+
+#[test]
+fn rgb24() {
+    let color = Color::from_rgb24(0x12_34_56);
+    assert_eq!(color, Color::new(0x12, 0x34, 0x56));
+    assert_eq!(color.rgb24(), 0x12_34_56);
+    assert_eq!(Color::from_rgb24(0xff_00_ff), Color::MAGENTA);
+}
+
+#[test]
+fn gamma_and_brightness() {
+    assert_eq!(gamma(0), 0);
+    assert_eq!(gamma(255), DUTY_MAX);
+    /* Half the intensity is well under half the duty */
+    assert!(gamma(128) < DUTY_MAX / 4);
+    for level in 1..=255u8 {
+        assert!(gamma(level) >= gamma(level - 1));
+    }
+
+    assert_eq!(duties(Color::RED, 255), [DUTY_MAX, 0, 0]);
+    assert_eq!(duties(Color::WHITE, 0), [0, 0, 0]);
+    /* Brightness scales the color before gamma */
+    assert_eq!(duties(Color::WHITE, 128), [gamma(128); 3]);
+    assert_eq!(Color::new(200, 100, 0).scale(128), Color::new(100, 50, 0));
+}
+
+#[test]
+fn fade() {
+    let fade = Animation::Fade {
+        from: Color::BLACK,
+        to: Color::new(200, 100, 50),
+        duration_ms: 1000,
+    };
+    assert_eq!(fade.color_at(0), Color::BLACK);
+    assert_eq!(fade.color_at(500), Color::new(100, 50, 25));
+    assert_eq!(fade.color_at(1000), Color::new(200, 100, 50));
+    assert_eq!(fade.color_at(5000), Color::new(200, 100, 50));
+    assert!(!fade.is_done(999));
+    assert!(fade.is_done(1000));
+
+    let down = Animation::Fade {
+        from: Color::WHITE,
+        to: Color::BLACK,
+        duration_ms: 100,
+    };
+    assert_eq!(down.color_at(25), Color::new(192, 192, 192));
+}
+
+#[test]
+fn breathe() {
+    let breathe = Animation::Breathe {
+        color: Color::BLUE,
+        period_ms: 2000,
+    };
+    assert_eq!(breathe.color_at(0), Color::BLACK);
+    assert_eq!(breathe.color_at(1000), Color::BLUE);
+    assert_eq!(breathe.color_at(500), breathe.color_at(1500));
+    assert_eq!(breathe.color_at(2000), Color::BLACK);
+    assert_eq!(breathe.color_at(2500), breathe.color_at(500));
+    assert!(!breathe.is_done(u32::MAX));
+}
+
+#[test]
+fn blink() {
+    let blink = Animation::Blink {
+        color: Color::RED,
+        on_ms: 100,
+        off_ms: 300,
+    };
+    assert_eq!(blink.color_at(0), Color::RED);
+    assert_eq!(blink.color_at(99), Color::RED);
+    assert_eq!(blink.color_at(100), Color::BLACK);
+    assert_eq!(blink.color_at(399), Color::BLACK);
+    assert_eq!(blink.color_at(400), Color::RED);
+}
+
+#[test]
+fn timer_outputs() {
+    assert_eq!(timer::TIMER0.output(0), function::TIMER0_TOGGLE1);
+    assert_eq!(timer::TIMER1.output(2), function::TIMER1_TOGGLE3);
+    assert_eq!(timer::TIMER2.output(3), function::TIMER2_TOGGLE4);
+}
+
+/* Byte offset of the load count of TIMER1 channel 2, written raw to give it a period */
+const LOAD_COUNT_2: usize = 2 * 0x14;
+
+#[test]
+fn duty_keeps_period() {
+    let fake = Fake::new();
+    let pwm = Pwm::new(&fake);
+    let regs = fake.timer_regs(1);
+    unsafe { *(fake.timer(1) as *mut u32).add(LOAD_COUNT_2 / 4) = 1000 };
+
+    pwm.set_duty(timer::TIMER1, 2, DUTY_MAX / 5);
+    assert_eq!(regs.channel[2].load_count.read().bits(), 800);
+    assert_eq!(regs.load_count2[2].read().bits(), 200);
+
+    /* Fully on keeps one clock of low time for the timer to run on */
+    pwm.set_duty(timer::TIMER1, 2, DUTY_MAX);
+    assert_eq!(regs.channel[2].load_count.read().bits(), 1);
+    assert_eq!(regs.load_count2[2].read().bits(), 999);
+
+    /* Going back down does not stretch the period */
+    pwm.set_duty(timer::TIMER1, 2, DUTY_MAX / 5);
+    assert_eq!(regs.channel[2].load_count.read().bits(), 800);
+    assert_eq!(regs.load_count2[2].read().bits(), 200);
+
+    pwm.set_enable(timer::TIMER1, 2, true);
+    assert!(pwm.is_enabled(timer::TIMER1, 2));
+    assert_eq!(regs.channel[2].load_count.read().bits(), 800);
+    pwm.set_enable(timer::TIMER1, 2, false);
+    assert!(!pwm.is_enabled(timer::TIMER1, 2));
+    assert!(!pwm.is_enabled(timer::TIMER1, 1));
+}
+
+#[test]
+fn led_pads_carry_the_timer() {
+    let fake = Fake::new();
+    /* Only the fake registers are routed, no one else uses the pads */
+    let pads = unsafe { Pads::steal() };
+    let _led = RgbLed::new(
+        &fake,
+        timer::TIMER1,
+        pads.rgb_led_r.unwrap(),
+        pads.rgb_led_g.unwrap(),
+        pads.rgb_led_b.unwrap(),
+    )
+    .unwrap();
+    let fpioa = Fpioa::new(&fake);
+    for &(pad, function) in &[
+        (io::RGB_LED_R, function::TIMER1_TOGGLE1),
+        (io::RGB_LED_G, function::TIMER1_TOGGLE2),
+        (io::RGB_LED_B, function::TIMER1_TOGGLE3),
+    ] {
+        let state = fpioa.pad_state(pad);
+        assert_eq!(state.function, function);
+        assert!(state.config.output_invert, "{:?}", pad);
+    }
+    let pwm = Pwm::new(&fake);
+    assert!((0..3).all(|channel| pwm.is_enabled(timer::TIMER1, channel)));
+    assert!(!pwm.is_enabled(timer::TIMER1, 3));
+}
@@ -0,0 +1,63 @@
+//! Blink codes of the status faults
+use std::panic;
+
+use board::rgb_led::{Animation, Color};
+use board::status::{self, fault, HALT_COLOR};
+
+#[test]
+fn codes_are_distinct() {
+    for (i, a) in fault::ALL.iter().enumerate() {
+        for b in &fault::ALL[i + 1..] {
+            assert_ne!(a.blinks(), b.blinks(), "{:?} and {:?}", a, b);
+        }
+    }
+    assert_eq!(fault::PANIC.blinks(), 1);
+    assert_eq!(
+        format!("{}", fault::CAMERA),
+        format!("camera not found (code {})", fault::CAMERA.blinks())
+    );
+}
+
+#[test]
+fn count() {
+    let code = Animation::Count {
+        color: Color::RED,
+        count: 3,
+        on_ms: 100,
+        off_ms: 200,
+        pause_ms: 1000,
+    };
+    let lit = |t| code.color_at(t) == Color::RED;
+    assert!(lit(0) && lit(99) && !lit(100) && !lit(299));
+    assert!(lit(300) && lit(600) && lit(699) && !lit(700));
+    /* Dark through the pause, then over again */
+    assert!(!lit(900) && !lit(1899));
+    assert!(lit(1900) && !lit(2000) && lit(2200));
+    assert!(!code.is_done(u32::MAX));
+}
+
+#[test]
+fn fault_code() {
+    let code = fault::SD_CARD.code(HALT_COLOR);
+    /* Count the ends of blinks in one period, 250 ms on and off each and a 1.5 s pause */
+    let period = u32::from(fault::SD_CARD.blinks()) * 500 + 1500;
+    let blinks = (0..period)
+        .filter(|&t| code.color_at(t) == HALT_COLOR && code.color_at(t + 1) != HALT_COLOR)
+        .count();
+    assert_eq!(blinks, usize::from(fault::SD_CARD.blinks()));
+    assert_eq!(code.color_at(period), HALT_COLOR);
+    assert_eq!(code.color_at(period - 1), Color::BLACK);
+}
+
+#[test]
+fn record_and_check() {
+    assert_eq!(status::check(Ok::<u32, &str>(7), fault::DMA), 7);
+    assert_eq!(status::recorded(), None);
+
+    let result = panic::catch_unwind(|| status::check(Err::<(), &str>("busy"), fault::LCD));
+    assert!(result.is_err());
+    assert_eq!(status::recorded(), Some(fault::LCD));
+
+    status::record(fault::CAMERA);
+    assert_eq!(status::recorded(), Some(fault::CAMERA));
+}
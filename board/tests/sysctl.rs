@@ -0,0 +1,327 @@
+//! SYSCTL driver against the fake register backend
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use board::regs::{Fake, Registers};
+use board::sysctl::{
+    self, clock, clock_select, io_power_mode, pll, power_bank, reset, threshold, ClockChange,
+    Sysctl, SysctlError,
+};
+use k210_hal::pac;
+
+/* Byte offset of the lock register, which the hardware sets */
+const PLL_LOCK: usize = 0x18;
+
+/** Make all three PLLs report lock */
+fn lock_plls(fake: &Fake) {
+    unsafe { *(fake.sysctl() as *mut u32).add(PLL_LOCK / 4) = 0x0001_0303 };
+}
+
+#[test]
+fn pll_freq() {
+    let fake = Fake::new();
+    lock_plls(&fake);
+    let sysctl = Sysctl::new(&fake);
+    assert_eq!(sysctl.pll_set_freq(pll::PLL0, 800_000_000), Ok(806_000_000));
+
+    let pll0 = fake.sysctl_regs().pll0.read();
+    assert_eq!(
+        (
+            pll0.clkr().bits(),
+            pll0.clkf().bits(),
+            pll0.clkod().bits(),
+            pll0.bwadj().bits()
+        ),
+        (0, 30, 0, 30)
+    );
+    assert!(pll0.pwrd().bit());
+    assert!(!pll0.reset().bit());
+    assert!(pll0.out_en().bit());
+    /* The CPU is back on PLL0 */
+    assert!(fake.sysctl_regs().clk_sel0.read().aclk_sel().bit());
+    assert_eq!(sysctl.clock_get_freq(clock::CPU), Ok(403_000_000));
+
+    /* PLL2 keeps its input, here PLL0 */
+    sysctl
+        .clock_set_clock_select(clock_select::PLL2, 1)
+        .unwrap();
+    assert_eq!(sysctl.pll_set_freq(pll::PLL2, 45_158_400), Ok(45_090_909));
+    assert_eq!(fake.sysctl_regs().pll2.read().ckin_sel().bits(), 1);
+    assert!(fake.sysctl_regs().pll2.read().out_en().bit());
+
+    assert_eq!(
+        sysctl.pll_set_freq(pll::PLL1, 3_000_000_000),
+        Err(SysctlError::PllOutOfRange(pll::PLL1, 3_000_000_000))
+    );
+}
+
+#[test]
+fn pll_lock_timeout() {
+    let fake = Fake::new();
+    let sysctl = Sysctl::new(&fake).with_lock_budget(100);
+    sysctl
+        .clock_set_clock_select(clock_select::ACLK, 1)
+        .unwrap();
+    assert_eq!(
+        sysctl.pll_set_freq(pll::PLL0, 800_000_000),
+        Err(SysctlError::LockTimeout(pll::PLL0))
+    );
+    /* The budget bounds the wait */
+    assert!(fake.cycles() < 200);
+    /* Output stays off and the CPU on IN0 */
+    assert!(!fake.sysctl_regs().pll0.read().out_en().bit());
+    assert!(!fake.sysctl_regs().clk_sel0.read().aclk_sel().bit());
+    assert_eq!(sysctl.clock_get_freq(clock::CPU), Ok(26_000_000));
+}
+
+#[test]
+fn power_mode() {
+    let fake = Fake::new();
+    let sysctl = Sysctl::new(&fake);
+    sysctl.set_power_mode(power_bank::BANK6, io_power_mode::V18);
+    sysctl.set_power_mode(power_bank::BANK7, io_power_mode::V18);
+    sysctl.set_power_mode(power_bank::BANK0, io_power_mode::V18);
+    sysctl.set_power_mode(power_bank::BANK0, io_power_mode::V33);
+    assert_eq!(fake.sysctl_regs().power_sel.read().bits(), 0b1100_0000);
+    assert_eq!(sysctl.get_power_mode(power_bank::BANK6), io_power_mode::V18);
+    assert_eq!(sysctl.get_power_mode(power_bank::BANK0), io_power_mode::V33);
+}
+
+#[test]
+fn clock_select_and_threshold() {
+    let fake = Fake::new();
+    let sysctl = Sysctl::new(&fake);
+    let regs = fake.sysctl_regs();
+
+    sysctl
+        .clock_set_clock_select(clock_select::TIMER1, 1)
+        .unwrap();
+    sysctl
+        .clock_set_clock_select(clock_select::PLL2, 2)
+        .unwrap();
+    assert!(regs.clk_sel0.read().timer1_clk_sel().bit());
+    assert!(!regs.clk_sel0.read().timer0_clk_sel().bit());
+    assert_eq!(regs.pll2.read().ckin_sel().bits(), 2);
+    assert_eq!(
+        sysctl.clock_set_clock_select(clock_select::PLL2, 3),
+        Err(SysctlError::InvalidSelect(clock_select::PLL2, 3))
+    );
+    assert_eq!(sysctl.clock_get_clock_select(clock_select::PLL2), 2);
+
+    sysctl.clock_set_threshold(threshold::ACLK, 3).unwrap();
+    sysctl.clock_set_threshold(threshold::APB1, 5).unwrap();
+    sysctl.clock_set_threshold(threshold::SPI0, 21).unwrap();
+    sysctl.clock_set_threshold(threshold::I2S1, 0x1234).unwrap();
+    assert_eq!(regs.clk_sel0.read().aclk_divider_sel().bits(), 3);
+    assert_eq!(regs.clk_sel0.read().apb1_clk_sel().bits(), 5);
+    assert_eq!(regs.clk_th1.read().spi0_clk().bits(), 21);
+    assert_eq!(regs.clk_th3.read().i2s1_clk().bits(), 0x1234);
+    assert_eq!(
+        sysctl.clock_set_threshold(threshold::APB0, 8),
+        Err(SysctlError::InvalidThreshold(threshold::APB0, 8))
+    );
+    for &which in threshold::ALL {
+        let expected = match which {
+            threshold::ACLK => 3,
+            threshold::APB1 => 5,
+            threshold::SPI0 => 21,
+            threshold::I2S1 => 0x1234,
+            _ => 0,
+        };
+        assert_eq!(sysctl.clock_get_threshold(which), expected, "{:?}", which);
+    }
+}
+
+/** Fake that records the asserted peripheral resets while the driver waits */
+struct Probe {
+    fake: Fake,
+    held: Cell<u32>,
+}
+
+impl Registers for Probe {
+    fn sysctl(&self) -> *const pac::sysctl::RegisterBlock {
+        self.fake.sysctl()
+    }
+
+    fn fpioa(&self) -> *const pac::fpioa::RegisterBlock {
+        self.fake.fpioa()
+    }
+
+    fn gpio(&self) -> *const pac::gpio::RegisterBlock {
+        self.fake.gpio()
+    }
+
+    fn gpiohs(&self) -> *const pac::gpiohs::RegisterBlock {
+        self.fake.gpiohs()
+    }
+
+    fn timer(&self, n: usize) -> *const pac::timer0::RegisterBlock {
+        self.fake.timer(n)
+    }
+
+    fn usleep(&self, _n: usize) {
+        self.held
+            .set(self.fake.sysctl_regs().peri_reset.read().bits());
+    }
+
+    fn cycles(&self) -> usize {
+        self.fake.cycles()
+    }
+}
+
+#[test]
+fn resets() {
+    let probe = Probe {
+        fake: Fake::new(),
+        held: Cell::new(0),
+    };
+    let sysctl = Sysctl::new(&probe);
+    let regs = probe.fake.sysctl_regs();
+
+    sysctl.reset(reset::SPI0);
+    assert!(!sysctl.reset_is_asserted(reset::SPI0));
+    assert_eq!(regs.peri_reset.read().bits(), 0);
+    /* Held while waiting */
+    let held = probe.held.get();
+    assert_eq!(held.count_ones(), 1);
+    regs.peri_reset.write(|w| unsafe { w.bits(held) });
+    assert!(sysctl.reset_is_asserted(reset::SPI0));
+    assert!(regs.peri_reset.read().spi0_reset().bit());
+    regs.peri_reset.write(|w| unsafe { w.bits(0) });
+
+    sysctl.reset_group(&[reset::DMA, reset::SPI0, reset::DVP]);
+    assert_eq!(regs.peri_reset.read().bits(), 0);
+    regs.peri_reset
+        .write(|w| unsafe { w.bits(probe.held.get()) });
+    for &which in reset::ALL {
+        let expected = which == reset::DMA || which == reset::SPI0 || which == reset::DVP;
+        assert_eq!(sysctl.reset_is_asserted(which), expected, "{:?}", which);
+    }
+}
+
+static CHANGES: AtomicUsize = AtomicUsize::new(0);
+static SPI0_CHANGED: AtomicBool = AtomicBool::new(false);
+
+fn count_changes(change: &ClockChange) {
+    CHANGES.fetch_add(1, Ordering::Relaxed);
+    SPI0_CHANGED.store(change.affects(clock::SPI0), Ordering::Relaxed);
+}
+
+#[test]
+fn listeners() {
+    let fake = Fake::new();
+    let sysctl = Sysctl::new(&fake);
+    let changes = || CHANGES.load(Ordering::Relaxed);
+    let subscription = sysctl::clock_subscribe(count_changes).unwrap();
+
+    /* Nested changes are reported once, by the outermost */
+    sysctl.clock_change(|| {
+        sysctl.clock_set_threshold(threshold::SPI0, 3).unwrap();
+        sysctl
+            .clock_change(|| sysctl.clock_set_threshold(threshold::APB0, 1))
+            .unwrap();
+        assert_eq!(changes(), 0);
+    });
+    assert_eq!(changes(), 1);
+    assert!(SPI0_CHANGED.load(Ordering::Relaxed));
+
+    /* Writing the same values is no change */
+    sysctl
+        .clock_change(|| sysctl.clock_set_threshold(threshold::SPI0, 3))
+        .unwrap();
+    assert_eq!(changes(), 1);
+
+    /* A change that fails halfway is still reported */
+    let result = sysctl.clock_change(|| {
+        sysctl.clock_set_threshold(threshold::APB1, 2)?;
+        sysctl.clock_set_threshold(threshold::APB1, 8)
+    });
+    assert_eq!(
+        result,
+        Err(SysctlError::InvalidThreshold(threshold::APB1, 8))
+    );
+    assert_eq!(changes(), 2);
+    assert!(!SPI0_CHANGED.load(Ordering::Relaxed));
+
+    sysctl::clock_unsubscribe(subscription);
+    sysctl
+        .clock_change(|| sysctl.clock_set_threshold(threshold::SPI0, 4))
+        .unwrap();
+    assert_eq!(changes(), 2);
+}
+
+#[test]
+fn pll_power_down() {
+    let fake = Fake::new();
+    lock_plls(&fake);
+    let sysctl = Sysctl::new(&fake);
+    sysctl.pll_set_freq(pll::PLL0, 800_000_000).unwrap();
+    sysctl.pll_set_freq(pll::PLL1, 400_000_000).unwrap();
+    sysctl.clock_enable(clock::CPU);
+    sysctl.clock_enable(clock::AI);
+
+    /* The CPU runs from PLL0 and the KPU from PLL1 */
+    assert!(sysctl.pll_in_use(pll::PLL0));
+    assert!(sysctl.pll_in_use(pll::PLL1));
+    sysctl.sysctl_clock_disable(clock::AI);
+    assert!(!sysctl.pll_in_use(pll::PLL1));
+    sysctl
+        .clock_set_clock_select(clock_select::ACLK, 0)
+        .unwrap();
+    assert!(!sysctl.pll_in_use(pll::PLL0));
+    sysctl.clock_enable(clock::SPI0);
+    assert!(sysctl.pll_in_use(pll::PLL0));
+
+    sysctl.pll_power_down(pll::PLL1);
+    let pll1 = fake.sysctl_regs().pll1.read();
+    assert!(!pll1.out_en().bit());
+    assert!(pll1.reset().bit());
+    assert!(!pll1.pwrd().bit());
+    assert!(!sysctl.pll_is_powered(pll::PLL1));
+    assert!(sysctl.pll_is_powered(pll::PLL0));
+
+    /* Setting a frequency brings it back */
+    sysctl.pll_set_freq(pll::PLL1, 400_000_000).unwrap();
+    assert!(sysctl.pll_is_powered(pll::PLL1));
+    assert!(!fake.sysctl_regs().pll1.read().reset().bit());
+}
+
+#[test]
+fn report_has_a_row_per_clock() {
+    let fake = Fake::new();
+    lock_plls(&fake);
+    let sysctl = Sysctl::new(&fake);
+    sysctl.pll_set_freq(pll::PLL0, 800_000_000).unwrap();
+    sysctl.clock_enable(clock::SPI0);
+    sysctl.clock_set_threshold(threshold::SPI0, 21).unwrap();
+    sysctl.set_power_mode(power_bank::BANK6, io_power_mode::V18);
+    /* Not a valid PLL2 input, its frequency can't be worked out */
+    fake.sysctl_regs()
+        .pll2
+        .modify(|_, w| unsafe { w.ckin_sel().bits(3) });
+
+    let mut report = String::new();
+    sysctl.report(&mut report).unwrap();
+    let rows: Vec<&str> = report.lines().collect();
+    let plls = 1;
+    let clocks = plls + 3 + 2;
+    let thresholds = clocks + clock::ALL.len() + 2;
+    let selects = thresholds + threshold::ALL.len() + 2;
+    let banks = selects + clock_select::ALL.len() + 2;
+    assert_eq!(rows.len(), banks + power_bank::ALL.len());
+
+    assert!(rows[0].starts_with("PLL    freq (Hz)"));
+    assert_eq!(rows[plls], "PLL0   806000000     on     yes   on");
+    assert_eq!(
+        rows[plls + 2],
+        "PLL2   (invalid clock select 3 for PLL2) off    yes   off"
+    );
+    assert!(rows[clocks - 1].starts_with("clock  freq (Hz)"));
+    assert_eq!(rows[clocks + 3], "CPU    403000000     off");
+    assert_eq!(rows[clocks + 15], "SPI0   18318181      on");
+    assert!(rows[thresholds - 1].starts_with("threshold"));
+    assert_eq!(rows[thresholds + 9], "SPI0       21     255");
+    assert_eq!(rows[selects + 4], "ACLK          1");
+    assert_eq!(rows[banks + 6], "BANK6  1.8V");
+    assert_eq!(rows[banks + 7], "BANK7  3.3V");
+}
@@ -1,46 +1,54 @@
 #![no_std]
 #![no_main]
 
-use board::def::io;
-use board::fpioa::{self, function};
-use board::gpio::direction;
-use k210_hal::pac::GPIO;
+use board::pads::Pads;
+use board::pwm::timer;
+use board::regs::Mmio;
+use board::rgb_led::{Animation, Color, RgbLed};
+use board::sleep::now_ms;
 use riscv_rt::entry;
 
-fn fpioa_init() {
-    fpioa::set_function(io::RGB_LED_R, function::GPIO0);
-    fpioa::set_function(io::RGB_LED_G, function::GPIO1);
-    fpioa::set_function(io::RGB_LED_B, function::GPIO2);
-}
-
-fn gpio_init() {
-    unsafe {
-        let ptr = GPIO::ptr();
-        (*ptr).direction.write(|w| {
-            w.pin0()
-                .variant(direction::OUTPUT)
-                .pin1()
-                .variant(direction::OUTPUT)
-                .pin2()
-                .variant(direction::OUTPUT)
-        });
-    }
-}
-
-fn light_up() {
-    unsafe {
-        let ptr = GPIO::ptr();
-        (*ptr)
-            .data_output
-            .write(|w| w.pin0().clear_bit().pin1().clear_bit().pin2().clear_bit())
-    }
-}
+/** What the LED shows, each for `SHOW_MS` */
+const SHOW: &[Animation] = &[
+    Animation::Breathe {
+        color: Color::CYAN,
+        period_ms: 3000,
+    },
+    Animation::Fade {
+        from: Color::RED,
+        to: Color::BLUE,
+        duration_ms: 4000,
+    },
+    Animation::Blink {
+        color: Color::ORANGE,
+        on_ms: 200,
+        off_ms: 800,
+    },
+];
+const SHOW_MS: u32 = 6000;
 
 #[entry]
 fn main() -> ! {
-    fpioa_init();
-    gpio_init();
-    light_up();
+    let pads = Pads::take().unwrap();
+    let mut led = RgbLed::new(
+        Mmio,
+        timer::TIMER0,
+        pads.rgb_led_r.unwrap(),
+        pads.rgb_led_g.unwrap(),
+        pads.rgb_led_b.unwrap(),
+    )
+    .unwrap();
 
-    loop {}
+    let mut shown = 0;
+    let mut since = now_ms();
+    led.animate(SHOW[shown], since);
+    loop {
+        let now = now_ms();
+        if now.wrapping_sub(since) >= SHOW_MS {
+            shown = (shown + 1) % SHOW.len();
+            since = now;
+            led.animate(SHOW[shown], now);
+        }
+        led.update(now);
+    }
 }
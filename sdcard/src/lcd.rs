@@ -1,3 +1,6 @@
+use board::baud;
+use board::dma::DmaChannel;
+use board::sysctl::dma_channel;
 use k210_hal::clock::Clocks;
 use k210_hal::dmac::{Dmac, DmacChannel};
 use k210_hal::gpiohs::GpiohsAccess;
@@ -6,7 +9,7 @@ use k210_hal::prelude::*;
 use k210_hal::sleep::usleep;
 use k210_hal::spi::{Spi, Spi01};
 
-const CLOCK_RATE: u32 = 12_000_000;
+pub const CLOCK_RATE: u32 = 12_000_000;
 
 #[repr(u8)]
 #[allow(dead_code)]
@@ -114,6 +117,8 @@ pub enum Direction {
 
 pub struct Lcd<SPI> {
     dmac: Dmac,
+    /** Keeps `channel` claimed for as long as the LCD exists */
+    _dma: DmaChannel,
     channel: DmacChannel,
     spi: Spi<SPI>,
     cs_num: u8,
@@ -124,14 +129,23 @@ pub struct Lcd<SPI> {
 impl<SPI: Spi01> Lcd<SPI> {
     pub fn new(
         dmac: Dmac,
-        channel: DmacChannel,
+        dma: DmaChannel,
         spi: Spi<SPI>,
         cs_num: u8,
         dc_gpio: u32,
         rs_gpio: u32,
     ) -> Self {
+        let channel = match dma.channel() {
+            dma_channel::CHANNEL0 => DmacChannel::Channel0,
+            dma_channel::CHANNEL1 => DmacChannel::Channel1,
+            dma_channel::CHANNEL2 => DmacChannel::Channel2,
+            dma_channel::CHANNEL3 => DmacChannel::Channel3,
+            dma_channel::CHANNEL4 => DmacChannel::Channel4,
+            dma_channel::CHANNEL5 => DmacChannel::Channel5,
+        };
         Self {
             dmac,
+            _dma: dma,
             channel,
             spi,
             cs_num,
@@ -178,6 +192,7 @@ impl<SPI: Spi01> Lcd<SPI> {
             Aitm::AS_FRAME_FORMAT,
             Tmod::TRANS,
         );
+        baud::sync_spi0_rate();
         self.spi.set_slave_select(Some(self.cs_num));
         self.spi.try_send(cmd as u32).unwrap();
     }
@@ -196,6 +211,7 @@ impl<SPI: Spi01> Lcd<SPI> {
             Aitm::AS_FRAME_FORMAT,
             Tmod::TRANS,
         );
+        baud::sync_spi0_rate();
         self.spi.set_slave_select(Some(self.cs_num));
         self.spi
             .send_data_dma(&mut self.dmac, self.channel, buf)
@@ -279,6 +295,7 @@ impl<SPI: Spi01> Lcd<SPI> {
             Aitm::AS_FRAME_FORMAT,
             Tmod::TRANS,
         );
+        baud::sync_spi0_rate();
         self.spi.set_slave_select(Some(self.cs_num));
         self.spi
             .send_data_dma(&mut self.dmac, self.channel, data)
@@ -1,8 +1,19 @@
 #![no_std]
 #![no_main]
 
+use board::button::{self, key};
+use board::gpiohs::GpiohsPin;
+use board::pads::Pads;
+use board::pins::{self, subsystem};
+use board::pwm::timer;
+use board::regs::Mmio;
+use board::rgb_led::RgbLed;
+use board::status::{self, fault, Status};
+use board::sysctl::dma_select;
+use board::{baud, dma, plic, power, sleep};
+use core::sync::atomic::{AtomicBool, Ordering};
 use embedded_sdmmc::{Controller, VolumeIdx};
-use k210_hal::dmac::{DmacChannel, DmacExt};
+use k210_hal::dmac::DmacExt;
 use k210_hal::dvp::DvpExt;
 use k210_hal::prelude::*;
 use k210_hal::rtc::RtcExt;
@@ -12,7 +23,6 @@ use k210_hal::{dvp, pac, spi};
 use riscv_rt::entry;
 use spi::SpiExt;
 
-mod init;
 mod lcd;
 mod ov2640;
 mod panic;
@@ -42,6 +52,13 @@ static mut FRAME: ScreenRAM = ScreenRAM {
     image: [COLOR; DISP_PIXELS / 2],
 };
 
+/** Set from the key interrupt, the main loop reports the keys */
+static KEYS_CHANGED: AtomicBool = AtomicBool::new(false);
+
+fn on_key(_pin: u8) {
+    KEYS_CHANGED.store(true, Ordering::Relaxed);
+}
+
 #[entry]
 fn main() -> ! {
     let p = pac::Peripherals::take().unwrap();
@@ -55,10 +72,29 @@ fn main() -> ! {
     let serial = p.UARTHS.configure((115_200 as u32).bps(), &clock);
     let (mut tx, _) = serial.split();
     let mut stdout = Stdout(&mut tx);
-
-    /* Correct the FPIOA routing */
-    let fpioa = p.FPIOA.split(&mut sysctl.apb0);
-    init::io(fpioa);
+    status::check(baud::keep_uarths_baud(115_200), fault::CLOCK);
+
+    /* Correct the FPIOA routing, the pin table keeps the pads it routes */
+    let pads = Pads::take().unwrap();
+    let subsystems = [
+        subsystem::DVP,
+        subsystem::LCD,
+        subsystem::SD,
+        subsystem::KEYS,
+    ];
+    let pads = status::check(pins::apply(pads, &subsystems), fault::PINS);
+    status::check(power::CORGIDUDE.apply(), fault::POWER);
+
+    /* Show on the RGB LED how things are going */
+    let led = RgbLed::new(
+        Mmio,
+        timer::TIMER0,
+        pads.rgb_led_r.unwrap(),
+        pads.rgb_led_g.unwrap(),
+        pads.rgb_led_b.unwrap(),
+    );
+    let led = status::check(led, fault::CLOCK);
+    let mut status = Status::new(led, sleep::now_ms());
 
     /* Configure DVP periperals */
     let dvp = p.DVP.constrain();
@@ -66,7 +102,7 @@ fn main() -> ! {
     let (mid, pid) = ov2640::read_id(&dvp);
     if mid != 0x7fa2 || pid != 0x2642 {
         writeln!(stdout, "[dvp] manufacturer and product id mismatched").unwrap();
-        panic!()
+        status::fail(fault::CAMERA)
     }
 
     writeln!(stdout, "[dvp] setting xclk rate").unwrap();
@@ -97,34 +133,52 @@ fn main() -> ! {
     writeln!(stdout, "[lcd] locking SPI0").unwrap();
     let spi0 = p.SPI0.constrain(&mut sysctl.apb2);
 
+    writeln!(stdout, "[lcd] claiming a DMA channel").unwrap();
+    let lcd_dma = status::check(dma::claim(dma_select::SSI0_TX_REQ), fault::DMA);
+
     writeln!(stdout, "[lcd] creating lcd instance").unwrap();
-    let mut lcd = lcd::Lcd::new(dmac, DmacChannel::Channel0, spi0, 3, 2, 3);
+    let mut lcd = lcd::Lcd::new(dmac, lcd_dma, spi0, 3, 2, 3);
 
     writeln!(stdout, "[lcd] flushing initial config").unwrap();
     lcd.init(&clock);
+    status::check(baud::keep_spi0_rate(lcd::CLOCK_RATE), fault::LCD);
+
+    /* All clocks are set up, dump them for debugging */
+    board::sysctl::report(&mut stdout).unwrap();
 
     writeln!(stdout, "[lcd] clearing the screen to {:04x}", &COLOR).unwrap();
     lcd.set_image(unsafe { &FRAME.image });
 
-    /* Configuring SD Card interface */
-    let spi1 = p.SPI1.constrain(&mut sysctl.apb2);
-    let gpio0 = p.GPIOHS.split().gpiohs0.into_output();
-    let mut sdcard = sdcard::SdCard::new(spi1, gpio0);
-    sdcard.init(&clock);
-
     /* Configuring real-time clock */
     let mut rtc = p.RTC.constrain(&mut sysctl.apb1);
     rtc.init(&clock);
     rtc.timer_set(2021, 3, 21, 20, 47, 00, &clock).unwrap();
     let rtc = rtc_source::RtcSource::new(rtc);
 
-    let mut sd = Controller::new(sdcard, rtc);
-
-    let volume = sd.get_volume(VolumeIdx(0)).unwrap();
-    let root = sd.open_root_dir(&volume).unwrap();
-    let central = sd.open_dir(&volume, &root, "1");
-
-    writeln!(stdout, "{:?}", &central).unwrap();
+    /* Configuring SD Card interface, the camera keeps streaming without a card */
+    let spi1 = p.SPI1.constrain(&mut sysctl.apb2);
+    let cs = GpiohsPin::output(0, true);
+    let mut sdcard = sdcard::SdCard::new(spi1, cs);
+    match sdcard.init(&clock) {
+        Ok(()) => {
+            let mut sd = Controller::new(sdcard, rtc);
+            match sd.get_volume(VolumeIdx(0)) {
+                Ok(volume) => {
+                    let root = sd.open_root_dir(&volume).unwrap();
+                    let central = sd.open_dir(&volume, &root, "1");
+                    writeln!(stdout, "{:?}", &central).unwrap();
+                }
+                Err(err) => {
+                    writeln!(stdout, "[sd] no volume: {:?}", err).unwrap();
+                    status.report(fault::SD_CARD, sleep::now_ms());
+                }
+            }
+        }
+        Err(err) => {
+            writeln!(stdout, "[sd] init failed: {:?}", err).unwrap();
+            status.report(fault::SD_CARD, sleep::now_ms());
+        }
+    }
 
     // let mut file = sd
     //     .open_file_in_dir(&mut volume, &root, "test.txt", Mode::ReadWriteCreate)
@@ -136,8 +190,18 @@ fn main() -> ! {
 
     // writeln!(stdout, "{:?}", buffer).unwrap();
 
+    /* Report key changes instead of polling the keys */
+    button::wake_on_keys(1, on_key);
+    plic::enable_interrupts();
+
     loop {
         dvp.get_image();
         lcd.set_image(unsafe { &FRAME.image });
+        status.update(sleep::now_ms());
+        if KEYS_CHANGED.swap(false, Ordering::Relaxed) {
+            for &key in key::ALL {
+                writeln!(stdout, "[keys] {:?} down: {}", key, key.is_down()).unwrap();
+            }
+        }
     }
 }
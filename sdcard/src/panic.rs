@@ -1,10 +1,10 @@
+use board::status::{self, fault};
 use core::panic::PanicInfo;
-use core::sync::atomic::{self, Ordering};
 use k210_hal::pac::Peripherals;
 use k210_hal::prelude::*;
 use k210_hal::stdout::Stdout;
 
-/** Send panic messages to UARTHS at 115200 baud */
+/** Send panic messages to UARTHS at 115200 baud, then blink the fault on the RGB LED */
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     let p = unsafe { Peripherals::steal() };
@@ -12,11 +12,7 @@ fn panic(info: &PanicInfo) -> ! {
     let serial = p.UARTHS.configure((115_200 as u32).bps(), &clocks);
     let (mut tx, _) = serial.split();
     let mut stdout = Stdout(&mut tx);
-    writeln!(stdout, "{}", info).unwrap();
+    let _ = writeln!(stdout, "{}", info);
 
-    loop {
-        // add some side effect to prevent this from turning into a UDF instruction
-        // see rust-lang/rust#28728 for details
-        atomic::compiler_fence(Ordering::SeqCst)
-    }
+    status::halt(status::recorded().unwrap_or(fault::PANIC))
 }
@@ -1,9 +1,10 @@
 #![allow(dead_code)]
+use board::gpiohs::GpiohsPin;
 use core::cell::RefCell;
 use core::result::Result;
+use embedded_hal::digital::OutputPin;
 use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx};
 use k210_hal::clock::Clocks;
-use k210_hal::gpiohs::{Floating, Gpiohs0, Output};
 use k210_hal::prelude::*;
 use k210_hal::sleep::usleep;
 use k210_hal::spi::{Spi, Spi01, *};
@@ -31,12 +32,19 @@ pub enum Cmd {
 
 pub struct SdCard<SPI> {
     spi: RefCell<Spi<SPI>>,
-    cs: RefCell<Gpiohs0<Output<Floating>>>,
+    cs: RefCell<GpiohsPin>,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum SdCardError {
-    InitFailed,
+    /** No idle response to CMD0, no card or not in SPI mode */
+    GoIdleFailed,
+    /** CMD8 rejected, a card older than SD 2.0 */
+    IfCondFailed,
+    /** CMD8 did not echo the 0x1AA check pattern */
+    CheckPatternMismatch,
+    /** A standard capacity card, which addresses bytes instead of blocks */
+    NotBlockAddressed,
     ReadCsdFailed,
     ReadDataFailed,
     CrcError,
@@ -45,14 +53,14 @@ pub enum SdCardError {
 }
 
 impl<SPI: Spi01> SdCard<SPI> {
-    pub fn new(spi: Spi<SPI>, cs: Gpiohs0<Output<Floating>>) -> SdCard<SPI> {
+    pub fn new(spi: Spi<SPI>, cs: GpiohsPin) -> SdCard<SPI> {
         SdCard {
             spi: RefCell::new(spi),
             cs: RefCell::new(cs),
         }
     }
 
-    pub fn init(&mut self, clocks: &Clocks) {
+    pub fn init(&mut self, clocks: &Clocks) -> Result<(), SdCardError> {
         self.spi.borrow_mut().set_clk_rate(Hertz(200000), &clocks);
         self.spi.borrow_mut().configure(
             WorkMode::MODE0,
@@ -77,15 +85,18 @@ impl<SPI: Spi01> SdCard<SPI> {
 
         self.send_command(Cmd::GoIdleState, 0x00, 0x95);
         if self.read_response() != 0x01 {
-            panic!("[sd] Error: Unknown card (CMD0 failed).");
+            /* Unknown card, CMD0 failed */
+            return Err(SdCardError::GoIdleFailed);
         }
 
         self.send_command(Cmd::SendIfCond, 0x01AA, 0x87);
         if self.read_response() != 0x01 {
-            panic!("[sd] Error: Unknown card (CMD8 failed).");
+            /* Unknown card, CMD8 failed */
+            return Err(SdCardError::IfCondFailed);
         }
         if self.read_trailing_data() & 0xffffff != 0x1aa {
-            panic!("[sd] Error: 0x1AA pattern check bit mismatched.");
+            /* 0x1AA pattern check bits mismatched */
+            return Err(SdCardError::CheckPatternMismatch);
         }
 
         let mut word = 0x01;
@@ -102,12 +113,14 @@ impl<SPI: Spi01> SdCard<SPI> {
         let ccs = (data >> 30) & 0x1;
 
         if ccs == 0 {
-            panic!("[sd] Error: Not using block address");
+            /* Not using block addresses */
+            return Err(SdCardError::NotBlockAddressed);
         }
 
         self.spi
             .borrow_mut()
             .set_clk_rate(Hertz(10_000_000), &clocks);
+        Ok(())
     }
 
     pub fn send_command(&self, cmd: Cmd, arg: u32, crc: u8) {
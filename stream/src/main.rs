@@ -1,7 +1,12 @@
 #![no_std]
 #![no_main]
 
-use k210_hal::dmac::{DmacChannel, DmacExt};
+use board::pads::Pads;
+use board::pins::{self, subsystem};
+use board::status::{self, fault};
+use board::sysctl::dma_select;
+use board::{baud, dma, power};
+use k210_hal::dmac::DmacExt;
 use k210_hal::dvp::DvpExt;
 use k210_hal::prelude::*;
 use k210_hal::stdout::Stdout;
@@ -9,10 +14,8 @@ use k210_hal::{dvp, pac, spi};
 use riscv_rt::entry;
 use spi::SpiExt;
 
-mod init;
 mod lcd;
 mod ov2640;
-mod panic;
 
 const DISP_PIXELS: usize = 320 * 240;
 const COLOR: u32 = 0x00;
@@ -44,10 +47,15 @@ fn main() -> ! {
     let serial = p.UARTHS.configure((115_200 as u32).bps(), &clock);
     let (mut tx, _) = serial.split();
     let mut stdout = Stdout(&mut tx);
+    status::check(baud::keep_uarths_baud(115_200), fault::CLOCK);
 
-    // Setup FPIOA
-    let fpioa = p.FPIOA.split(&mut sysctl.apb0);
-    init::io(fpioa);
+    // Setup FPIOA, the pin table owns the pads from here on
+    let pads = Pads::take().unwrap();
+    status::check(
+        pins::apply(pads, &[subsystem::DVP, subsystem::LCD, subsystem::PROBES]),
+        fault::PINS,
+    );
+    status::check(power::CORGIDUDE.apply(), fault::POWER);
 
     // Init DVP
     let dvp = p.DVP.constrain();
@@ -59,7 +67,7 @@ fn main() -> ! {
 
     if mid != 0x7fa2 || pid != 0x2642 {
         writeln!(stdout, "[dvp] manufacturer and product id mismatched").unwrap();
-        panic!()
+        status::fail(fault::CAMERA)
     }
 
     writeln!(stdout, "[dvp] setting xclk rate").unwrap();
@@ -89,11 +97,15 @@ fn main() -> ! {
     writeln!(stdout, "[lcd] locking SPI0").unwrap();
     let spi0 = p.SPI0.constrain(&mut sysctl.apb2);
 
+    writeln!(stdout, "[lcd] claiming a DMA channel").unwrap();
+    let lcd_dma = status::check(dma::claim(dma_select::SSI0_TX_REQ), fault::DMA);
+
     writeln!(stdout, "[lcd] creating lcd instance").unwrap();
-    let mut lcd = lcd::Lcd::new(dmac, DmacChannel::Channel0, spi0, 3, 2, 3);
+    let mut lcd = lcd::Lcd::new(dmac, lcd_dma, spi0, 3, 2, 3);
 
     writeln!(stdout, "[lcd] flushing initial config").unwrap();
     lcd.init(&clock);
+    status::check(baud::keep_spi0_rate(lcd::CLOCK_RATE), fault::LCD);
 
     writeln!(stdout, "[lcd] clearing the screen to {:04x}", &COLOR).unwrap();
     lcd.set_image(unsafe { &FRAME.image });